@@ -0,0 +1,47 @@
+use lodtree::coords::OctVec;
+use lodtree::{Tree, UpdateMode};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// moves a target along a line through the tree, updating until the tree is done each step
+fn move_target(tree: &mut Tree<(), OctVec>, depth: u8) {
+    let cmax = 1u64 << depth;
+    for step in 0..8 {
+        let coord = cmax * step / 8;
+        let target = OctVec::new(coord, cmax / 2, coord, depth);
+        while tree.prepare_update(&[target], 2, &mut |_| ()) {
+            tree.do_update();
+            tree.complete_update();
+        }
+    }
+}
+
+pub fn bench_balanced_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("balanced update");
+    group.significance_level(0.1).sample_size(10);
+
+    for mode in [UpdateMode::OneLevel, UpdateMode::SinglePass] {
+        for balanced in [false, true] {
+            for depth in [4u8, 6, 8].iter() {
+                let name = format!(
+                    "{:?}/{}",
+                    mode,
+                    if balanced { "balanced" } else { "unbalanced" }
+                );
+                group.bench_with_input(BenchmarkId::new(name, depth), depth, |b, &depth| {
+                    b.iter(|| {
+                        let mut tree = Tree::<(), OctVec>::new(0);
+                        tree.set_update_mode(mode);
+                        tree.set_balanced(balanced);
+                        move_target(&mut tree, depth);
+                        black_box(tree);
+                    });
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_balanced_update);
+criterion_main!(benches);
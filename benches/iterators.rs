@@ -21,7 +21,7 @@ fn generate_area_bounds(rng: &mut SmallRng, depth:u8) -> (OctVec, OctVec) {
         rng.gen_range(min.z + 1..cmax),
         depth,
     );
-    return (min, max);
+    (min, max)
 }
 
 struct ChuChunk {
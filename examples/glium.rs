@@ -61,7 +61,7 @@ fn make_shaders(display: &Display) -> Program {
     }
     )
     .unwrap();
-    return program;
+    program
 }
 
 #[derive(Copy, Clone)]
@@ -82,7 +82,7 @@ impl RenderContext {
     pub fn new(event_loop: &EventLoop<()>) -> Self {
         let wb = glutin::window::WindowBuilder::new().with_title("Quadtree demo");
         let cb = glutin::ContextBuilder::new().with_vsync(true);
-        let display = Display::new(wb, cb, &event_loop).unwrap();
+        let display = Display::new(wb, cb, event_loop).unwrap();
         // make a vertex buffer
         // we'll reuse it as we only need to draw one quad multiple times anyway
         let vertex_buffer = {
@@ -109,7 +109,7 @@ impl RenderContext {
         let index_buffer = IndexBuffer::new(
             &display,
             PrimitiveType::TrianglesList,
-            &[0 as u16, 1, 2, 1, 2, 3],
+            &[0_u16, 1, 2, 1, 2, 3],
         )
         .unwrap();
 
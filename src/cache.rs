@@ -0,0 +1,786 @@
+//! Contains the ChunkCache trait, which decides which chunks removed from the tree are kept around for later,
+//! and the caches that come with the crate.
+//!
+//! LruCache is what Tree::new uses, FifoCache, CostAwareCache and WeightedCache can be picked with Tree::with_cache.
+
+use crate::traits::LodVec;
+use std::collections::{BTreeMap, HashMap};
+
+/// trait for the cache of chunks that were removed from the tree.
+/// When a chunk is removed from the tree, it's put in the cache, and when a chunk is added, the cache is checked first.
+///
+/// The cache decides which chunks to evict when it's full, these are passed to the evict function, and end up in chunks_to_delete.
+pub trait ChunkCache<C: Sized, L: LodVec> {
+    /// puts a chunk in the cache, replacing any chunk that was at the same position.
+    /// Chunks that don't fit in the cache anymore, including a replaced one, are passed to evict.
+    fn insert(&mut self, position: L, chunk: C, evict: &mut dyn FnMut(L, C));
+
+    /// takes a chunk out of the cache for good, if it's in it, such as to add it back to the tree
+    fn remove(&mut self, position: L) -> Option<C>;
+
+    /// removes a chunk from the cache because it's outdated, such as after an edit.
+    /// Unlike remove, this also removes the chunk from anywhere else the cache keeps chunks, like the store of a TieredCache.
+    /// Returns the chunk if it was in the cache itself.
+    ///
+    /// The default is the same as remove.
+    #[inline]
+    fn evict(&mut self, position: L) -> Option<C> {
+        self.remove(position)
+    }
+
+    /// gets a chunk in the cache, if it's in it
+    fn get(&self, position: L) -> Option<&C>;
+
+    /// gets a mutable chunk in the cache, if it's in it.
+    /// This counts as using the chunk, for caches where that matters
+    fn get_mut(&mut self, position: L) -> Option<&mut C>;
+
+    /// number of chunks in the cache
+    fn len(&self) -> usize;
+
+    /// wether the cache is empty
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// max size of the cache, which is the max number of chunks unless the cache says otherwise
+    fn size(&self) -> usize;
+
+    /// sets the max size of the cache.
+    /// If there are more chunks in it, they are evicted on the next insert
+    fn set_size(&mut self, size: usize);
+
+    /// max number of chunks in the cache, if it's known, used to preallocate memory
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        Some(self.size())
+    }
+
+    /// iterates over every chunk in the cache and it's position, in the order they would be evicted in
+    fn iter(&self) -> Box<dyn Iterator<Item = (L, &C)> + '_>;
+
+    /// calls the function for every chunk in the cache, in the order they would be evicted in
+    #[inline]
+    fn for_each<'a>(&'a self, func: &mut dyn FnMut(L, &'a C)) {
+        for (position, chunk) in self.iter() {
+            func(position, chunk);
+        }
+    }
+
+    /// takes all chunks out of the cache, in the order they would be evicted in
+    fn drain(&mut self, func: &mut dyn FnMut(L, C));
+
+    /// shrinks the internal buffers to fit
+    #[inline]
+    fn shrink_to_fit(&mut self) {}
+
+    /// called when chunks are outdated, such as by Tree::shift_origin and Tree::prepare_edit, with a function that tells wether the chunk at a position is outdated.
+    /// The tree takes outdated chunks out of the cache itself, so this is for chunks the cache keeps somewhere else, like the store of a TieredCache.
+    ///
+    /// The default does nothing.
+    #[inline]
+    fn invalidate(&mut self, _is_outdated: &mut dyn FnMut(L) -> bool) {}
+
+    /// called when the positions of chunks move, such as by Tree::shift_origin, with a function that gives the new position of the chunk at a position,
+    /// or none if it's outdated.
+    /// Like invalidate, the tree moves the chunks in the cache itself, so this is for chunks the cache keeps somewhere else.
+    ///
+    /// The default invalidates everything, as it's all at the wrong position.
+    #[inline]
+    fn shift(&mut self, _new_position: &mut dyn FnMut(L) -> Option<L>) {
+        self.invalidate(&mut |_| true);
+    }
+}
+
+// chunks ordered by a key, so the one with the lowest key can be evicted first
+// the key is kept next to the chunk, so removing a chunk also removes it from the order, and no stale entries are left
+#[derive(Clone, Debug)]
+struct OrderedChunks<C: Sized, L: LodVec, K: Ord + Copy> {
+    chunks: HashMap<L, (C, K)>,
+    order: BTreeMap<K, L>,
+}
+
+impl<C: Sized, L: LodVec, K: Ord + Copy> OrderedChunks<C, L, K> {
+    fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    fn insert(&mut self, position: L, chunk: C, key: K) -> Option<(C, K)> {
+        let old = self.remove(position);
+        self.chunks.insert(position, (chunk, key));
+        self.order.insert(key, position);
+        old
+    }
+
+    #[inline]
+    fn remove(&mut self, position: L) -> Option<(C, K)> {
+        let (chunk, key) = self.chunks.remove(&position)?;
+        self.order.remove(&key);
+        Some((chunk, key))
+    }
+
+    #[inline]
+    fn reorder(&mut self, position: L, key: K) {
+        if let Some((_, old_key)) = self.chunks.get_mut(&position) {
+            self.order.remove(old_key);
+            *old_key = key;
+            self.order.insert(key, position);
+        }
+    }
+
+    #[inline]
+    fn pop_first(&mut self) -> Option<(L, C, K)> {
+        let (_, position) = self.order.pop_first()?;
+        let (chunk, key) = self.chunks.remove(&position)?;
+        Some((position, chunk, key))
+    }
+
+    // evicts chunks until there are at most size chunks
+    #[inline]
+    fn evict_to(&mut self, size: usize, evict: &mut dyn FnMut(L, C)) {
+        while self.chunks.len() > size {
+            match self.pop_first() {
+                Some((position, chunk, _)) => evict(position, chunk),
+                None => break,
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (L, &C)> + '_ {
+        self.order.values().filter_map(move |position| {
+            self.chunks
+                .get(position)
+                .map(|(chunk, _)| (*position, chunk))
+        })
+    }
+
+    fn drain(&mut self, func: &mut dyn FnMut(L, C)) {
+        while let Some((position, chunk, _)) = self.pop_first() {
+            func(position, chunk);
+        }
+    }
+}
+
+/// Cache that evicts the least recently used chunk first.
+/// A chunk is used when it's put in the cache, or with get_mut.
+#[derive(Clone, Debug)]
+pub struct LruCache<C: Sized, L: LodVec> {
+    chunks: OrderedChunks<C, L, u64>,
+    size: usize,
+    time: u64,
+}
+
+impl<C: Sized, L: LodVec> LruCache<C, L> {
+    /// creates a new cache, that holds at most size chunks
+    pub fn new(size: usize) -> Self {
+        Self {
+            chunks: OrderedChunks::new(),
+            size,
+            time: 0,
+        }
+    }
+}
+
+impl<C: Sized, L: LodVec> Default for LruCache<C, L> {
+    /// creates a new cache that can't hold any chunks
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<C: Sized, L: LodVec> ChunkCache<C, L> for LruCache<C, L> {
+    #[inline]
+    fn insert(&mut self, position: L, chunk: C, evict: &mut dyn FnMut(L, C)) {
+        // a cache without room doesn't hold on to anything, so skip it entirely
+        if self.size == 0 {
+            self.chunks.drain(evict);
+            evict(position, chunk);
+            return;
+        }
+
+        self.time += 1;
+        if let Some((old, _)) = self.chunks.insert(position, chunk, self.time) {
+            evict(position, old);
+        }
+        self.chunks.evict_to(self.size, evict);
+    }
+
+    #[inline]
+    fn remove(&mut self, position: L) -> Option<C> {
+        self.chunks.remove(position).map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn get(&self, position: L) -> Option<&C> {
+        self.chunks.chunks.get(&position).map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        self.time += 1;
+        self.chunks.reorder(position, self.time);
+        self.chunks
+            .chunks
+            .get_mut(&position)
+            .map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.chunks.chunks.len()
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    fn set_size(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (L, &C)> + '_> {
+        Box::new(self.chunks.iter())
+    }
+
+    fn drain(&mut self, func: &mut dyn FnMut(L, C)) {
+        self.chunks.drain(func);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.chunks.chunks.shrink_to_fit();
+    }
+}
+
+/// Cache that evicts the chunk that was put in the cache first.
+/// Unlike LruCache, using a chunk with get_mut or putting it in the cache again doesn't move it back.
+#[derive(Clone, Debug)]
+pub struct FifoCache<C: Sized, L: LodVec> {
+    chunks: OrderedChunks<C, L, u64>,
+    size: usize,
+    time: u64,
+}
+
+impl<C: Sized, L: LodVec> FifoCache<C, L> {
+    /// creates a new cache, that holds at most size chunks
+    pub fn new(size: usize) -> Self {
+        Self {
+            chunks: OrderedChunks::new(),
+            size,
+            time: 0,
+        }
+    }
+}
+
+impl<C: Sized, L: LodVec> Default for FifoCache<C, L> {
+    /// creates a new cache that can't hold any chunks
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<C: Sized, L: LodVec> ChunkCache<C, L> for FifoCache<C, L> {
+    #[inline]
+    fn insert(&mut self, position: L, chunk: C, evict: &mut dyn FnMut(L, C)) {
+        // a cache without room doesn't hold on to anything, so skip it entirely
+        if self.size == 0 {
+            self.chunks.drain(evict);
+            evict(position, chunk);
+            return;
+        }
+
+        // keep the place of a chunk that's already in the cache
+        let time = match self.chunks.chunks.get(&position) {
+            Some((_, time)) => *time,
+            None => {
+                self.time += 1;
+                self.time
+            }
+        };
+
+        if let Some((old, _)) = self.chunks.insert(position, chunk, time) {
+            evict(position, old);
+        }
+        self.chunks.evict_to(self.size, evict);
+    }
+
+    #[inline]
+    fn remove(&mut self, position: L) -> Option<C> {
+        self.chunks.remove(position).map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn get(&self, position: L) -> Option<&C> {
+        self.chunks.chunks.get(&position).map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        self.chunks
+            .chunks
+            .get_mut(&position)
+            .map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.chunks.chunks.len()
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    fn set_size(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (L, &C)> + '_> {
+        Box::new(self.chunks.iter())
+    }
+
+    fn drain(&mut self, func: &mut dyn FnMut(L, C)) {
+        self.chunks.drain(func);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.chunks.chunks.shrink_to_fit();
+    }
+}
+
+/// Cache that evicts the chunk that's cheapest to make again first, and the least recently used one if they cost the same.
+///
+/// The cost is given by a function of the position and chunk, called when the chunk is put in the cache.
+/// As an example, a cache that evicts coarse lods first, as those are cheap to make again compared to the area they cover:
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::QuadVec;
+/// # struct Chunk {}
+/// let cache = CostAwareCache::new(64, |position: QuadVec, _: &Chunk| position.depth as u64);
+/// let mut tree = Tree::<Chunk, QuadVec, _>::with_cache(cache);
+/// ```
+#[derive(Clone)]
+pub struct CostAwareCache<C: Sized, L: LodVec, F: Fn(L, &C) -> u64> {
+    chunks: OrderedChunks<C, L, (u64, u64)>,
+    size: usize,
+    time: u64,
+    cost: F,
+}
+
+impl<C: Sized, L: LodVec, F: Fn(L, &C) -> u64> CostAwareCache<C, L, F> {
+    /// creates a new cache, that holds at most size chunks
+    /// # Args
+    /// * `size` The max number of chunks in the cache
+    /// * `cost` How expensive a chunk is to make again, chunks with a lower cost are evicted first
+    pub fn new(size: usize, cost: F) -> Self {
+        Self {
+            chunks: OrderedChunks::new(),
+            size,
+            time: 0,
+            cost,
+        }
+    }
+}
+
+impl<C: Sized, L: LodVec, F: Fn(L, &C) -> u64> std::fmt::Debug for CostAwareCache<C, L, F>
+where
+    C: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CostAwareCache")
+            .field("chunks", &self.chunks)
+            .field("size", &self.size)
+            .field("time", &self.time)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Sized, L: LodVec, F: Fn(L, &C) -> u64> ChunkCache<C, L> for CostAwareCache<C, L, F> {
+    #[inline]
+    fn insert(&mut self, position: L, chunk: C, evict: &mut dyn FnMut(L, C)) {
+        // a cache without room doesn't hold on to anything, so skip it entirely
+        if self.size == 0 {
+            self.chunks.drain(evict);
+            evict(position, chunk);
+            return;
+        }
+
+        self.time += 1;
+        let cost = (self.cost)(position, &chunk);
+        if let Some((old, _)) = self.chunks.insert(position, chunk, (cost, self.time)) {
+            evict(position, old);
+        }
+        self.chunks.evict_to(self.size, evict);
+    }
+
+    #[inline]
+    fn remove(&mut self, position: L) -> Option<C> {
+        self.chunks.remove(position).map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn get(&self, position: L) -> Option<&C> {
+        self.chunks.chunks.get(&position).map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        self.time += 1;
+        if let Some((_, (cost, _))) = self.chunks.chunks.get(&position) {
+            let cost = *cost;
+            self.chunks.reorder(position, (cost, self.time));
+        }
+        self.chunks
+            .chunks
+            .get_mut(&position)
+            .map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.chunks.chunks.len()
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    fn set_size(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (L, &C)> + '_> {
+        Box::new(self.chunks.iter())
+    }
+
+    fn drain(&mut self, func: &mut dyn FnMut(L, C)) {
+        self.chunks.drain(func);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.chunks.chunks.shrink_to_fit();
+    }
+}
+
+/// Cache that's limited by the total weight of the chunks in it, such as the number of bytes they take up, instead of the number of chunks.
+/// Evicts the least recently used chunk first, like LruCache, until the total weight fits in the size of the cache.
+///
+/// The weight is given by a function of the position and chunk, called when the chunk is put in the cache.
+/// For this cache, size and set_size are the max total weight.
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::OctVec;
+/// struct Chunk {
+///     voxels: Vec<u8>,
+/// }
+///
+/// // keep at most 64 MiB of voxels around
+/// let cache = WeightedCache::new(64 << 20, |_: OctVec, chunk: &Chunk| chunk.voxels.len());
+/// let mut tree = Tree::<Chunk, OctVec, _>::with_cache(cache);
+/// ```
+#[derive(Clone)]
+pub struct WeightedCache<C: Sized, L: LodVec, F: Fn(L, &C) -> usize> {
+    // the weight is part of the key so it's known when a chunk is removed,
+    // the time is unique so it's still only ordered by time
+    chunks: OrderedChunks<C, L, (u64, usize)>,
+    size: usize,
+    weight: usize,
+    time: u64,
+    weigh: F,
+}
+
+impl<C: Sized, L: LodVec, F: Fn(L, &C) -> usize> WeightedCache<C, L, F> {
+    /// creates a new cache, that holds chunks up to the given total weight
+    /// # Args
+    /// * `size` The max total weight of the chunks in the cache
+    /// * `weigh` The weight of a chunk, such as the number of bytes it takes up
+    pub fn new(size: usize, weigh: F) -> Self {
+        Self {
+            chunks: OrderedChunks::new(),
+            size,
+            weight: 0,
+            time: 0,
+            weigh,
+        }
+    }
+
+    /// total weight of the chunks currently in the cache
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+}
+
+impl<C: Sized, L: LodVec, F: Fn(L, &C) -> usize> std::fmt::Debug for WeightedCache<C, L, F>
+where
+    C: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeightedCache")
+            .field("chunks", &self.chunks)
+            .field("size", &self.size)
+            .field("weight", &self.weight)
+            .field("time", &self.time)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Sized, L: LodVec, F: Fn(L, &C) -> usize> ChunkCache<C, L> for WeightedCache<C, L, F> {
+    #[inline]
+    fn insert(&mut self, position: L, chunk: C, evict: &mut dyn FnMut(L, C)) {
+        // a cache without room doesn't hold on to anything, so skip it entirely
+        if self.size == 0 {
+            self.chunks.drain(evict);
+            self.weight = 0;
+            evict(position, chunk);
+            return;
+        }
+
+        self.time += 1;
+        let weight = (self.weigh)(position, &chunk);
+        self.weight += weight;
+        if let Some((old, (_, old_weight))) =
+            self.chunks.insert(position, chunk, (self.time, weight))
+        {
+            self.weight -= old_weight;
+            evict(position, old);
+        }
+
+        // a chunk heavier than the entire cache is evicted right away
+        while self.weight > self.size {
+            match self.chunks.pop_first() {
+                Some((position, chunk, (_, weight))) => {
+                    self.weight -= weight;
+                    evict(position, chunk);
+                }
+                None => break,
+            }
+        }
+    }
+
+    #[inline]
+    fn remove(&mut self, position: L) -> Option<C> {
+        let (chunk, (_, weight)) = self.chunks.remove(position)?;
+        self.weight -= weight;
+        Some(chunk)
+    }
+
+    #[inline]
+    fn get(&self, position: L) -> Option<&C> {
+        self.chunks.chunks.get(&position).map(|(chunk, _)| chunk)
+    }
+
+    /// gets a mutable chunk in the cache, if it's in it.
+    /// The chunk is not weighed again, so it keeps the weight it had when it was put in the cache
+    #[inline]
+    fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        self.time += 1;
+        if let Some((_, (_, weight))) = self.chunks.chunks.get(&position) {
+            let weight = *weight;
+            self.chunks.reorder(position, (self.time, weight));
+        }
+        self.chunks
+            .chunks
+            .get_mut(&position)
+            .map(|(chunk, _)| chunk)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.chunks.chunks.len()
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    fn set_size(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (L, &C)> + '_> {
+        Box::new(self.chunks.iter())
+    }
+
+    fn drain(&mut self, func: &mut dyn FnMut(L, C)) {
+        self.weight = 0;
+        self.chunks.drain(func);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.chunks.chunks.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::coords::*;
+    use crate::tree::*;
+
+    // puts the chunks in the cache, and returns the positions that got evicted
+    fn fill<K: ChunkCache<u32, QuadVec>>(cache: &mut K, chunks: &[u64]) -> Vec<u64> {
+        let mut evicted = Vec::new();
+        for x in chunks {
+            cache.insert(QuadVec::new(*x, 0, 4), *x as u32, &mut |pos, chunk| {
+                assert_eq!(pos.x as u32, chunk);
+                evicted.push(pos.x);
+            });
+        }
+        evicted
+    }
+
+    #[test]
+    fn lru_and_fifo() {
+        let mut lru = LruCache::new(3);
+        let mut fifo = FifoCache::new(3);
+        assert_eq!(fill(&mut lru, &[0, 1, 2]), [0u64; 0]);
+        assert_eq!(fill(&mut fifo, &[0, 1, 2]), [0u64; 0]);
+
+        // using 0 keeps it around in the lru cache, but not in the fifo cache
+        assert!(lru.get_mut(QuadVec::new(0, 0, 4)).is_some());
+        assert!(fifo.get_mut(QuadVec::new(0, 0, 4)).is_some());
+        assert_eq!(fill(&mut lru, &[3]), [1]);
+        assert_eq!(fill(&mut fifo, &[3]), [0]);
+
+        // taking a chunk out doesn't leave anything behind, so the next eviction is the right one
+        assert_eq!(lru.remove(QuadVec::new(2, 0, 4)), Some(2));
+        assert_eq!(fill(&mut lru, &[2, 4]), [0]);
+        assert_eq!(lru.len(), 3);
+
+        // putting the same chunk in again replaces it
+        assert_eq!(fill(&mut fifo, &[3]), [3]);
+        assert_eq!(fifo.len(), 3);
+
+        // and everything comes out in the order it would be evicted in
+        let mut order = Vec::new();
+        lru.for_each(&mut |pos, _| order.push(pos.x));
+        assert_eq!(order, [3, 2, 4]);
+        assert!(lru.iter().map(|(pos, _)| pos.x).eq(order));
+
+        // shrinking evicts on the next insert
+        lru.set_size(1);
+        assert_eq!(fill(&mut lru, &[5]), [3, 2, 4]);
+
+        let mut drained = Vec::new();
+        lru.drain(&mut |pos, _| drained.push(pos.x));
+        assert_eq!(drained, [5]);
+        assert!(lru.is_empty());
+    }
+
+    #[test]
+    fn cost_aware() {
+        // higher x costs more
+        let mut cache = CostAwareCache::new(2, |pos: QuadVec, _: &u32| pos.x);
+        assert_eq!(fill(&mut cache, &[5, 1, 3]), [1]);
+        assert_eq!(fill(&mut cache, &[7, 0]), [3, 0]);
+        assert_eq!(cache.get(QuadVec::new(5, 0, 4)), Some(&5));
+    }
+
+    #[test]
+    fn weighted() {
+        // the weight of a chunk is its x
+        let mut cache = WeightedCache::new(10, |pos: QuadVec, _: &u32| pos.x as usize);
+        assert_eq!(fill(&mut cache, &[4, 5, 1]), [0u64; 0]);
+        assert_eq!(cache.weight(), 10);
+
+        // using a chunk keeps it around
+        assert!(cache.get_mut(QuadVec::new(4, 0, 4)).is_some());
+        assert_eq!(fill(&mut cache, &[3]), [5]);
+        assert_eq!(cache.weight(), 8);
+
+        // replacing a chunk doesn't count the old weight
+        assert_eq!(fill(&mut cache, &[3]), [3]);
+        assert_eq!(cache.weight(), 8);
+
+        // a chunk that's too heavy for the entire cache doesn't stay in it
+        assert_eq!(fill(&mut cache, &[11]), [1, 4, 3, 11]);
+        assert_eq!(cache.weight(), 0);
+        assert!(cache.is_empty());
+
+        assert_eq!(fill(&mut cache, &[2]), [0u64; 0]);
+        assert_eq!(cache.remove(QuadVec::new(2, 0, 4)), Some(2));
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn zero_size() {
+        // chunks go straight through a cache without room
+        let chunks = [3, 1, 3, 2];
+        assert_eq!(fill(&mut LruCache::new(0), &chunks), chunks);
+        assert_eq!(fill(&mut FifoCache::new(0), &chunks), chunks);
+        assert_eq!(
+            fill(&mut CostAwareCache::new(0, |_, _: &u32| 0), &chunks),
+            chunks
+        );
+        assert_eq!(
+            fill(&mut WeightedCache::new(0, |_, _: &u32| 0), &chunks),
+            chunks
+        );
+
+        // including the ones that were in it before it was shrunk
+        let mut lru = LruCache::new(2);
+        assert_eq!(fill(&mut lru, &[1, 2]), [0u64; 0]);
+        lru.set_size(0);
+        assert_eq!(fill(&mut lru, &[3]), [1, 2, 3]);
+        assert!(lru.is_empty());
+
+        // so a tree with it never holds a chunk in the cache
+        let mut tree = Tree::<(), QuadVec>::new(0);
+        let mut deleted = 0;
+        for target in [QuadVec::new(0, 0, 3), QuadVec::new(7, 7, 3)] {
+            while tree.prepare_update(&[target], 0, &mut |_| ()) {
+                tree.do_update();
+                assert_eq!(tree.get_num_cached_chunks(), 0);
+                deleted += tree.get_num_chunks_to_delete();
+                tree.complete_update();
+            }
+        }
+        assert!(deleted > 0);
+    }
+
+    #[test]
+    fn tree_with_cache() {
+        let mut tree = Tree::<(), QuadVec, _>::with_cache(FifoCache::new(4));
+
+        // go deep in one corner, then another, so the first corner ends up in the cache
+        let mut deleted = 0;
+        for target in [QuadVec::new(0, 0, 3), QuadVec::new(7, 7, 3)] {
+            while tree.prepare_update(&[target], 0, &mut |_| ()) {
+                tree.do_update();
+                deleted += tree.get_num_chunks_to_delete();
+                tree.complete_update();
+            }
+        }
+
+        // and going back to the first corner gets the chunks from the cache
+        let mut created = 0;
+        let mut added = 0;
+        while tree.prepare_update(&[QuadVec::new(0, 0, 3)], 0, &mut |_| created += 1) {
+            added += tree.get_num_chunks_to_add();
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        assert!(deleted > 0);
+        assert_eq!(created + 4, added);
+    }
+}
@@ -1,12 +1,32 @@
 //! Contains coordinate structs, QuadVec for quadtrees, and OctVec for octrees, as well as their LodVec implementation
+//! LodVecN works the same way for any number of dimensions, and CubeFaceVec is for the six faces of a cube sphere.
 
-use crate::traits::LodVec;
+use crate::traits::{GridVec, LodVec};
 use std::cmp::Ordering;
 
+// distance between two nodes along one axis, in nodes at the deepest depth of the two, or 0 if they overlap
+#[inline]
+fn axis_distance(a: u64, a_depth: u8, b: u64, b_depth: u8) -> u64 {
+    let depth = a_depth.max(b_depth);
+
+    // first and last node at that depth, on this axis
+    let a_min = a << (depth - a_depth);
+    let a_max = ((a + 1) << (depth - a_depth)) - 1;
+    let b_min = b << (depth - b_depth);
+    let b_max = ((b + 1) << (depth - b_depth)) - 1;
+
+    if a_max < b_min {
+        b_min - a_max
+    } else {
+        a_min.saturating_sub(b_max)
+    }
+}
+
 /// A Lod Vector for use in a quadtree.
 /// It subdivides into 4 children of equal size.
 //#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Hash)]
 #[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct QuadVec {
     /// x position in the quadtree.
     pub x: u64,
@@ -86,6 +106,19 @@ impl QuadVec {
     pub fn get_size(self) -> f64 {
         1.0 / (1 << self.depth) as f64
     }
+
+    /// offsets to the neighbour in each direction, as used by LodVec::neighbor.
+    /// The faces come first (-x, +x, -y, +y), then the corners.
+    pub const NEIGHBOR_OFFSETS: [(i64, i64); 8] = [
+        (-1, 0),
+        (1, 0),
+        (0, -1),
+        (0, 1),
+        (-1, -1),
+        (1, -1),
+        (-1, 1),
+        (1, 1),
+    ];
 }
 
 impl LodVec for QuadVec {
@@ -119,6 +152,21 @@ impl LodVec for QuadVec {
         }
     }
 
+    #[inline]
+    fn depth(self) -> u8 {
+        self.depth
+    }
+
+    #[inline]
+    fn get_parent(self) -> Option<Self> {
+        let depth = self.depth.checked_sub(1)?;
+        Some(Self {
+            x: self.x >> 1,
+            y: self.y >> 1,
+            depth,
+        })
+    }
+
     #[inline]
     fn can_subdivide(self, node: Self, detail: u32) -> bool {
         let detail = detail as u64;
@@ -192,11 +240,53 @@ impl LodVec for QuadVec {
         // and check
         self.x == x && self.y == y
     }
+
+    const NUM_NEIGHBORS: u32 = 8;
+    const NUM_FACE_NEIGHBORS: u32 = 4;
+
+    #[inline]
+    fn distance(self, other: Self) -> u64 {
+        axis_distance(self.x, self.depth, other.x, other.depth).max(axis_distance(
+            self.y,
+            self.depth,
+            other.y,
+            other.depth,
+        ))
+    }
+
+    #[inline]
+    fn neighbor(self, direction: u32) -> Option<Self> {
+        let (dx, dy) = *Self::NEIGHBOR_OFFSETS.get(direction as usize)?;
+
+        // the neighbour needs to stay inside the root node
+        let size = 1 << self.depth;
+        let x = self.x.checked_add_signed(dx).filter(|x| *x < size)?;
+        let y = self.y.checked_add_signed(dy).filter(|y| *y < size)?;
+
+        Some(Self {
+            x,
+            y,
+            depth: self.depth,
+        })
+    }
+
+    #[inline]
+    fn opposite_direction(self, direction: u32) -> Option<u32> {
+        self.neighbor(direction)?;
+
+        // the direction with the opposite offset
+        let (dx, dy) = Self::NEIGHBOR_OFFSETS[direction as usize];
+        Self::NEIGHBOR_OFFSETS
+            .iter()
+            .position(|x| *x == (-dx, -dy))
+            .map(|x| x as u32)
+    }
 }
 
 /// A Lod Vector for use in an octree.
 /// It subdivides into 8 children of equal size.
 #[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OctVec {
     /// x position in the octree.
     pub x: u64,
@@ -285,6 +375,40 @@ impl OctVec {
     pub fn get_size(self) -> f64 {
         1.0 / (1 << self.depth) as f64
     }
+
+    /// offsets to the neighbour in each direction, as used by LodVec::neighbor.
+    /// The faces come first (-x, +x, -y, +y, -z, +z), then the edges, then the corners.
+    pub const NEIGHBOR_OFFSETS: [(i64, i64, i64); 26] = [
+        // faces
+        (-1, 0, 0),
+        (1, 0, 0),
+        (0, -1, 0),
+        (0, 1, 0),
+        (0, 0, -1),
+        (0, 0, 1),
+        // edges
+        (-1, -1, 0),
+        (1, -1, 0),
+        (-1, 1, 0),
+        (1, 1, 0),
+        (-1, 0, -1),
+        (1, 0, -1),
+        (-1, 0, 1),
+        (1, 0, 1),
+        (0, -1, -1),
+        (0, 1, -1),
+        (0, -1, 1),
+        (0, 1, 1),
+        // corners
+        (-1, -1, -1),
+        (1, -1, -1),
+        (-1, 1, -1),
+        (1, 1, -1),
+        (-1, -1, 1),
+        (1, -1, 1),
+        (-1, 1, 1),
+        (1, 1, 1),
+    ];
 }
 
 impl LodVec for OctVec {
@@ -322,6 +446,22 @@ impl LodVec for OctVec {
         }
     }
 
+    #[inline]
+    fn depth(self) -> u8 {
+        self.depth
+    }
+
+    #[inline]
+    fn get_parent(self) -> Option<Self> {
+        let depth = self.depth.checked_sub(1)?;
+        Some(Self {
+            x: self.x >> 1,
+            y: self.y >> 1,
+            z: self.z >> 1,
+            depth,
+        })
+    }
+
     #[inline]
     fn can_subdivide(self, node: Self, detail: u32) -> bool {
         let detail = detail as u64;
@@ -411,4 +551,1289 @@ impl LodVec for OctVec {
         // and check
         self.x == x && self.y == y && self.z == z
     }
+
+    const NUM_NEIGHBORS: u32 = 26;
+    const NUM_FACE_NEIGHBORS: u32 = 6;
+
+    #[inline]
+    fn distance(self, other: Self) -> u64 {
+        axis_distance(self.x, self.depth, other.x, other.depth)
+            .max(axis_distance(self.y, self.depth, other.y, other.depth))
+            .max(axis_distance(self.z, self.depth, other.z, other.depth))
+    }
+
+    #[inline]
+    fn neighbor(self, direction: u32) -> Option<Self> {
+        let (dx, dy, dz) = *Self::NEIGHBOR_OFFSETS.get(direction as usize)?;
+
+        // the neighbour needs to stay inside the root node
+        let size = 1 << self.depth;
+        let x = self.x.checked_add_signed(dx).filter(|x| *x < size)?;
+        let y = self.y.checked_add_signed(dy).filter(|y| *y < size)?;
+        let z = self.z.checked_add_signed(dz).filter(|z| *z < size)?;
+
+        Some(Self {
+            x,
+            y,
+            z,
+            depth: self.depth,
+        })
+    }
+
+    #[inline]
+    fn opposite_direction(self, direction: u32) -> Option<u32> {
+        self.neighbor(direction)?;
+
+        // the direction with the opposite offset
+        let (dx, dy, dz) = Self::NEIGHBOR_OFFSETS[direction as usize];
+        Self::NEIGHBOR_OFFSETS
+            .iter()
+            .position(|x| *x == (-dx, -dy, -dz))
+            .map(|x| x as u32)
+    }
+}
+
+/// A Lod Vector with any number of dimensions, for use in a tree with 2^D children per node.
+/// D = 1 gives a binary tree, 2 and 3 behave the same as QuadVec and OctVec, and 4 can be used for space-time.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct LodVecN<const D: usize> {
+    /// position in the tree, on each axis.
+    pub pos: [u64; D],
+
+    /// lod depth in the tree.
+    /// this is limited, hence we use u8.
+    pub depth: u8,
+}
+
+impl<const D: usize> Default for LodVecN<D> {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+impl<const D: usize> PartialOrd for LodVecN<D> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.depth != other.depth {
+            return None;
+        }
+        if self.pos == other.pos {
+            return Some(Ordering::Equal);
+        }
+
+        if self.pos.iter().zip(other.pos.iter()).all(|(a, b)| a < b) {
+            return Some(Ordering::Less);
+        } else if self.pos.iter().zip(other.pos.iter()).all(|(a, b)| a > b) {
+            return Some(Ordering::Greater);
+        }
+        None
+    }
+}
+
+impl<const D: usize> LodVecN<D> {
+    /// creates a new vector from the raw coords.
+    /// # Args
+    /// * `pos` The position in the tree, on each axis. Allowed range scales with the depth (doubles as the depth increases by one)
+    /// * `depth` the lod depth the coord is at. This is soft limited at roughly 60, and the tree might behave weird if it gets higher
+    #[inline]
+    pub fn new(pos: [u64; D], depth: u8) -> Self {
+        debug_assert!(D >= 1 && D <= 16);
+        debug_assert!(pos.iter().all(|x| *x < (1 << depth)));
+        debug_assert!(depth <= 60);
+        Self { pos, depth }
+    }
+
+    /// creates a new vector from floating point coords.
+    /// mapped so that all zeros is the lowest corner and all ones is the highest corner.
+    /// # Args
+    /// * `coords` coords of the float vector, from 0 to 1
+    /// * `depth` The lod depth of the coord
+    #[inline]
+    pub fn from_float_coords(coords: [f64; D], depth: u8) -> Self {
+        // scaling factor due to the lod depth
+        let scale_factor = (1 << depth) as f64;
+
+        // and get the actual coord
+        Self {
+            pos: coords.map(|x| (x * scale_factor) as u64),
+            depth,
+        }
+    }
+
+    /// converts the coord into float coords.
+    /// Returns the coordinates of the lowest corner.
+    #[inline]
+    pub fn get_float_coords(self) -> [f64; D] {
+        // scaling factor to scale the coords down with
+        let scale_factor = 1.0 / (1 << self.depth) as f64;
+
+        self.pos.map(|x| x as f64 * scale_factor)
+    }
+
+    /// gets the size the chunk of this lod vector takes up, with the root taking up.
+    #[inline]
+    pub fn get_size(self) -> f64 {
+        1.0 / (1 << self.depth) as f64
+    }
+
+    /// gets the offset to the neighbour in the given direction, as used by LodVec::neighbor.
+    /// The faces come first (-x, +x, -y, +y, ...), then the directions moving along two axes, then three and so on.
+    /// This is the same order as QuadVec::NEIGHBOR_OFFSETS and OctVec::NEIGHBOR_OFFSETS.
+    pub fn get_neighbor_offset(direction: u32) -> [i64; D] {
+        debug_assert!(direction < Self::NUM_NEIGHBORS);
+        let mut direction = direction;
+
+        // go over the amount of axes that move
+        for moving in 1..=D as u32 {
+            // and which axes those are
+            for axes in (1u32..1 << D).filter(|x| x.count_ones() == moving) {
+                // every combination of directions on those axes, with the lowest axis changing fastest
+                let combinations = 1 << moving;
+                if direction >= combinations {
+                    direction -= combinations;
+                    continue;
+                }
+
+                let mut offset = [0; D];
+                let mut bit = 0;
+                for (axis, offset) in offset.iter_mut().enumerate() {
+                    if axes & (1 << axis) != 0 {
+                        *offset = if direction & (1 << bit) != 0 { 1 } else { -1 };
+                        bit += 1;
+                    }
+                }
+                return offset;
+            }
+        }
+
+        [0; D]
+    }
+}
+
+impl<const D: usize> LodVec for LodVecN<D> {
+    #[inline]
+    fn get_child(self, index: u32) -> Self {
+        debug_assert!(index < Self::NUM_CHILDREN);
+
+        // the positions, doubled in scale, and incremented with the bit for that axis
+        let mut pos = self.pos;
+        for (axis, x) in pos.iter_mut().enumerate() {
+            *x = (*x << 1) + ((index as u64 >> axis) & 1);
+        }
+
+        Self {
+            pos,
+            depth: self.depth + 1,
+        }
+    }
+
+    const NUM_CHILDREN: u32 = 1 << D;
+
+    #[inline]
+    fn root() -> Self {
+        Self {
+            pos: [0; D],
+            depth: 0,
+        }
+    }
+
+    #[inline]
+    fn depth(self) -> u8 {
+        self.depth
+    }
+
+    #[inline]
+    fn get_parent(self) -> Option<Self> {
+        let depth = self.depth.checked_sub(1)?;
+        Some(Self {
+            pos: self.pos.map(|x| x >> 1),
+            depth,
+        })
+    }
+
+    #[inline]
+    fn can_subdivide(self, node: Self, detail: u32) -> bool {
+        let detail = detail as u64;
+        // return early if the level of this chunk is too high
+        if node.depth >= self.depth {
+            return false;
+        }
+
+        // difference in lod level between the target and the node
+        let level_difference = self.depth - node.depth;
+
+        // check if the target is inside of the bounding box on every axis
+        node.pos.iter().zip(self.pos.iter()).all(|(node, target)| {
+            // minimum corner of the bounding box
+            let min = (node << (level_difference + 1))
+                .saturating_sub(((detail + 1) << level_difference) - (1 << level_difference));
+
+            // max as well
+            let max = (node << (level_difference + 1))
+                .saturating_add(((detail + 1) << level_difference) + (1 << level_difference));
+
+            // local position of the target, which is one lod level higher to allow more detail
+            let local = target << 1;
+
+            local >= min && local < max
+        })
+    }
+
+    #[inline]
+    fn is_inside_bounds(self, min: Self, max: Self, max_depth: u8) -> bool {
+        // get the lowest lod level
+        let level = self.depth.min(min.depth.min(max.depth));
+
+        // bring all coords to the lowest level
+        let self_difference = self.depth - level;
+        let min_difference = min.depth - level;
+        let max_difference = max.depth - level;
+
+        // then check if we are inside the AABB
+        self.depth <= max_depth
+            && (0..D).all(|axis| {
+                let x = self.pos[axis] >> self_difference;
+                x >= min.pos[axis] >> min_difference && x <= max.pos[axis] >> max_difference
+            })
+    }
+
+    #[inline]
+    fn contains_child_node(self, child: Self) -> bool {
+        // basically, move the child node up to this level and check if they're equal
+        let level_difference = child.depth - self.depth;
+
+        self.pos
+            .iter()
+            .zip(child.pos.iter())
+            .all(|(x, child)| *x == child >> level_difference)
+    }
+
+    const NUM_NEIGHBORS: u32 = 3u32.pow(D as u32) - 1;
+    const NUM_FACE_NEIGHBORS: u32 = 2 * D as u32;
+
+    #[inline]
+    fn distance(self, other: Self) -> u64 {
+        self.pos
+            .iter()
+            .zip(other.pos.iter())
+            .map(|(a, b)| axis_distance(*a, self.depth, *b, other.depth))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[inline]
+    fn neighbor(self, direction: u32) -> Option<Self> {
+        if direction >= Self::NUM_NEIGHBORS {
+            return None;
+        }
+        let offset = Self::get_neighbor_offset(direction);
+
+        // the neighbour needs to stay inside the root node
+        let size = 1 << self.depth;
+        let mut pos = self.pos;
+        for (x, offset) in pos.iter_mut().zip(offset) {
+            *x = x.checked_add_signed(offset).filter(|x| *x < size)?;
+        }
+
+        Some(Self {
+            pos,
+            depth: self.depth,
+        })
+    }
+
+    #[inline]
+    fn opposite_direction(self, direction: u32) -> Option<u32> {
+        self.neighbor(direction)?;
+
+        // the direction with the opposite offset
+        let opposite = Self::get_neighbor_offset(direction).map(|x| -x);
+        (0..Self::NUM_NEIGHBORS).find(|x| Self::get_neighbor_offset(*x) == opposite)
+    }
+}
+
+impl From<QuadVec> for LodVecN<2> {
+    #[inline]
+    fn from(vec: QuadVec) -> Self {
+        Self {
+            pos: [vec.x, vec.y],
+            depth: vec.depth,
+        }
+    }
+}
+
+impl From<LodVecN<2>> for QuadVec {
+    #[inline]
+    fn from(vec: LodVecN<2>) -> Self {
+        Self {
+            x: vec.pos[0],
+            y: vec.pos[1],
+            depth: vec.depth,
+        }
+    }
+}
+
+impl From<OctVec> for LodVecN<3> {
+    #[inline]
+    fn from(vec: OctVec) -> Self {
+        Self {
+            pos: [vec.x, vec.y, vec.z],
+            depth: vec.depth,
+        }
+    }
+}
+
+impl From<LodVecN<3>> for OctVec {
+    #[inline]
+    fn from(vec: LodVecN<3>) -> Self {
+        Self {
+            x: vec.pos[0],
+            y: vec.pos[1],
+            z: vec.pos[2],
+            depth: vec.depth,
+        }
+    }
+}
+
+impl<const D: usize> GridVec for LodVecN<D> {
+    type Region = [i64; D];
+
+    #[inline]
+    fn region_distance(a: Self::Region, b: Self::Region) -> u64 {
+        (0..D).map(|i| a[i].abs_diff(b[i])).max().unwrap_or(0)
+    }
+
+    fn for_each_region_around(
+        center: Self::Region,
+        radius: u32,
+        func: &mut dyn FnMut(Self::Region),
+    ) {
+        let radius = radius as i64;
+
+        // go over all offsets in the box around the center, with the first axis varying fastest
+        let mut offset = [-radius; D];
+        loop {
+            let mut region = center;
+            for i in 0..D {
+                region[i] = region[i].saturating_add(offset[i]);
+            }
+            func(region);
+
+            // and step to the next offset
+            let mut axis = 0;
+            loop {
+                if axis == D {
+                    return;
+                }
+                if offset[axis] < radius {
+                    offset[axis] += 1;
+                    break;
+                }
+                offset[axis] = -radius;
+                axis += 1;
+            }
+        }
+    }
+
+    #[inline]
+    fn project_to_region(self, from: Self::Region, to: Self::Region) -> Self {
+        // distance to the closest node in the other region, in nodes at our depth
+        let size = 1u64 << self.depth;
+        let mut distance = 0u64;
+        for i in 0..D {
+            let regions_between = to[i].abs_diff(from[i]).saturating_sub(1);
+            let to_edge = match to[i].cmp(&from[i]) {
+                Ordering::Greater => size - 1 - self.pos[i],
+                Ordering::Less => self.pos[i],
+                Ordering::Equal => 0,
+            };
+            distance = distance.max(regions_between.saturating_mul(size).saturating_add(to_edge));
+        }
+
+        // go up one depth for every doubling of the distance
+        let depth = self
+            .depth
+            .saturating_sub((u64::BITS - distance.leading_zeros()) as u8);
+        let shift = self.depth - depth;
+
+        // the closest node is on the edge of the other region that faces this one
+        let max = (1 << depth) - 1;
+        let mut pos = self.pos;
+        for i in 0..D {
+            pos[i] = match to[i].cmp(&from[i]) {
+                Ordering::Greater => 0,
+                Ordering::Less => max,
+                Ordering::Equal => pos[i] >> shift,
+            };
+        }
+
+        Self { pos, depth }
+    }
+
+    fn shift_origin(self, offset: Self::Region, depth: u8) -> Option<Self> {
+        let mut pos = self.pos;
+        for i in 0..D {
+            // offset in nodes at our own depth, i128 so it can't overflow
+            let offset = if self.depth >= depth {
+                (offset[i] as i128) << (self.depth - depth)
+            } else {
+                let scale = 1i128 << (depth - self.depth);
+                if (offset[i] as i128).rem_euclid(scale) != 0 {
+                    return None;
+                }
+                (offset[i] as i128).div_euclid(scale)
+            };
+
+            let moved = pos[i] as i128 - offset;
+            if moved < 0 || moved >= 1i128 << self.depth {
+                return None;
+            }
+            pos[i] = moved as u64;
+        }
+
+        Some(Self {
+            pos,
+            depth: self.depth,
+        })
+    }
+}
+
+impl GridVec for QuadVec {
+    type Region = [i64; 2];
+
+    #[inline]
+    fn region_distance(a: Self::Region, b: Self::Region) -> u64 {
+        LodVecN::<2>::region_distance(a, b)
+    }
+
+    #[inline]
+    fn for_each_region_around(
+        center: Self::Region,
+        radius: u32,
+        func: &mut dyn FnMut(Self::Region),
+    ) {
+        LodVecN::<2>::for_each_region_around(center, radius, func)
+    }
+
+    #[inline]
+    fn project_to_region(self, from: Self::Region, to: Self::Region) -> Self {
+        LodVecN::from(self).project_to_region(from, to).into()
+    }
+
+    #[inline]
+    fn shift_origin(self, offset: Self::Region, depth: u8) -> Option<Self> {
+        LodVecN::from(self)
+            .shift_origin(offset, depth)
+            .map(Into::into)
+    }
+}
+
+impl GridVec for OctVec {
+    type Region = [i64; 3];
+
+    #[inline]
+    fn region_distance(a: Self::Region, b: Self::Region) -> u64 {
+        LodVecN::<3>::region_distance(a, b)
+    }
+
+    #[inline]
+    fn for_each_region_around(
+        center: Self::Region,
+        radius: u32,
+        func: &mut dyn FnMut(Self::Region),
+    ) {
+        LodVecN::<3>::for_each_region_around(center, radius, func)
+    }
+
+    #[inline]
+    fn project_to_region(self, from: Self::Region, to: Self::Region) -> Self {
+        LodVecN::from(self).project_to_region(from, to).into()
+    }
+
+    #[inline]
+    fn shift_origin(self, offset: Self::Region, depth: u8) -> Option<Self> {
+        LodVecN::from(self)
+            .shift_origin(offset, depth)
+            .map(Into::into)
+    }
+}
+
+/// A Lod Vector for a cube sphere, such as a planet.
+/// The root is the entire sphere, which has the six faces of the cube as children, and each face subdivides like a quadtree.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CubeFaceVec {
+    /// face of the cube, from 0 to 5 in the order +x, -x, +y, -y, +z, -z.
+    /// The root, which holds all faces, uses CubeFaceVec::ROOT_FACE
+    pub face: u8,
+
+    /// position on the face, a depth of 0 is the entire face.
+    pub quad: QuadVec,
+}
+
+// normal axis, and the axes along the x and y of the quad on each face, as the axis index and sign
+const CUBE_FACES: [[(usize, f64); 3]; 6] = [
+    [(0, 1.0), (2, -1.0), (1, 1.0)],
+    [(0, -1.0), (2, 1.0), (1, 1.0)],
+    [(1, 1.0), (0, 1.0), (2, -1.0)],
+    [(1, -1.0), (0, 1.0), (2, 1.0)],
+    [(2, 1.0), (0, 1.0), (1, 1.0)],
+    [(2, -1.0), (0, -1.0), (1, 1.0)],
+];
+
+impl PartialOrd for CubeFaceVec {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.face != other.face {
+            return None;
+        }
+        self.quad.partial_cmp(&other.quad)
+    }
+}
+
+impl Default for CubeFaceVec {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+impl CubeFaceVec {
+    /// face used by the root node, which holds all six faces.
+    pub const ROOT_FACE: u8 = 6;
+
+    /// creates a new vector from a face and the position on that face.
+    /// # Args
+    /// * `face` The face of the cube, from 0 to 5 in the order +x, -x, +y, -y, +z, -z
+    /// * `quad` The position on that face
+    #[inline]
+    pub fn new(face: u8, quad: QuadVec) -> Self {
+        debug_assert!(face < Self::ROOT_FACE);
+        Self { face, quad }
+    }
+
+    /// creates a new vector from a direction from the center of the sphere.
+    /// The direction does not need to be normalized, but can't be zero.
+    /// # Args
+    /// * `x` x component of the direction
+    /// * `y` y component of the direction
+    /// * `z` z component of the direction
+    /// * `depth` The lod depth of the position on the face
+    pub fn from_direction(x: f64, y: f64, z: f64, depth: u8) -> Self {
+        let (face, u, v) = Self::cube_to_face([x, y, z]);
+
+        // keep the highest edge on the face
+        let max = (1 << depth) - 1;
+        let quad = QuadVec::from_float_coords(u, v, depth);
+
+        Self {
+            face,
+            quad: QuadVec {
+                x: quad.x.min(max),
+                y: quad.y.min(max),
+                depth,
+            },
+        }
+    }
+
+    /// gets the normalized direction from the center of the sphere to the center of this node.
+    /// The root has no direction, and returns all zeros.
+    pub fn get_direction(self) -> (f64, f64, f64) {
+        if self.is_root() {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let size = self.quad.get_size();
+        let (u, v) = self.quad.get_float_coords();
+        let p = Self::face_to_cube(self.face, u + size * 0.5, v + size * 0.5);
+        let length = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+
+        (p[0] / length, p[1] / length, p[2] / length)
+    }
+
+    /// wether this is the root node.
+    #[inline]
+    pub fn is_root(self) -> bool {
+        self.face == Self::ROOT_FACE
+    }
+
+    /// gets the depth in the tree, which is one deeper than the depth on the face, as the root holds the faces.
+    #[inline]
+    pub fn get_depth(self) -> u8 {
+        if self.is_root() {
+            0
+        } else {
+            self.quad.depth + 1
+        }
+    }
+
+    // point on the plane of the face, with the face itself going from -1 to 1
+    fn face_to_cube(face: u8, u: f64, v: f64) -> [f64; 3] {
+        let [normal, axis_u, axis_v] = CUBE_FACES[face as usize];
+        let mut p = [0.0; 3];
+        p[normal.0] = normal.1;
+        p[axis_u.0] = axis_u.1 * (u * 2.0 - 1.0);
+        p[axis_v.0] = axis_v.1 * (v * 2.0 - 1.0);
+        p
+    }
+
+    // face a direction points to, and the position on the face, from 0 to 1
+    fn cube_to_face(p: [f64; 3]) -> (u8, f64, f64) {
+        // the axis that's the furthest out decides the face
+        let axis = (0..3)
+            .max_by(|a, b| p[*a].abs().total_cmp(&p[*b].abs()))
+            .unwrap_or(0);
+        let face = (axis * 2) as u8 + (p[axis] < 0.0) as u8;
+
+        // and project onto it
+        let [_, axis_u, axis_v] = CUBE_FACES[face as usize];
+        let scale = 1.0 / p[axis].abs();
+        let u = (p[axis_u.0] * axis_u.1 * scale + 1.0) * 0.5;
+        let v = (p[axis_v.0] * axis_v.1 * scale + 1.0) * 0.5;
+
+        (face, u.clamp(0.0, 1.0), v.clamp(0.0, 1.0))
+    }
+
+    // cell of this node on the grid of another face at the same depth, with this face unfolded around the edge they share.
+    // the grid is moved by one face, so the other face goes from n to 2n and the faces around it fit from 0 to 3n.
+    // None if it's the opposite face, as that doesn't share an edge
+    fn unfold_onto(self, face: u8) -> Option<QuadVec> {
+        let [normal, axis_u, axis_v] = CUBE_FACES[face as usize];
+        let [own_normal, own_u, own_v] = CUBE_FACES[self.face as usize];
+        if normal.0 == own_normal.0 {
+            return None;
+        }
+
+        // center of this node on the cube, which goes from -n to n, so the center is a whole number
+        let n = 1i64 << self.quad.depth;
+        let mut p = [0i64; 3];
+        p[own_normal.0] = own_normal.1 as i64 * n;
+        p[own_u.0] = own_u.1 as i64 * (self.quad.x as i64 * 2 + 1 - n);
+        p[own_v.0] = own_v.1 as i64 * (self.quad.y as i64 * 2 + 1 - n);
+
+        // distance from the shared edge, which then goes past the edge of the other face
+        let distance = n - normal.1 as i64 * p[normal.0];
+        p[own_normal.0] = own_normal.1 as i64 * (n + distance);
+        p[normal.0] = normal.1 as i64 * n;
+
+        // and the cell it's in on the plane of the other face, the center is always odd so this divides exactly
+        let u = p[axis_u.0] * axis_u.1 as i64 + n;
+        let v = p[axis_v.0] * axis_v.1 as i64 + n;
+        Some(QuadVec {
+            x: ((u - 1) / 2 + n) as u64,
+            y: ((v - 1) / 2 + n) as u64,
+            depth: self.quad.depth,
+        })
+    }
+}
+
+impl LodVec for CubeFaceVec {
+    #[inline]
+    fn get_child(self, index: u32) -> Self {
+        if self.is_root() {
+            debug_assert!(index < 6);
+            Self {
+                face: index as u8,
+                quad: QuadVec::root(),
+            }
+        } else {
+            Self {
+                face: self.face,
+                quad: self.quad.get_child(index),
+            }
+        }
+    }
+
+    const NUM_CHILDREN: u32 = 4;
+    const NUM_ROOT_CHILDREN: u32 = 6;
+
+    #[inline]
+    fn root() -> Self {
+        Self {
+            face: Self::ROOT_FACE,
+            quad: QuadVec::root(),
+        }
+    }
+
+    #[inline]
+    fn depth(self) -> u8 {
+        // the faces are at depth 1
+        if self.is_root() {
+            0
+        } else {
+            self.quad.depth + 1
+        }
+    }
+
+    #[inline]
+    fn get_parent(self) -> Option<Self> {
+        // the faces are the children of the root
+        if self.is_root() {
+            None
+        } else if self.quad == QuadVec::root() {
+            Some(Self::root())
+        } else {
+            Some(Self {
+                face: self.face,
+                quad: self.quad.get_parent()?,
+            })
+        }
+    }
+
+    /// nodes on the same face subdivide like a quadtree.
+    /// For nodes on another face, the face of the target is unfolded onto the face of the node around the edge they share,
+    /// and the target subdivides the node like a quadtree on that unfolded grid. Targets on the opposite face never subdivide the node
+    fn can_subdivide(self, node: Self, detail: u32) -> bool {
+        // the root always subdivides, unless the target is the root
+        if self.is_root() {
+            return false;
+        } else if node.is_root() {
+            return true;
+        }
+
+        // on the same face, this works the same as a quadtree
+        if self.face == node.face {
+            return self.quad.can_subdivide(node.quad, detail);
+        }
+
+        // otherwise, unfold the face of the target onto the face of the node
+        let Some(target) = self.unfold_onto(node.face) else {
+            return false;
+        };
+
+        // and move the node along with the grid, so it's the same as a quadtree
+        let offset = 1 << node.quad.depth;
+        target.can_subdivide(
+            QuadVec {
+                x: node.quad.x + offset,
+                y: node.quad.y + offset,
+                depth: node.quad.depth,
+            },
+            detail,
+        )
+    }
+
+    /// the bounds are on a single face, unless min or max is the root, which makes it contain all nodes.
+    /// the max depth is the depth in the tree, see get_depth
+    fn is_inside_bounds(self, min: Self, max: Self, max_depth: u8) -> bool {
+        if self.get_depth() > max_depth {
+            false
+        } else if self.is_root() || min.is_root() || max.is_root() {
+            true
+        } else {
+            self.face == min.face
+                && self.face == max.face
+                && self
+                    .quad
+                    .is_inside_bounds(min.quad, max.quad, max_depth - 1)
+        }
+    }
+
+    #[inline]
+    fn contains_child_node(self, child: Self) -> bool {
+        if self.is_root() {
+            true
+        } else {
+            self.face == child.face && self.quad.contains_child_node(child.quad)
+        }
+    }
+
+    const NUM_NEIGHBORS: u32 = 8;
+    const NUM_FACE_NEIGHBORS: u32 = 4;
+
+    /// distance between the nodes on the same face, in the same way as QuadVec.
+    /// Nodes on different faces are considered to be a face apart, and the root overlaps everything
+    #[inline]
+    fn distance(self, other: Self) -> u64 {
+        if self.is_root() || other.is_root() {
+            0
+        } else if self.face == other.face {
+            self.quad.distance(other.quad)
+        } else {
+            1 << self.quad.depth.max(other.quad.depth)
+        }
+    }
+
+    /// gets the neighbour in the same directions as QuadVec, which can be on another face if this is at the edge of a face.
+    /// The direction on the other face can be different, as the faces are rotated.
+    /// Only three faces meet at a corner of the cube, so a node in the corner of a face has no diagonal neighbour across that corner,
+    /// and that direction returns None. The nodes across the two edges next to the corner are it's only neighbours there
+    fn neighbor(self, direction: u32) -> Option<Self> {
+        if self.is_root() {
+            return None;
+        }
+
+        // on the same face
+        if let Some(quad) = self.quad.neighbor(direction) {
+            return Some(Self {
+                face: self.face,
+                quad,
+            });
+        }
+
+        // center of the neighbour, on the plane of this face
+        let (dx, dy) = *QuadVec::NEIGHBOR_OFFSETS.get(direction as usize)?;
+        let size = self.quad.get_size();
+        let u = (self.quad.x as f64 + 0.5 + dx as f64) * size;
+        let v = (self.quad.y as f64 + 0.5 + dy as f64) * size;
+        let mut p = Self::face_to_cube(self.face, u, v);
+
+        // fold it over the edge onto the next face
+        let [normal, axis_u, axis_v] = CUBE_FACES[self.face as usize];
+        let past_u = p[axis_u.0].abs() > 1.0;
+        let past_v = p[axis_v.0].abs() > 1.0;
+        let axis = match (past_u, past_v) {
+            (true, false) => axis_u.0,
+            (false, true) => axis_v.0,
+            // past both, so across a corner of the cube
+            _ => return None,
+        };
+
+        let distance = p[axis].abs() - 1.0;
+        p[axis] = p[axis].signum();
+        p[normal.0] = normal.1 * (1.0 - distance);
+
+        // and find where it ends up
+        let (face, u, v) = Self::cube_to_face(p);
+        let max = (1 << self.quad.depth) - 1;
+        Some(Self {
+            face,
+            quad: QuadVec {
+                x: ((u / size) as u64).min(max),
+                y: ((v / size) as u64).min(max),
+                depth: self.quad.depth,
+            },
+        })
+    }
+
+    /// gets the direction back from the neighbour, which is the opposite direction on the same face.
+    /// Across the edge of a face the neighbour is rotated, so it's whichever direction leads back
+    fn opposite_direction(self, direction: u32) -> Option<u32> {
+        let neighbor = self.neighbor(direction)?;
+        if neighbor.face == self.face {
+            return self.quad.opposite_direction(direction);
+        }
+
+        (0..Self::NUM_NEIGHBORS).find(|x| neighbor.neighbor(*x) == Some(self))
+    }
+}
+
+// arrays of generic size can't derive these, so the position is stored as a sequence
+#[cfg(feature = "serde")]
+impl<const D: usize> serde::Serialize for LodVecN<D> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("LodVecN", 2)?;
+        state.serialize_field("pos", &self.pos[..])?;
+        state.serialize_field("depth", &self.depth)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const D: usize> serde::Deserialize<'de> for LodVecN<D> {
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "LodVecN")]
+        struct Data {
+            pos: Vec<u64>,
+            depth: u8,
+        }
+
+        let data = Data::deserialize(deserializer)?;
+        let len = data.pos.len();
+
+        Ok(Self {
+            pos: data
+                .pos
+                .try_into()
+                .map_err(|_| De::Error::invalid_length(len, &"one coordinate per axis"))?,
+            depth: data.depth,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    // random vectors, up to the given depth
+    fn random_quad(rng: &mut SmallRng, max_depth: u8) -> QuadVec {
+        let depth = rng.gen_range(0..=max_depth);
+        QuadVec::new(
+            rng.gen_range(0..1 << depth),
+            rng.gen_range(0..1 << depth),
+            depth,
+        )
+    }
+
+    fn random_oct(rng: &mut SmallRng, max_depth: u8) -> OctVec {
+        let depth = rng.gen_range(0..=max_depth);
+        OctVec::new(
+            rng.gen_range(0..1 << depth),
+            rng.gen_range(0..1 << depth),
+            rng.gen_range(0..1 << depth),
+            depth,
+        )
+    }
+
+    // quadvec that only implements the required methods, to check the defaults
+    #[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd)]
+    struct Plain(QuadVec);
+
+    impl LodVec for Plain {
+        const NUM_CHILDREN: u32 = QuadVec::NUM_CHILDREN;
+
+        fn get_child(self, index: u32) -> Self {
+            Self(self.0.get_child(index))
+        }
+
+        fn root() -> Self {
+            Self(QuadVec::root())
+        }
+
+        fn can_subdivide(self, node: Self, detail: u32) -> bool {
+            self.0.can_subdivide(node.0, detail)
+        }
+
+        fn is_inside_bounds(self, min: Self, max: Self, max_depth: u8) -> bool {
+            self.0.is_inside_bounds(min.0, max.0, max_depth)
+        }
+
+        fn contains_child_node(self, child: Self) -> bool {
+            self.0.contains_child_node(child.0)
+        }
+    }
+
+    #[test]
+    fn default_depth() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let position = random_quad(&mut rng, 12);
+            assert_eq!(Plain(position).depth(), position.depth);
+            assert_eq!(
+                Plain(position).get_parent(),
+                position.get_parent().map(Plain)
+            );
+        }
+        assert_eq!(Plain::root().depth(), 0);
+        assert_eq!(Plain::root().get_parent(), None);
+    }
+
+    #[test]
+    fn matches_quadvec() {
+        let mut rng = SmallRng::seed_from_u64(42);
+
+        assert_eq!(LodVecN::<2>::NUM_CHILDREN, QuadVec::NUM_CHILDREN);
+        assert_eq!(LodVecN::<2>::NUM_NEIGHBORS, QuadVec::NUM_NEIGHBORS);
+        assert_eq!(
+            LodVecN::<2>::NUM_FACE_NEIGHBORS,
+            QuadVec::NUM_FACE_NEIGHBORS
+        );
+        assert_eq!(LodVecN::<2>::root(), QuadVec::root().into());
+
+        for _ in 0..1000 {
+            let a = random_quad(&mut rng, 8);
+            let b = random_quad(&mut rng, 8);
+            let (min, max) = (random_quad(&mut rng, 8), random_quad(&mut rng, 8));
+            let detail = rng.gen_range(0..4);
+            let (an, bn) = (LodVecN::<2>::from(a), LodVecN::<2>::from(b));
+
+            for i in 0..QuadVec::NUM_CHILDREN {
+                assert_eq!(an.get_child(i), a.get_child(i).into());
+            }
+            for i in 0..QuadVec::NUM_NEIGHBORS {
+                assert_eq!(an.neighbor(i), a.neighbor(i).map(Into::into));
+                assert_eq!(an.opposite_direction(i), a.opposite_direction(i));
+            }
+
+            assert_eq!(an.can_subdivide(bn, detail), a.can_subdivide(b, detail));
+            assert_eq!(
+                an.is_inside_bounds(min.into(), max.into(), 6),
+                a.is_inside_bounds(min, max, 6)
+            );
+            if a.depth <= b.depth {
+                assert_eq!(an.contains_child_node(bn), a.contains_child_node(b));
+            }
+            assert_eq!(an.partial_cmp(&bn), a.partial_cmp(&b));
+
+            let (x, y) = a.get_float_coords();
+            assert_eq!(an.get_float_coords(), [x, y]);
+            assert_eq!(an.get_size(), a.get_size());
+            assert_eq!(
+                LodVecN::<2>::from_float_coords([x, y], a.depth + 1),
+                QuadVec::from_float_coords(x, y, a.depth + 1).into()
+            );
+        }
+    }
+
+    #[test]
+    fn matches_octvec() {
+        let mut rng = SmallRng::seed_from_u64(42);
+
+        assert_eq!(LodVecN::<3>::NUM_CHILDREN, OctVec::NUM_CHILDREN);
+        assert_eq!(LodVecN::<3>::NUM_NEIGHBORS, OctVec::NUM_NEIGHBORS);
+        assert_eq!(LodVecN::<3>::NUM_FACE_NEIGHBORS, OctVec::NUM_FACE_NEIGHBORS);
+        assert_eq!(LodVecN::<3>::root(), OctVec::root().into());
+
+        for _ in 0..1000 {
+            let a = random_oct(&mut rng, 8);
+            let b = random_oct(&mut rng, 8);
+            let (min, max) = (random_oct(&mut rng, 8), random_oct(&mut rng, 8));
+            let detail = rng.gen_range(0..4);
+            let (an, bn) = (LodVecN::<3>::from(a), LodVecN::<3>::from(b));
+
+            for i in 0..OctVec::NUM_CHILDREN {
+                assert_eq!(an.get_child(i), a.get_child(i).into());
+            }
+            for i in 0..OctVec::NUM_NEIGHBORS {
+                assert_eq!(an.neighbor(i), a.neighbor(i).map(Into::into));
+                assert_eq!(an.opposite_direction(i), a.opposite_direction(i));
+            }
+
+            assert_eq!(an.can_subdivide(bn, detail), a.can_subdivide(b, detail));
+            assert_eq!(
+                an.is_inside_bounds(min.into(), max.into(), 6),
+                a.is_inside_bounds(min, max, 6)
+            );
+            if a.depth <= b.depth {
+                assert_eq!(an.contains_child_node(bn), a.contains_child_node(b));
+            }
+            assert_eq!(an.partial_cmp(&bn), a.partial_cmp(&b));
+
+            let (x, y, z) = a.get_float_coords();
+            assert_eq!(an.get_float_coords(), [x, y, z]);
+            assert_eq!(
+                LodVecN::<3>::from_float_coords([x, y, z], a.depth + 1),
+                OctVec::from_float_coords(x, y, z, a.depth + 1).into()
+            );
+        }
+    }
+
+    #[test]
+    fn other_dimensions() {
+        // binary tree
+        assert_eq!(LodVecN::<1>::NUM_CHILDREN, 2);
+        assert_eq!(LodVecN::<1>::NUM_NEIGHBORS, 2);
+        assert_eq!(LodVecN::<1>::root().get_child(1), LodVecN::new([1], 1));
+
+        // space-time
+        assert_eq!(LodVecN::<4>::NUM_CHILDREN, 16);
+        assert_eq!(LodVecN::<4>::NUM_NEIGHBORS, 80);
+        assert_eq!(LodVecN::<4>::NUM_FACE_NEIGHBORS, 8);
+
+        // all neighbour offsets are different, and faces come in opposite pairs
+        let offsets = (0..LodVecN::<4>::NUM_NEIGHBORS)
+            .map(LodVecN::<4>::get_neighbor_offset)
+            .collect::<Vec<_>>();
+        for (i, offset) in offsets.iter().enumerate() {
+            assert_ne!(*offset, [0; 4]);
+            assert!(!offsets[..i].contains(offset));
+        }
+        for face in 0..LodVecN::<4>::NUM_FACE_NEIGHBORS as usize {
+            assert_eq!(offsets[face].map(|x| -x), offsets[face ^ 1]);
+        }
+
+        // and it works in a tree
+        let mut tree = crate::Tree::<(), LodVecN<4>>::new(0);
+        while tree.prepare_update(&[LodVecN::new([3, 1, 2, 0], 2)], 0, &mut |_| ()) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        assert_eq!(tree.get_num_chunks(), 1 + 16 + 16);
+    }
+
+    #[test]
+    fn cube_faces() {
+        // the root holds all six faces
+        let root = CubeFaceVec::root();
+        assert_eq!(root.num_children(), 6);
+        assert_eq!(root.get_child(3), CubeFaceVec::new(3, QuadVec::root()));
+        assert_eq!(root.get_child(3).num_children(), 4);
+        assert!(root.contains_child_node(CubeFaceVec::new(5, QuadVec::new(1, 2, 3))));
+
+        // the faces are one deeper than the root
+        assert_eq!(root.depth(), 0);
+        assert_eq!(root.get_child(3).depth(), 1);
+        assert_eq!(CubeFaceVec::new(5, QuadVec::new(1, 2, 3)).depth(), 4);
+
+        // directions end up on the face they point to, and back
+        let axes = [
+            (1.0, 0.0, 0.0),
+            (-1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (0.0, -1.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (0.0, 0.0, -1.0),
+        ];
+        for (face, (x, y, z)) in axes.into_iter().enumerate() {
+            let vec = CubeFaceVec::from_direction(x, y, z, 1);
+            assert_eq!(vec.face, face as u8);
+            assert_eq!(
+                CubeFaceVec::from_direction(x, y, z, 0).get_direction(),
+                (x, y, z)
+            );
+        }
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        let mut rotated = 0;
+        for _ in 0..1000 {
+            let face = rng.gen_range(0..6);
+            let vec = CubeFaceVec::new(face, random_quad(&mut rng, 6));
+
+            // the direction of a node ends up in that node
+            let (x, y, z) = vec.get_direction();
+            assert_eq!(CubeFaceVec::from_direction(x, y, z, vec.quad.depth), vec);
+
+            // neighbours across faces always exist, and are next to us on the sphere
+            for direction in 0..CubeFaceVec::NUM_FACE_NEIGHBORS {
+                let neighbor = vec.neighbor(direction).unwrap();
+                assert_eq!(neighbor.quad.depth, vec.quad.depth);
+
+                // and we are their neighbour, in the direction back
+                let back = vec.opposite_direction(direction).unwrap();
+                assert!(back < CubeFaceVec::NUM_FACE_NEIGHBORS);
+                assert_eq!(neighbor.neighbor(back), Some(vec));
+                if back != direction ^ 1 {
+                    rotated += 1;
+                }
+
+                let (nx, ny, nz) = neighbor.get_direction();
+                let angle = (x * nx + y * ny + z * nz).clamp(-1.0, 1.0).acos();
+                assert!(angle < vec.quad.get_size() * 2.0);
+
+                // across an edge, the neighbour unfolds right next to us
+                if neighbor.face != vec.face && vec.quad.depth > 0 {
+                    let offset = 1 << vec.quad.depth;
+                    let unfolded = neighbor.unfold_onto(vec.face).unwrap();
+                    assert_eq!(
+                        unfolded.x.abs_diff(vec.quad.x + offset)
+                            + unfolded.y.abs_diff(vec.quad.y + offset),
+                        1
+                    );
+
+                    // so it only subdivides our parent with enough detail, same as on a single face
+                    let parent = vec.get_parent().unwrap();
+                    assert!(neighbor.can_subdivide(parent, 1));
+                    assert!(!neighbor.can_subdivide(parent, 0));
+                }
+            }
+
+            // a node always subdivides for a target inside of it
+            if vec.quad.depth > 0 {
+                let parent = CubeFaceVec::new(
+                    face,
+                    QuadVec::new(vec.quad.x >> 1, vec.quad.y >> 1, vec.quad.depth - 1),
+                );
+                assert!(vec.can_subdivide(parent, 0));
+            }
+        }
+
+        // across some edges the direction back isn't the opposite one
+        assert!(rotated > 0);
+
+        // the opposite face is never subdivided
+        let target = CubeFaceVec::new(0, QuadVec::new(0, 0, 4));
+        assert!(!target.can_subdivide(CubeFaceVec::new(1, QuadVec::root()), 64));
+        assert!(target.can_subdivide(CubeFaceVec::new(2, QuadVec::root()), 64));
+
+        // no diagonal across the corner of the cube
+        assert_eq!(CubeFaceVec::new(0, QuadVec::new(0, 0, 2)).neighbor(4), None);
+        assert!(CubeFaceVec::new(0, QuadVec::new(1, 0, 2))
+            .neighbor(4)
+            .is_some());
+    }
+
+    #[test]
+    fn cube_tree() {
+        let mut tree = crate::Tree::<(), CubeFaceVec>::new(0);
+        tree.set_balanced(true);
+
+        // near the corner of three faces
+        let target = CubeFaceVec::from_direction(1.0, 0.9, 0.95, 5);
+        while tree.prepare_update(&[target], 1, &mut |_| ()) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        // all six faces are in there, and the target as well
+        for face in 0..6 {
+            assert!(tree
+                .get_chunk_from_position(CubeFaceVec::new(face, QuadVec::root()))
+                .is_some());
+        }
+        assert!(tree.get_chunk_from_position(target).is_some());
+
+        // the faces next to the target got subdivided as well
+        let neighbor = (0..4)
+            .filter_map(|x| target.neighbor(x))
+            .find(|x| x.face != target.face)
+            .unwrap();
+        assert!(tree.get_chunk_from_position(neighbor).is_some());
+
+        // and all leaves have neighbours on every side, at most one lod level apart
+        let positions = (0..tree.get_num_chunks())
+            .map(|i| tree.get_chunk_position(i))
+            .collect::<Vec<_>>();
+        for position in positions.iter().copied() {
+            if position.is_root()
+                || positions.iter().any(|x| {
+                    x.get_depth() > position.get_depth() && position.contains_child_node(*x)
+                })
+            {
+                continue;
+            }
+
+            let neighbors = tree.get_neighbors(position).unwrap();
+            for direction in 0..4 {
+                assert!(neighbors.iter().any(|x| x.direction == direction));
+            }
+            for neighbor in neighbors {
+                assert!(neighbor.depth.abs_diff(position.get_depth()) <= 1);
+                assert_eq!(neighbor.depth, neighbor.position.get_depth());
+            }
+        }
+
+        // and it can all be removed again
+        while tree.prepare_update(&[CubeFaceVec::root()], 1, &mut |_| ()) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        assert_eq!(tree.get_num_chunks(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let vec = LodVecN::new([1, 2, 3], 2);
+        let json = serde_json::to_string(&vec).unwrap();
+        assert_eq!(serde_json::from_str::<LodVecN<3>>(&json).unwrap(), vec);
+
+        // needs the right number of axes
+        assert!(serde_json::from_str::<LodVecN<2>>(&json).is_err());
+    }
+
+    #[test]
+    fn distance() {
+        // overlapping nodes are at 0, no matter the depth
+        assert_eq!(QuadVec::new(0, 0, 1).distance(QuadVec::new(1, 1, 2)), 0);
+        assert_eq!(QuadVec::root().distance(QuadVec::new(7, 3, 3)), 0);
+
+        // otherwise it's the distance in nodes at the deepest depth, from the closest edge
+        assert_eq!(QuadVec::new(0, 0, 1).distance(QuadVec::new(3, 1, 2)), 2);
+        assert_eq!(QuadVec::new(3, 1, 2).distance(QuadVec::new(0, 0, 1)), 2);
+        assert_eq!(OctVec::new(0, 0, 0, 2).distance(OctVec::new(1, 3, 0, 2)), 3);
+        assert_eq!(
+            LodVecN::new([5, 0, 0, 0], 3).distance(LodVecN::new([0, 0, 0, 1], 1)),
+            4
+        );
+
+        // and nodes on different faces of a cube are a face apart
+        let a = CubeFaceVec::new(0, QuadVec::new(0, 0, 1));
+        let b = CubeFaceVec::new(1, QuadVec::new(0, 0, 1));
+        assert_eq!(a.distance(b), 2);
+        assert_eq!(a.distance(CubeFaceVec::root()), 0);
+    }
 }
@@ -0,0 +1,50 @@
+//! Contains the Error type, returned by the try_ functions of the tree
+
+use crate::tree::TREE_MAX_DEPTH;
+
+/// Error returned when the tree is used in a way it can't handle, such as by the try_ variants of the getters and update functions
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// the index is past the end of the chunk group it was used on
+    IndexOutOfBounds {
+        /// the index that was used
+        index: usize,
+        /// number of chunks in the group
+        len: usize,
+    },
+
+    /// do_update was called without an update prepared by prepare_update, or the update was already done
+    NoUpdatePrepared,
+
+    /// prepare_update was called while the last prepared update was not done yet.
+    /// Use do_update, cancel_update or revalidate_update for it first
+    UpdatePending,
+
+    /// prepare_update was called after do_update, without calling complete_update first
+    UpdateNotCompleted,
+
+    /// a target or position is deeper than TREE_MAX_DEPTH
+    TooDeep(u8),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IndexOutOfBounds { index, len } => write!(
+                f,
+                "index {} is out of bounds for a chunk group of length {}",
+                index, len
+            ),
+            Self::NoUpdatePrepared => write!(f, "no update was prepared"),
+            Self::UpdatePending => write!(f, "the prepared update was not done yet"),
+            Self::UpdateNotCompleted => write!(f, "the last update was not completed yet"),
+            Self::TooDeep(d) => write!(
+                f,
+                "depth {} is deeper than the max of {}",
+                d, TREE_MAX_DEPTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
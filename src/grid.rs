@@ -0,0 +1,692 @@
+//! Grid of trees, for worlds that are bigger than a single tree can cover
+//!
+//! The world is split into regions with integer coordinates, and each region has it's own tree with the region as root.
+//! Regions are made around the targets, and dropped again once no target is close to them.
+
+use crate::cache::*;
+use crate::traits::*;
+use crate::tree::*;
+use std::collections::{HashMap, HashSet};
+
+/// A sparse grid of trees, where each region of the world is it's own tree.
+///
+/// Regions within the radius of a target's region are kept, and others are dropped,
+/// so the world doesn't have a fixed size like a single tree.
+/// Targets also affect the regions around them, as if they were at the closest node in that region.
+///
+/// The chunk groups of all regions are merged, and positions come with the region they are in.
+/// Neighbours and restitching don't go across regions.
+///
+/// When a region is dropped, all of it's chunks are in chunks_to_remove until do_update,
+/// and in chunks_to_delete after it, so they can be hidden and cleaned up like any other removed chunk.
+///
+/// Each region has it's own cache, made by the cache factory when the region is made, see TreeGrid::with_cache.
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::QuadVec;
+/// let mut grid = TreeGrid::<(), QuadVec>::new(64, 1);
+///
+/// // a target in region (-3, 2)
+/// while grid.prepare_update(&[([-3, 2], QuadVec::new(4, 4, 3))], 2, &mut |_, _| ()) {
+///     grid.do_update();
+///     grid.complete_update();
+/// }
+///
+/// // makes the region and the ones around it
+/// assert_eq!(grid.get_num_regions(), 9);
+/// assert!(grid.get_region([-4, 1]).is_some());
+/// ```
+pub struct TreeGrid<C: Sized, L: GridVec, K: ChunkCache<C, L> = LruCache<C, L>> {
+    // all regions that are in the grid
+    regions: HashMap<L::Region, Tree<C, L, K>>,
+
+    // regions that were dropped in this update, with all their chunks to remove until do_update, and to delete after it
+    dropped_regions: Vec<(L::Region, Tree<C, L, K>)>,
+
+    // regions that are needed for the current targets
+    needed_regions: HashSet<L::Region>,
+
+    // how many regions around a target's region are kept
+    radius: u32,
+
+    // makes the cache of each new region
+    cache_factory: Box<dyn FnMut(L::Region) -> K + Send + Sync>,
+
+    // settings for all trees, the cache size is only set if it was changed after the grid was made
+    cache_size: Option<usize>,
+    update_mode: UpdateMode,
+    balanced: bool,
+}
+
+impl<C, L> TreeGrid<C, L>
+where
+    C: Sized,
+    L: GridVec,
+{
+    /// creates a new, empty grid
+    /// # Args
+    /// * `cache_size` The size of the chunk cache of each region
+    /// * `radius` How many regions around the region of a target are kept, as the largest difference along any axis
+    pub fn new(cache_size: usize, radius: u32) -> Self {
+        Self::with_cache(radius, move |_| LruCache::new(cache_size))
+    }
+}
+
+impl<C, L, K> TreeGrid<C, L, K>
+where
+    C: Sized,
+    L: GridVec,
+    K: ChunkCache<C, L>,
+{
+    /// creates a new, empty grid, where the cache of each region is made by the given function.
+    /// This allows picking a different cache per region, such as a TieredCache with a store for each region
+    /// # Args
+    /// * `radius` How many regions around the region of a target are kept, as the largest difference along any axis
+    /// * `cache_factory` function to make the cache for a new region
+    /// ```rust
+    /// # use lodtree::*;
+    /// # use lodtree::coords::QuadVec;
+    /// let mut grid = TreeGrid::<(), QuadVec, _>::with_cache(1, |_| FifoCache::new(16));
+    ///
+    /// while grid.prepare_update(&[([0, 0], QuadVec::new(4, 4, 3))], 2, &mut |_, _| ()) {
+    ///     grid.do_update();
+    ///     grid.complete_update();
+    /// }
+    ///
+    /// assert_eq!(grid.get_region([0, 0]).unwrap().get_cache().max_len(), Some(16));
+    /// ```
+    pub fn with_cache(
+        radius: u32,
+        cache_factory: impl FnMut(L::Region) -> K + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            regions: HashMap::new(),
+            dropped_regions: Vec::new(),
+            needed_regions: HashSet::new(),
+            radius,
+            cache_factory: Box::new(cache_factory),
+            cache_size: None,
+            update_mode: UpdateMode::default(),
+            balanced: false,
+        }
+    }
+
+    /// get the number of regions in the grid
+    #[inline]
+    pub fn get_num_regions(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// get the tree of a region, if the region is in the grid
+    #[inline]
+    pub fn get_region(&self, region: L::Region) -> Option<&Tree<C, L, K>> {
+        self.regions.get(&region)
+    }
+
+    /// get the tree of a region as mutable, if the region is in the grid
+    #[inline]
+    pub fn get_region_mut(&mut self, region: L::Region) -> Option<&mut Tree<C, L, K>> {
+        self.regions.get_mut(&region)
+    }
+
+    /// returns an iterator over all regions and their trees
+    #[inline]
+    pub fn iter_regions(&self) -> impl Iterator<Item = (L::Region, &Tree<C, L, K>)> {
+        self.regions.iter().map(|(region, tree)| (*region, tree))
+    }
+
+    /// returns an iterator over all regions and their trees, mutable
+    #[inline]
+    pub fn iter_regions_mut(&mut self) -> impl Iterator<Item = (L::Region, &mut Tree<C, L, K>)> {
+        self.regions
+            .iter_mut()
+            .map(|(region, tree)| (*region, tree))
+    }
+
+    /// get a chunk by region and position, if it exists
+    #[inline]
+    pub fn get_chunk_from_position(&self, region: L::Region, position: L) -> Option<&C> {
+        self.regions.get(&region)?.get_chunk_from_position(position)
+    }
+
+    /// get a mutable chunk by region and position, if it exists
+    #[inline]
+    pub fn get_chunk_from_position_mut(
+        &mut self,
+        region: L::Region,
+        position: L,
+    ) -> Option<&mut C> {
+        self.regions
+            .get_mut(&region)?
+            .get_chunk_from_position_mut(position)
+    }
+
+    /// prepares all regions for an update, see Tree::prepare_update.
+    /// This also makes the regions around the targets, and drops regions that are too far away from all targets.
+    /// The chunks of dropped regions are in chunks_to_remove, and end up in chunks_to_delete after do_update.
+    /// # Params
+    /// * `targets` The region each target is in, and it's position in that region. Targets can also be Target descriptors
+    /// * `metric` Decides wether a node subdivides for a target, see LodMetric
+    /// * `chunk_creator` function to create a new chunk from a given region and position
+    ///
+    /// returns whether any update is needed.
+    pub fn prepare_update<T: TargetPosition<L>, M: LodMetric<L, T> + Clone>(
+        &mut self,
+        targets: &[(L::Region, T)],
+        metric: M,
+        chunk_creator: &mut dyn FnMut(L::Region, L) -> C,
+    ) -> bool {
+        // find all regions that are needed
+        self.needed_regions.clear();
+        for (region, _) in targets {
+            L::for_each_region_around(*region, self.radius, &mut |region| {
+                self.needed_regions.insert(region);
+            });
+        }
+
+        // drop the ones that aren't needed anymore
+        // the chunks are deleted in do_update, so they can be reported as removed first
+        let dropped = self
+            .regions
+            .keys()
+            .filter(|region| !self.needed_regions.contains(*region))
+            .copied()
+            .collect::<Vec<_>>();
+        for region in dropped {
+            if let Some(tree) = self.regions.remove(&region) {
+                self.dropped_regions.push((region, tree));
+            }
+        }
+
+        // and make the new ones
+        for region in &self.needed_regions {
+            if !self.regions.contains_key(region) {
+                let mut tree = Tree::with_cache((self.cache_factory)(*region));
+                if let Some(cache_size) = self.cache_size {
+                    tree.set_cache_size(cache_size);
+                }
+                tree.set_update_mode(self.update_mode);
+                tree.set_balanced(self.balanced);
+                self.regions.insert(*region, tree);
+            }
+        }
+
+        // then update every region with the targets close enough to it
+        let mut needs_update = !self.dropped_regions.is_empty();
+        let mut region_targets = Vec::with_capacity(targets.len());
+        for (region, tree) in self.regions.iter_mut() {
+            region_targets.clear();
+            for (target_region, target) in targets {
+                if L::region_distance(*region, *target_region) <= self.radius as u64 {
+                    let position = target.position().project_to_region(*target_region, *region);
+                    region_targets.push(target.with_position(position));
+                }
+            }
+
+            let region = *region;
+            needs_update |= tree.prepare_update(&region_targets, metric.clone(), &mut |position| {
+                chunk_creator(region, position)
+            });
+        }
+
+        needs_update
+    }
+
+    /// runs the update on all regions, see Tree::do_update.
+    /// The chunks of dropped regions are moved to chunks_to_delete
+    pub fn do_update(&mut self) {
+        for tree in self.regions.values_mut() {
+            tree.do_update();
+        }
+        for (_, tree) in self.dropped_regions.iter_mut() {
+            tree.queue_all_for_deletion();
+        }
+    }
+
+    /// completes the update on all regions, see Tree::complete_update.
+    /// This also removes the regions that were dropped.
+    pub fn complete_update(&mut self) {
+        for tree in self.regions.values_mut() {
+            tree.complete_update();
+        }
+        self.dropped_regions.clear();
+    }
+
+    /// clears the grid, removing all regions
+    #[inline]
+    pub fn clear(&mut self) {
+        self.regions.clear();
+        self.dropped_regions.clear();
+        self.needed_regions.clear();
+    }
+
+    /// shrinks the internal buffers of the grid and all regions to fit, see Tree::shrink
+    pub fn shrink(&mut self) {
+        for tree in self.regions.values_mut() {
+            tree.shrink();
+        }
+        self.regions.shrink_to_fit();
+        self.dropped_regions.shrink_to_fit();
+        self.needed_regions.shrink_to_fit();
+    }
+
+    /// sets how many regions around the region of a target are kept.
+    /// Regions are made or dropped on the next update
+    #[inline]
+    pub fn set_radius(&mut self, radius: u32) {
+        self.radius = radius;
+    }
+
+    /// sets how prepare_update computes the changes to each region, see UpdateMode
+    #[inline]
+    pub fn set_update_mode(&mut self, update_mode: UpdateMode) {
+        self.update_mode = update_mode;
+        for tree in self.regions.values_mut() {
+            tree.set_update_mode(update_mode);
+        }
+    }
+
+    /// sets wether each region is kept balanced, see Tree::set_balanced
+    #[inline]
+    pub fn set_balanced(&mut self, balanced: bool) {
+        self.balanced = balanced;
+        for tree in self.regions.values_mut() {
+            tree.set_balanced(balanced);
+        }
+    }
+
+    /// resizes the cache size of each region, including the regions that are made later
+    #[inline]
+    pub fn set_cache_size(&mut self, cache_size: usize) {
+        self.cache_size = Some(cache_size);
+        for tree in self.regions.values_mut() {
+            tree.set_cache_size(cache_size);
+        }
+    }
+
+    // all trees, including the dropped ones
+    #[inline]
+    fn all_regions(&self) -> impl Iterator<Item = (L::Region, &Tree<C, L, K>)> {
+        self.regions
+            .iter()
+            .map(|(region, tree)| (*region, tree))
+            .chain(
+                self.dropped_regions
+                    .iter()
+                    .map(|(region, tree)| (*region, tree)),
+            )
+    }
+
+    // all trees, including the dropped ones, mutable
+    #[inline]
+    fn all_regions_mut(&mut self) -> impl Iterator<Item = (L::Region, &mut Tree<C, L, K>)> {
+        self.regions
+            .iter_mut()
+            .map(|(region, tree)| (*region, tree))
+            .chain(
+                self.dropped_regions
+                    .iter_mut()
+                    .map(|(region, tree)| (*region, &mut *tree)),
+            )
+    }
+}
+
+impl<C, L> Default for TreeGrid<C, L>
+where
+    C: Sized,
+    L: GridVec,
+{
+    /// creates a new grid with a cache size of 64 and a radius of 1
+    fn default() -> Self {
+        Self::new(64, 1)
+    }
+}
+
+// implements the merged getters and iterators for a chunk group
+macro_rules! impl_grid_group {
+    (
+        $len:ident,
+        $get:ident,
+        $get_pos:ident,
+        $iter_mut:ident,
+        $(#[$doc_len:meta])*
+        $func_len:ident,
+        $(#[$doc:meta])*
+        $func_name:ident,
+        $(#[$doc_mut:meta])*
+        $func_name_mut:ident,
+    ) => {
+        impl<C, L, K> TreeGrid<C, L, K>
+        where
+            C: Sized,
+            L: GridVec,
+            K: ChunkCache<C, L>,
+        {
+            #[inline]
+            $(#[$doc_len])*
+            pub fn $func_len(&self) -> usize {
+                self.all_regions().map(|(_, tree)| tree.$len()).sum()
+            }
+
+            #[inline]
+            $(#[$doc])*
+            pub fn $func_name(&self) -> impl Iterator<Item = (&C, L::Region, L)> {
+                self.all_regions().flat_map(|(region, tree)| {
+                    (0..tree.$len()).map(move |i| (tree.$get(i), region, tree.$get_pos(i)))
+                })
+            }
+
+            #[inline]
+            $(#[$doc_mut])*
+            pub fn $func_name_mut(&mut self) -> impl Iterator<Item = (&mut C, L::Region, L)> {
+                self.all_regions_mut().flat_map(|(region, tree)| {
+                    tree.$iter_mut()
+                        .map(move |(chunk, position)| (chunk, region, position))
+                })
+            }
+        }
+    };
+}
+
+impl_grid_group!(
+    get_num_chunks,
+    get_chunk,
+    get_chunk_position,
+    iter_chunks_and_positions_mut,
+    /// get the number of chunks in all regions
+    get_num_chunks,
+    /// returns an iterator over all chunks in all regions, with their region and position
+    iter_chunks_and_positions,
+    /// returns an iterator over all chunks in all regions as mutable, with their region and position
+    iter_chunks_and_positions_mut,
+);
+
+impl_grid_group!(
+    get_num_chunks_to_activate,
+    get_chunk_to_activate,
+    get_position_of_chunk_to_activate,
+    iter_chunks_to_activate_and_positions_mut,
+    /// get the number of chunks to activate in all regions
+    get_num_chunks_to_activate,
+    /// returns an iterator over all chunks to activate, with their region and position
+    iter_chunks_to_activate_and_positions,
+    /// returns an iterator over all chunks to activate as mutable, with their region and position
+    iter_chunks_to_activate_and_positions_mut,
+);
+
+impl_grid_group!(
+    get_num_chunks_to_deactivate,
+    get_chunk_to_deactivate,
+    get_position_of_chunk_to_deactivate,
+    iter_chunks_to_deactivate_and_positions_mut,
+    /// get the number of chunks to deactivate in all regions
+    get_num_chunks_to_deactivate,
+    /// returns an iterator over all chunks to deactivate, with their region and position
+    iter_chunks_to_deactivate_and_positions,
+    /// returns an iterator over all chunks to deactivate as mutable, with their region and position
+    iter_chunks_to_deactivate_and_positions_mut,
+);
+
+impl_grid_group!(
+    get_num_chunks_to_restitch,
+    get_chunk_to_restitch,
+    get_position_of_chunk_to_restitch,
+    iter_chunks_to_restitch_and_positions_mut,
+    /// get the number of chunks to restitch in all regions
+    get_num_chunks_to_restitch,
+    /// returns an iterator over all chunks to restitch, with their region and position
+    iter_chunks_to_restitch_and_positions,
+    /// returns an iterator over all chunks to restitch as mutable, with their region and position
+    iter_chunks_to_restitch_and_positions_mut,
+);
+
+impl_grid_group!(
+    get_num_chunks_to_add,
+    get_chunk_to_add,
+    get_position_of_chunk_to_add,
+    iter_chunks_to_add_and_positions_mut,
+    /// get the number of chunks to add in all regions
+    get_num_chunks_to_add,
+    /// returns an iterator over all chunks to add, with their region and position
+    iter_chunks_to_add_and_positions,
+    /// returns an iterator over all chunks to add as mutable, with their region and position
+    iter_chunks_to_add_and_positions_mut,
+);
+
+// the chunks to remove also include every chunk of the dropped regions, as they are removed from the grid
+impl<C, L, K> TreeGrid<C, L, K>
+where
+    C: Sized,
+    L: GridVec,
+    K: ChunkCache<C, L>,
+{
+    /// get the number of chunks to remove in all regions, including the chunks of the regions that were dropped
+    #[inline]
+    pub fn get_num_chunks_to_remove(&self) -> usize {
+        let removed: usize = self
+            .regions
+            .values()
+            .map(|tree| tree.get_num_chunks_to_remove())
+            .sum();
+        let dropped: usize = self
+            .dropped_regions
+            .iter()
+            .map(|(_, tree)| tree.get_num_chunks())
+            .sum();
+        removed + dropped
+    }
+
+    /// returns an iterator over all chunks to remove, with their region and position, including the chunks of the regions that were dropped
+    #[inline]
+    pub fn iter_chunks_to_remove_and_positions(&self) -> impl Iterator<Item = (&C, L::Region, L)> {
+        let removed = self.regions.iter().flat_map(|(region, tree)| {
+            (0..tree.get_num_chunks_to_remove()).map(move |i| {
+                (
+                    tree.get_chunk_to_remove(i),
+                    *region,
+                    tree.get_position_of_chunk_to_remove(i),
+                )
+            })
+        });
+        let dropped = self.dropped_regions.iter().flat_map(|(region, tree)| {
+            (0..tree.get_num_chunks())
+                .map(move |i| (tree.get_chunk(i), *region, tree.get_chunk_position(i)))
+        });
+        removed.chain(dropped)
+    }
+
+    /// returns an iterator over all chunks to remove as mutable, with their region and position, including the chunks of the regions that were dropped
+    #[inline]
+    pub fn iter_chunks_to_remove_and_positions_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (&mut C, L::Region, L)> {
+        let removed = self.regions.iter_mut().flat_map(|(region, tree)| {
+            let region = *region;
+            tree.iter_chunks_to_remove_and_positions_mut()
+                .map(move |(chunk, position)| (chunk, region, position))
+        });
+        let dropped = self.dropped_regions.iter_mut().flat_map(|(region, tree)| {
+            let region = *region;
+            tree.iter_chunks_and_positions_mut()
+                .map(move |(chunk, position)| (chunk, region, position))
+        });
+        removed.chain(dropped)
+    }
+}
+
+impl_grid_group!(
+    get_num_chunks_to_delete,
+    get_chunk_to_delete,
+    get_position_of_chunk_to_delete,
+    iter_chunks_to_delete_and_positions_mut,
+    /// get the number of chunks to delete in all regions, including the regions that were dropped
+    get_num_chunks_to_delete,
+    /// returns an iterator over all chunks to delete, with their region and position, including the regions that were dropped
+    iter_chunks_to_delete_and_positions,
+    /// returns an iterator over all chunks to delete as mutable, with their region and position, including the regions that were dropped
+    iter_chunks_to_delete_and_positions_mut,
+);
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::coords::*;
+
+    #[test]
+    fn regions_follow_targets() {
+        let mut grid = TreeGrid::<([i64; 2], QuadVec), QuadVec>::new(16, 1);
+
+        // in the corner of region (0, 0), so the regions to the left and bottom need detail as well
+        let target = QuadVec::new(0, 0, 4);
+        let mut cycles = 0;
+        while grid.prepare_update(&[([0, 0], target)], 2, &mut |region, position| {
+            (region, position)
+        }) {
+            // all chunks know where they are
+            for (chunk, region, position) in grid.iter_chunks_to_add_and_positions() {
+                assert_eq!(*chunk, (region, position));
+            }
+
+            grid.do_update();
+            grid.complete_update();
+
+            cycles += 1;
+            assert!(cycles < 64);
+        }
+
+        assert_eq!(grid.get_num_regions(), 9);
+
+        // the target is deep in it's own region
+        assert!(grid
+            .get_chunk_from_position([0, 0], QuadVec::new(0, 0, 4))
+            .is_some());
+
+        // and the other side of the border has detail too, but regions further away don't
+        assert!(grid
+            .get_chunk_from_position([-1, -1], QuadVec::new(15, 15, 4))
+            .is_some());
+        assert!(grid
+            .get_chunk_from_position([-1, -1], QuadVec::new(0, 0, 4))
+            .is_none());
+        assert!(grid
+            .get_chunk_from_position([1, 1], QuadVec::new(0, 0, 4))
+            .is_none());
+
+        let num_chunks = grid.get_num_chunks();
+        assert_eq!(grid.iter_chunks_and_positions().count(), num_chunks);
+        let old_chunks: HashSet<_> = grid
+            .iter_chunks_and_positions()
+            .map(|(chunk, _, _)| *chunk)
+            .collect();
+
+        // move far away, and all old regions get dropped
+        let mut deleted = 0;
+        let mut dropped = HashSet::new();
+        let mut removed = HashSet::new();
+        while grid.prepare_update(&[([100, -100], target)], 2, &mut |region, position| {
+            (region, position)
+        }) {
+            for (chunk, region, position) in grid.iter_chunks_to_remove_and_positions() {
+                assert_eq!(*chunk, (region, position));
+                removed.insert(*chunk);
+            }
+            assert_eq!(
+                grid.iter_chunks_to_remove_and_positions_mut().count(),
+                grid.get_num_chunks_to_remove()
+            );
+
+            grid.do_update();
+
+            for (chunk, region, position) in grid.iter_chunks_to_delete_and_positions() {
+                assert_eq!(*chunk, (region, position));
+                dropped.insert(region);
+            }
+            deleted += grid.get_num_chunks_to_delete();
+
+            grid.complete_update();
+        }
+
+        assert_eq!(grid.get_num_regions(), 9);
+        assert!(grid.get_region([0, 0]).is_none());
+        assert!(grid.get_region([101, -99]).is_some());
+        assert_eq!(dropped.len(), 9);
+
+        // every chunk that was in the old regions, including the visible ones, is reported as removed,
+        // and then deleted, along with the cache
+        assert!(old_chunks.is_subset(&removed));
+        assert!(deleted >= num_chunks);
+    }
+
+    #[test]
+    fn projection() {
+        // next to the border, the target stays as deep
+        let target = QuadVec::new(1, 6, 3);
+        assert_eq!(
+            target.project_to_region([0, 0], [-1, 0]),
+            QuadVec::new(3, 3, 2)
+        );
+        assert_eq!(
+            QuadVec::new(0, 6, 3).project_to_region([0, 0], [-1, 0]),
+            QuadVec::new(7, 6, 3)
+        );
+
+        // and further away, it gets coarser
+        assert_eq!(
+            target.project_to_region([0, 0], [1, 0]),
+            QuadVec::new(0, 0, 0)
+        );
+        assert_eq!(target.project_to_region([0, 0], [-3, 5]), QuadVec::root());
+    }
+
+    #[test]
+    fn cache_factory() {
+        // each region gets it's own cache, sized by how far it is from the origin
+        let mut grid = TreeGrid::<(), QuadVec, _>::with_cache(1, |region: [i64; 2]| {
+            FifoCache::new(4 + region[0].unsigned_abs() as usize)
+        });
+
+        while grid.prepare_update(&[([0, 0], QuadVec::new(4, 4, 3))], 2, &mut |_, _| ()) {
+            grid.do_update();
+            grid.complete_update();
+        }
+
+        for (region, tree) in grid.iter_regions() {
+            assert_eq!(
+                tree.get_cache().max_len(),
+                Some(4 + region[0].unsigned_abs() as usize)
+            );
+        }
+
+        // resizing also applies to regions made later
+        grid.set_cache_size(2);
+        while grid.prepare_update(&[([5, 0], QuadVec::new(4, 4, 3))], 2, &mut |_, _| ()) {
+            grid.do_update();
+            grid.complete_update();
+        }
+        assert!(grid
+            .iter_regions()
+            .all(|(_, tree)| tree.get_cache().max_len() == Some(2)));
+    }
+
+    #[test]
+    fn target_descriptors() {
+        let mut grid = TreeGrid::<(), OctVec>::new(0, 0);
+        grid.set_update_mode(UpdateMode::SinglePass);
+
+        let targets = [
+            ([0, 0, 0], Target::new(OctVec::new(3, 3, 3, 3), 1)),
+            ([5, 0, 0], Target::new(OctVec::new(3, 3, 3, 3), 1)),
+        ];
+        while grid.prepare_update(&targets, TargetDetail, &mut |_, _| ()) {
+            grid.do_update();
+            grid.complete_update();
+        }
+
+        // a radius of 0 only keeps the regions with a target in it
+        assert_eq!(grid.get_num_regions(), 2);
+        assert!(grid
+            .get_chunk_from_position([5, 0, 0], OctVec::new(3, 3, 3, 3))
+            .is_some());
+    }
+}
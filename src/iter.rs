@@ -1,7 +1,21 @@
 //! Iterators over chunks
+//!
+//! The mutable iterators over groups that hold nodes, such as the chunks to activate, go over the chunks in the order they are stored in the tree,
+//! and give every chunk once, even if it's node is in the group more than once.
+use crate::cache::*;
+use crate::split::*;
 use crate::traits::*;
 use crate::tree::*;
 
+use std::marker::PhantomData;
+
+// mutable chunks of a group that's stored with their positions, such as all chunks, or the chunks to add
+type ContainerIterMut<'a, T, C, L> =
+    std::iter::Map<std::slice::IterMut<'a, T>, fn(&mut T) -> (&mut C, L)>;
+
+// mutable chunks of a group that's stored as node indices, which are split off from the tree as they are iterated over
+type NodeChunkIterMut<'a, C, L> = GroupChunksMut<'a, C, L>;
+
 // implements all iterators for the given functions
 // this allows quickly and easily set them up for all chunks
 macro_rules! impl_all_iterators {
@@ -13,7 +27,7 @@ macro_rules! impl_all_iterators {
 		$name_chunk_and_pos_mut:ident,
 		$len:ident,
 		$get:ident,
-		$get_mut:ident,
+		$chunks_mut_ty:ty = |$tree:ident| $chunks_mut:expr,
 		$get_pos:ident,
 		$(#[$doc:meta])*
 		$func_name:ident,
@@ -28,37 +42,37 @@ macro_rules! impl_all_iterators {
 	) => {
         // define the struct
         #[doc=concat!("Iterator for chunks, see ", stringify!($func_name), "() under Tree for documentation")]
-		pub struct $name<'a, C: Sized, L: LodVec> {
-            tree: &'a Tree<C, L>,
+		pub struct $name<'a, C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
+            tree: &'a Tree<C, L, K>,
             index: usize,
         }
 
 		#[doc=concat!("Iterator for mutable chunks, see ", stringify!($func_name_mut), "() under Tree for documentation")]
-        pub struct $name_mut<'a, C: Sized, L: LodVec> {
-            tree: &'a mut Tree<C, L>,
-            index: usize,
+        pub struct $name_mut<'a, C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
+            chunks: $chunks_mut_ty,
+            cache: PhantomData<K>,
         }
 
         #[doc=concat!("Iterator for chunk positions, see ", stringify!($func_name_pos), "() under Tree for documentation")]
-        pub struct $name_pos<'a, C: Sized, L: LodVec> {
-            tree: &'a Tree<C, L>,
+        pub struct $name_pos<'a, C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
+            tree: &'a Tree<C, L, K>,
             index: usize,
         }
 
         #[doc=concat!("Iterator for chunks and positions, see ", stringify!($func_name_chunk_and_pos), "() under Tree for documentation")]
-        pub struct $name_chunk_and_pos<'a, C: Sized, L: LodVec> {
-            tree: &'a Tree<C, L>,
+        pub struct $name_chunk_and_pos<'a, C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
+            tree: &'a Tree<C, L, K>,
             index: usize,
         }
 
         #[doc=concat!("Iterator for mutable chunks and positions, see ", stringify!($func_name_chunk_and_pos_mut), "() under Tree for documentation")]
-		pub struct $name_chunk_and_pos_mut<'a, C: Sized, L: LodVec> {
-            tree: &'a mut Tree<C, L>,
-            index: usize,
+		pub struct $name_chunk_and_pos_mut<'a, C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
+            chunks: $chunks_mut_ty,
+            cache: PhantomData<K>,
         }
 
         // and implement iterator for it
-        impl<'a, C: Sized, L: LodVec> Iterator for $name<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator for $name<'a, C, L, K> {
             type Item = &'a C;
 
 			#[inline]
@@ -78,27 +92,17 @@ macro_rules! impl_all_iterators {
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> Iterator for $name_mut<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator for $name_mut<'a, C, L, K> {
             type Item = &'a mut C;
 
 			#[inline]
             fn next(&mut self) -> Option<Self::Item> {
-                // if the item is too big, stop
-                if self.index >= self.tree.$len() {
-                    None
-                } else {
-                    // otherwise, get the item
-                    let item = unsafe { self.tree.$get_mut(self.index).as_mut()? };
-
-                    // increment the index
-                    self.index += 1;
-
-                    Some(item)
-                }
+                // the chunks are split off from the tree, so just take the next one
+                self.chunks.next().map(|(chunk, _)| chunk)
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> Iterator for $name_pos<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator for $name_pos<'a, C, L, K> {
             type Item = L;
 
 			#[inline]
@@ -118,7 +122,7 @@ macro_rules! impl_all_iterators {
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> Iterator for $name_chunk_and_pos<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator for $name_chunk_and_pos<'a, C, L, K> {
             type Item = (&'a C, L);
 
 			#[inline]
@@ -138,85 +142,73 @@ macro_rules! impl_all_iterators {
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> Iterator for $name_chunk_and_pos_mut<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator for $name_chunk_and_pos_mut<'a, C, L, K> {
             type Item = (&'a mut C, L);
 
 			#[inline]
             fn next(&mut self) -> Option<Self::Item> {
-                // if the item is too big, stop
-                if self.index >= self.tree.$len() {
-                    None
-                } else {
-                    // otherwise, get the item
-                    let item = (
-                        unsafe { self.tree.$get_mut(self.index).as_mut()? },
-                        self.tree.$get_pos(self.index),
-                    );
-
-                    // increment the index
-                    self.index += 1;
-
-                    Some(item)
-                }
+                // the chunks are split off from the tree, so just take the next one
+                self.chunks.next()
             }
         }
 
         // exact size as well
-        impl<'a, C: Sized, L: LodVec> ExactSizeIterator for $name<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> ExactSizeIterator for $name<'a, C, L, K> {
 			#[inline]
             fn len(&self) -> usize {
                 self.tree.$len()
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> ExactSizeIterator for $name_mut<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> ExactSizeIterator for $name_mut<'a, C, L, K> {
 			#[inline]
             fn len(&self) -> usize {
-                self.tree.$len()
+                self.chunks.len()
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> ExactSizeIterator for $name_pos<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> ExactSizeIterator for $name_pos<'a, C, L, K> {
 			#[inline]
             fn len(&self) -> usize {
                 self.tree.$len()
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> ExactSizeIterator for $name_chunk_and_pos<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> ExactSizeIterator for $name_chunk_and_pos<'a, C, L, K> {
 			#[inline]
             fn len(&self) -> usize {
                 self.tree.$len()
             }
         }
 
-        impl<'a, C: Sized, L: LodVec> ExactSizeIterator for $name_chunk_and_pos_mut<'a, C, L> {
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> ExactSizeIterator for $name_chunk_and_pos_mut<'a, C, L, K> {
 			#[inline]
             fn len(&self) -> usize {
-                self.tree.$len()
+                self.chunks.len()
             }
         }
 
         // fused, because it will always return none when done
-        impl<'a, C: Sized, L: LodVec> std::iter::FusedIterator for $name<'a, C, L> {}
-        impl<'a, C: Sized, L: LodVec> std::iter::FusedIterator for $name_mut<'a, C, L> {}
-        impl<'a, C: Sized, L: LodVec> std::iter::FusedIterator for $name_pos<'a, C, L> {}
-        impl<'a, C: Sized, L: LodVec> std::iter::FusedIterator for $name_chunk_and_pos<'a, C, L> {}
-        impl<'a, C: Sized, L: LodVec> std::iter::FusedIterator
-            for $name_chunk_and_pos_mut<'a, C, L>
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> std::iter::FusedIterator for $name<'a, C, L, K> {}
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> std::iter::FusedIterator for $name_mut<'a, C, L, K> {}
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> std::iter::FusedIterator for $name_pos<'a, C, L, K> {}
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> std::iter::FusedIterator for $name_chunk_and_pos<'a, C, L, K> {}
+        impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> std::iter::FusedIterator
+            for $name_chunk_and_pos_mut<'a, C, L, K>
         {
         }
 
         // and implement all of them for the tree
-        impl<'a, C, L> Tree<C, L>
+        impl<'a, C, L, K> Tree<C, L, K>
         where
             C: Sized,
             L: LodVec,
+            K: ChunkCache<C, L>,
             Self: 'a,
         {
 			#[inline]
 			$(#[$doc])*
-			pub fn $func_name(&mut self) -> $name<C, L> {
+			pub fn $func_name(&mut self) -> $name<'_, C, L, K> {
 				$name {
 					tree: self,
 					index: 0,
@@ -225,16 +217,17 @@ macro_rules! impl_all_iterators {
 
 			#[inline]
 			$(#[$doc_mut])*
-			pub fn $func_name_mut(&mut self) -> $name_mut<C, L> {
+			pub fn $func_name_mut(&mut self) -> $name_mut<'_, C, L, K> {
+				let $tree = self;
 				$name_mut {
-					tree: self,
-					index: 0,
+					chunks: $chunks_mut,
+					cache: PhantomData,
 				}
 			}
 
 			#[inline]
 			$(#[$doc_pos])*
-			pub fn $func_name_pos(&mut self) -> $name_pos<C, L> {
+			pub fn $func_name_pos(&mut self) -> $name_pos<'_, C, L, K> {
 				$name_pos {
 					tree: self,
 					index: 0,
@@ -243,7 +236,7 @@ macro_rules! impl_all_iterators {
 
 			#[inline]
 			$(#[$doc_chunk_and_pos])*
-			pub fn $func_name_chunk_and_pos(&mut self) -> $name_chunk_and_pos<C, L> {
+			pub fn $func_name_chunk_and_pos(&mut self) -> $name_chunk_and_pos<'_, C, L, K> {
 				$name_chunk_and_pos {
 					tree: self,
 					index: 0,
@@ -252,10 +245,11 @@ macro_rules! impl_all_iterators {
 
 			#[inline]
 			$(#[$doc_chunk_and_pos_mut])*
-			pub fn $func_name_chunk_and_pos_mut(&mut self) -> $name_chunk_and_pos_mut<C, L> {
+			pub fn $func_name_chunk_and_pos_mut(&mut self) -> $name_chunk_and_pos_mut<'_, C, L, K> {
+				let $tree = self;
 				$name_chunk_and_pos_mut {
-					tree: self,
-					index: 0,
+					chunks: $chunks_mut,
+					cache: PhantomData,
 				}
 			}
         }
@@ -271,7 +265,10 @@ impl_all_iterators!(
     ChunkAndPositionIterMut,
     get_num_chunks,
     get_chunk,
-    get_chunk_pointer_mut,
+    ContainerIterMut<'a, ChunkContainer<C, L>, C, L> = |tree| tree
+        .chunks
+        .iter_mut()
+        .map((|x| (&mut x.chunk, x.position)) as fn(&mut ChunkContainer<C, L>) -> (&mut C, L)),
     get_chunk_position,
     /// returns an iterator over all chunks
     iter_chunks,
@@ -294,7 +291,12 @@ impl_all_iterators!(
     ChunkAndPositionIterToActivateMut,
     get_num_chunks_to_activate,
     get_chunk_to_activate,
-    get_chunk_to_activate_pointer_mut,
+    NodeChunkIterMut<'a, C, L> = |tree| chunks_of_nodes_mut(
+        &tree.nodes,
+        &mut tree.chunks,
+        &mut tree.group_order,
+        tree.chunks_to_activate.iter().copied(),
+    ),
     get_position_of_chunk_to_activate,
     /// returns an iterator over all chunks to activate
     iter_chunks_to_activate,
@@ -317,7 +319,12 @@ impl_all_iterators!(
     ChunkAndPositionIterToDeactivateMut,
     get_num_chunks_to_deactivate,
     get_chunk_to_deactivate,
-    get_chunk_to_deactivate_pointer_mut,
+    NodeChunkIterMut<'a, C, L> = |tree| chunks_of_nodes_mut(
+        &tree.nodes,
+        &mut tree.chunks,
+        &mut tree.group_order,
+        tree.chunks_to_deactivate.iter().copied(),
+    ),
     get_position_of_chunk_to_deactivate,
     /// returns an iterator over all chunks to deactivate
     iter_chunks_to_deactivate,
@@ -331,6 +338,62 @@ impl_all_iterators!(
     iter_chunks_to_deactivate_and_positions_mut,
 );
 
+// to restitch
+impl_all_iterators!(
+    ChunkToRestitchIter,
+    ChunkToRestitchIterMut,
+    PositionToRestitchIter,
+    ChunkAndPositionToRestitchIter,
+    ChunkAndPositionIterToRestitchMut,
+    get_num_chunks_to_restitch,
+    get_chunk_to_restitch,
+    NodeChunkIterMut<'a, C, L> = |tree| chunks_of_nodes_mut(
+        &tree.nodes,
+        &mut tree.chunks,
+        &mut tree.group_order,
+        tree.chunks_to_restitch.iter().copied(),
+    ),
+    get_position_of_chunk_to_restitch,
+    /// returns an iterator over all chunks to restitch
+    iter_chunks_to_restitch,
+    /// returns an iterator over all chunks to restitch, mutable
+    iter_chunks_to_restitch_mut,
+    /// returns an iterator over all positions of all chunks to restitch
+    iter_chunks_to_restitch_positions,
+    /// returns an iterator over all chunks to restitch and their positions
+    iter_chunks_to_restitch_and_positions,
+    /// returns an iterator over all chunks to restitch as mutable and their positions
+    iter_chunks_to_restitch_and_positions_mut,
+);
+
+// to edit
+impl_all_iterators!(
+    ChunkToEditIter,
+    ChunkToEditIterMut,
+    PositionToEditIter,
+    ChunkAndPositionToEditIter,
+    ChunkAndPositionIterToEditMut,
+    get_num_chunks_to_edit,
+    get_chunk_to_edit,
+    NodeChunkIterMut<'a, C, L> = |tree| chunks_of_nodes_mut(
+        &tree.nodes,
+        &mut tree.chunks,
+        &mut tree.group_order,
+        tree.chunks_to_edit.iter().copied(),
+    ),
+    get_position_of_chunk_to_edit,
+    /// returns an iterator over all chunks to edit
+    iter_chunks_to_edit,
+    /// returns an iterator over all chunks to edit, mutable
+    iter_chunks_to_edit_mut,
+    /// returns an iterator over all positions of all chunks to edit
+    iter_chunks_to_edit_positions,
+    /// returns an iterator over all chunks to edit and their positions
+    iter_chunks_to_edit_and_positions,
+    /// returns an iterator over all chunks to edit as mutable and their positions
+    iter_chunks_to_edit_and_positions_mut,
+);
+
 // to add
 impl_all_iterators!(
     ChunkToAddIter,
@@ -340,7 +403,10 @@ impl_all_iterators!(
     ChunkAndPositionIterToAddMut,
     get_num_chunks_to_add,
     get_chunk_to_add,
-    get_chunk_to_add_pointer_mut,
+    ContainerIterMut<'a, ToAddContainer<C, L>, C, L> = |tree| tree
+        .chunks_to_add
+        .iter_mut()
+        .map((|x| (&mut x.chunk, x.position)) as fn(&mut ToAddContainer<C, L>) -> (&mut C, L)),
     get_position_of_chunk_to_add,
     /// returns an iterator over all chunks to add
     iter_chunks_to_add,
@@ -363,7 +429,12 @@ impl_all_iterators!(
     ChunkAndPositionIterToRemoveMut,
     get_num_chunks_to_remove,
     get_chunk_to_remove,
-    get_chunk_to_remove_pointer_mut,
+    NodeChunkIterMut<'a, C, L> = |tree| chunks_of_nodes_mut(
+        &tree.nodes,
+        &mut tree.chunks,
+        &mut tree.group_order,
+        tree.chunks_to_remove.iter().map(|x| x.chunk),
+    ),
     get_position_of_chunk_to_remove,
     /// returns an iterator over all chunks to remove
     iter_chunks_to_remove,
@@ -386,7 +457,10 @@ impl_all_iterators!(
     ChunkAndPositionIterToDeleteMut,
     get_num_chunks_to_delete,
     get_chunk_to_delete,
-    get_chunk_to_delete_pointer_mut,
+    ContainerIterMut<'a, ToDeleteContainer<C, L>, C, L> = |tree| tree
+        .chunks_to_delete
+        .iter_mut()
+        .map((|x| (&mut x.chunk, x.position)) as fn(&mut ToDeleteContainer<C, L>) -> (&mut C, L)),
     get_position_of_chunk_to_delete,
     /// returns an iterator over all chunks to delete
     iter_chunks_to_delete,
@@ -423,7 +497,7 @@ impl<L: LodVec> Iterator for ChunksInBoundIter<L> {
         let current = self.stack.pop()?;
 
         // go over all child nodes
-        for i in 0..L::NUM_CHILDREN {
+        for i in 0..current.num_children() {
             let position = current.get_child(i);
 
             // if they are in bounds, and the correct depth, add them to the stack
@@ -436,9 +510,14 @@ impl<L: LodVec> Iterator for ChunksInBoundIter<L> {
     }
 }
 
-pub struct ChunksInBoundAndMaybeTreeIter<'a, C: Sized, L: LodVec> {
+pub struct ChunksInBoundAndMaybeTreeIter<
+    'a,
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L> = LruCache<C, L>,
+> {
     // the tree
-    tree: &'a Tree<C, L>,
+    tree: &'a Tree<C, L, K>,
 
     // internal stack for which chunks are next
     stack: Vec<(L, Option<TreeNode>)>,
@@ -453,7 +532,9 @@ pub struct ChunksInBoundAndMaybeTreeIter<'a, C: Sized, L: LodVec> {
     bound_max: L,
 }
 
-impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIter<'a, C, L> {
+impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator
+    for ChunksInBoundAndMaybeTreeIter<'a, C, L, K>
+{
     type Item = (L, Option<&'a C>);
 
     #[inline]
@@ -461,7 +542,7 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIter<'a, C,
         let (current_position, current_node) = self.stack.pop()?;
 
         // go over all child nodes
-        for i in 0..L::NUM_CHILDREN {
+        for i in 0..current_position.num_children() {
             let position = current_position.get_child(i);
 
             // if they are in bounds, and the correct depth, add them to the stack
@@ -499,9 +580,9 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIter<'a, C,
     }
 }
 
-pub struct ChunksInBoundAndTreeIter<'a, C: Sized, L: LodVec> {
+pub struct ChunksInBoundAndTreeIter<'a, C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
     // the tree
-    tree: &'a Tree<C, L>,
+    tree: &'a Tree<C, L, K>,
 
     // internal stack for which chunks are next
     stack: Vec<(L, TreeNode)>,
@@ -516,7 +597,9 @@ pub struct ChunksInBoundAndTreeIter<'a, C: Sized, L: LodVec> {
     bound_max: L,
 }
 
-impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIter<'a, C, L> {
+impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator
+    for ChunksInBoundAndTreeIter<'a, C, L, K>
+{
     type Item = (L, &'a C);
 
     #[inline]
@@ -524,7 +607,7 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIter<'a, C, L> {
         let (current_position, current_node) = self.stack.pop()?;
 
         // go over all child nodes
-        for i in 0..L::NUM_CHILDREN {
+        for i in 0..current_position.num_children() {
             let position = current_position.get_child(i);
 
             // if the node has children
@@ -546,9 +629,44 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIter<'a, C, L> {
     }
 }
 
-pub struct ChunksInBoundAndMaybeTreeIterMut<'a, C: Sized, L: LodVec> {
-    // the tree
-    tree: &'a mut Tree<C, L>,
+// the chunks of a tree, borrowed mutably, and handed out one node at a time by the bound iterators
+// every chunk is taken out at most once, so the iterators can hand them out as they go, without splitting off the chunks in the bounds first
+struct NodeChunksMut<'a, C: Sized> {
+    chunks: Vec<Option<&'a mut C>>,
+}
+
+impl<'a, C: Sized> NodeChunksMut<'a, C> {
+    #[inline]
+    fn new<L: LodVec>(chunks: &'a mut [ChunkContainer<C, L>]) -> Self {
+        Self {
+            chunks: chunks.iter_mut().map(|x| Some(&mut x.chunk)).collect(),
+        }
+    }
+
+    // takes the chunk of a node
+    // the bound iterators only go down the tree, so they visit every node, and with that every chunk, at most once
+    #[inline]
+    fn take(&mut self, node: TreeNode) -> &'a mut C {
+        self.chunks[node.chunk as usize]
+            .take()
+            .expect("every node has it's own chunk")
+    }
+}
+
+pub struct ChunksInBoundAndMaybeTreeIterMut<
+    'a,
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L> = LruCache<C, L>,
+> {
+    // the nodes of the tree
+    nodes: &'a [TreeNode],
+
+    // and the chunks, which are handed out once their node is visited
+    chunks: NodeChunksMut<'a, C>,
+
+    // the tree's cache isn't used
+    cache: PhantomData<K>,
 
     // internal stack for which chunks are next
     stack: Vec<(L, Option<TreeNode>)>,
@@ -563,7 +681,9 @@ pub struct ChunksInBoundAndMaybeTreeIterMut<'a, C: Sized, L: LodVec> {
     bound_max: L,
 }
 
-impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIterMut<'a, C, L> {
+impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator
+    for ChunksInBoundAndMaybeTreeIterMut<'a, C, L, K>
+{
     type Item = (L, Option<&'a mut C>);
 
     #[inline]
@@ -571,7 +691,7 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIterMut<'a,
         let (current_position, current_node) = self.stack.pop()?;
 
         // go over all child nodes
-        for i in 0..L::NUM_CHILDREN {
+        for i in 0..current_position.num_children() {
             let position = current_position.get_child(i);
 
             // if they are in bounds, and the correct depth, add them to the stack
@@ -581,10 +701,8 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIterMut<'a,
                     // and if it has children
                     if let Some(children) = node.children {
                         // children, so node
-                        self.stack.push((
-                            position,
-                            Some(self.tree.nodes[(children.get() + i) as usize]),
-                        ));
+                        self.stack
+                            .push((position, Some(self.nodes[(children.get() + i) as usize])));
                     } else {
                         // no node, so no chunk
                         self.stack.push((position, None));
@@ -598,11 +716,10 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIterMut<'a,
         // and return this item from the stack
         if let Some(node) = current_node {
             // there is a node, so get the chunk it has
-            let chunk = &mut self.tree.chunks[node.chunk as usize].chunk as *mut C;
+            let chunk = self.chunks.take(node);
 
             // and return it
-            // Safety: The iterator lives at least as long as the tree, and no changes can be made to the tree while it's borrowed by the iterator
-            Some((current_position, Some(unsafe { chunk.as_mut()? })))
+            Some((current_position, Some(chunk)))
         } else {
             // no chunk, so return that as None
             Some((current_position, None))
@@ -610,9 +727,20 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndMaybeTreeIterMut<'a,
     }
 }
 
-pub struct ChunksInBoundAndTreeIterMut<'a, C: Sized, L: LodVec> {
-    // the tree
-    tree: &'a mut Tree<C, L>,
+pub struct ChunksInBoundAndTreeIterMut<
+    'a,
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L> = LruCache<C, L>,
+> {
+    // the nodes of the tree
+    nodes: &'a [TreeNode],
+
+    // and the chunks, which are handed out once their node is visited
+    chunks: NodeChunksMut<'a, C>,
+
+    // the tree's cache isn't used
+    cache: PhantomData<K>,
 
     // internal stack for which chunks are next
     stack: Vec<(L, TreeNode)>,
@@ -627,7 +755,9 @@ pub struct ChunksInBoundAndTreeIterMut<'a, C: Sized, L: LodVec> {
     bound_max: L,
 }
 
-impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIterMut<'a, C, L> {
+impl<'a, C: Sized, L: LodVec, K: ChunkCache<C, L>> Iterator
+    for ChunksInBoundAndTreeIterMut<'a, C, L, K>
+{
     type Item = (L, &'a mut C);
 
     #[inline]
@@ -635,7 +765,7 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIterMut<'a, C, L>
         let (current_position, current_node) = self.stack.pop()?;
 
         // go over all child nodes
-        for i in 0..L::NUM_CHILDREN {
+        for i in 0..current_position.num_children() {
             let position = current_position.get_child(i);
 
             // if the node has children
@@ -644,16 +774,13 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIterMut<'a, C, L>
                 if position.is_inside_bounds(self.bound_min, self.bound_max, self.max_depth) {
                     // and push to the stack
                     self.stack
-                        .push((position, self.tree.nodes[(children.get() + i) as usize]));
+                        .push((position, self.nodes[(children.get() + i) as usize]));
                 }
             }
         }
 
-        // and return the position and node
-        // Safety: The iterator lives at least as long as the tree, and no changes can be made to the tree while it's borrowed by the iterator
-        Some((current_position, unsafe {
-            (&mut self.tree.chunks[current_node.chunk as usize].chunk as *mut C).as_mut()?
-        }))
+        // and return the position and chunk
+        Some((current_position, self.chunks.take(current_node)))
     }
 }
 
@@ -661,10 +788,11 @@ impl<'a, C: Sized, L: LodVec> Iterator for ChunksInBoundAndTreeIterMut<'a, C, L>
 // as in: chunks in tree and bounds, immutable and mutable
 // all chunks in the bounds, and ones in the tree, if any
 
-impl<'a, C, L> Tree<C, L>
+impl<'a, C, L, K> Tree<C, L, K>
 where
     C: Sized,
     L: LodVec,
+    K: ChunkCache<C, L>,
     Self: 'a,
 {
     /// iterate over all chunks that would be affected by an edit inside a certain bound
@@ -693,7 +821,7 @@ where
         bound_min: L,
         bound_max: L,
         max_depth: u8,
-    ) -> ChunksInBoundAndMaybeTreeIter<C, L> {
+    ) -> ChunksInBoundAndMaybeTreeIter<'a, C, L, K> {
         ChunksInBoundAndMaybeTreeIter {
             stack: vec![(L::root(), self.nodes.first().copied())],
             tree: self,
@@ -711,7 +839,7 @@ where
         bound_min: L,
         bound_max: L,
         max_depth: u8,
-    ) -> ChunksInBoundAndTreeIter<C, L> {
+    ) -> ChunksInBoundAndTreeIter<'a, C, L, K> {
         // get the stack, empty if we can't get the first node
         let stack = if let Some(node) = self.nodes.first() {
             vec![(L::root(), *node)]
@@ -735,10 +863,12 @@ where
         bound_min: L,
         bound_max: L,
         max_depth: u8,
-    ) -> ChunksInBoundAndMaybeTreeIterMut<C, L> {
+    ) -> ChunksInBoundAndMaybeTreeIterMut<'a, C, L, K> {
         ChunksInBoundAndMaybeTreeIterMut {
             stack: vec![(L::root(), self.nodes.first().copied())],
-            tree: self,
+            nodes: &self.nodes,
+            chunks: NodeChunksMut::new(&mut self.chunks),
+            cache: PhantomData,
             max_depth,
             bound_min,
             bound_max,
@@ -753,7 +883,7 @@ where
         bound_min: L,
         bound_max: L,
         max_depth: u8,
-    ) -> ChunksInBoundAndTreeIterMut<C, L> {
+    ) -> ChunksInBoundAndTreeIterMut<'a, C, L, K> {
         // get the stack, empty if we can't get the first node
         let stack = if let Some(node) = self.nodes.first() {
             vec![(L::root(), *node)]
@@ -762,7 +892,9 @@ where
         };
         ChunksInBoundAndTreeIterMut {
             stack,
-            tree: self,
+            nodes: &self.nodes,
+            chunks: NodeChunksMut::new(&mut self.chunks),
+            cache: PhantomData,
             max_depth,
             bound_min,
             bound_max,
@@ -951,4 +1083,56 @@ mod tests {
             println!("  filled {:?}", filled_voxels);
         }
     }
+
+    #[test]
+    fn bounds_mut() {
+        let mut tree = Tree::<u32, QuadVec>::new(0);
+        while tree.prepare_update(&[QuadVec::new(0, 0, 3)], 1, &mut |_| 0) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        // every chunk in the bounds is visited once
+        let (min, max) = (QuadVec::new(0, 0, 3), QuadVec::new(2, 2, 3));
+        let mut visited = 0;
+        for (_, chunk) in tree.iter_all_chunks_in_bounds_and_tree_mut(min, max, 3) {
+            *chunk += 1;
+            visited += 1;
+        }
+        for (_, chunk) in tree.iter_all_chunks_in_bounds_and_maybe_tree_mut(min, max, 3) {
+            if let Some(chunk) = chunk {
+                *chunk += 1;
+            }
+        }
+
+        assert!(visited > 0);
+        assert_eq!(tree.iter_chunks().filter(|x| **x == 2).count(), visited);
+        assert_eq!(tree.iter_chunks().filter(|x| **x == 1).count(), 0);
+
+        // and in the same order as the immutable iterators
+        let positions = tree
+            .iter_all_chunks_in_bounds_and_tree(min, max, 3)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+        let positions_mut = tree
+            .iter_all_chunks_in_bounds_and_tree_mut(min, max, 3)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, positions_mut);
+
+        // the mutable group iterators give the same chunks as the getters
+        tree.prepare_update(&[QuadVec::new(7, 7, 3)], 1, &mut |_| 0);
+        assert!(tree.get_num_chunks_to_remove() > 0);
+        for (i, (chunk, position)) in tree.iter_chunks_to_remove_and_positions_mut().enumerate() {
+            *chunk = i as u32 + 10;
+            assert!(position.depth > 0);
+        }
+        for i in 0..tree.get_num_chunks_to_remove() {
+            assert_eq!(*tree.get_chunk_to_remove(i), i as u32 + 10);
+        }
+        assert_eq!(
+            tree.iter_chunks_to_deactivate_mut().len(),
+            tree.get_num_chunks_to_deactivate()
+        );
+    }
 }
@@ -33,9 +33,11 @@
 //! The given LodVec implementations (OctVec and QuadVec) take in 4 and 3 arguments respectively.
 //! The first 3/2 are the position in the tree, which is dependant on the lod level.
 //! and the last parameter is the lod level. No lods smaller than this will be generated for this target.
+//! To give targets their own detail and max depth, pass them as `Target`s instead, together with the `TargetDetail` metric.
 //!
 //! Detail: The amount of detail for the targets.
 //! The default implementation defines this as the amount of chunks at the target lod level surrounding the target chunk.
+//! This can be any LodMetric, such as a closure taking the node, its depth and the target, if a different shape or error metric is needed.
 //!
 //! Chunk creator:
 //! Internally a buffer for new chunks is filled, and this function is called to create the new chunk.
@@ -55,7 +57,8 @@
 //!
 //! Now, the tree is ready for an update, so now we'll want to do something with that.
 //! First, we want to process all chunks that are going to be added.
-//! This is the only thing the API exposes as a slice, so we can nicely iterate over that in parallel with rayon.
+//! This is exposed as a slice, so we can nicely iterate over that in parallel with rayon.
+//! With the `rayon` feature, all chunk groups can be iterated over in parallel, see Iterators.
 //! ```rust
 //! # use lodtree::*;
 //! # use lodtree::coords::QuadVec;
@@ -73,6 +76,10 @@
 //!     });
 //! ```
 //!
+//! The chunks to add are ordered with the ones closest to the targets first, so those can be made first.
+//! To spread the work over multiple updates, `tree.set_max_subdivisions(Some(n))` only subdivides the n most important nodes in each update.
+//! A custom metric can decide what's most important with `LodMetric::priority`.
+//!
 //! Next, we'll also want to change the visibility of some chunks so they don't overlap with higher detail lods.
 //! ```rust
 //! # use lodtree::*;
@@ -107,6 +114,11 @@
 //! ```
 //! And finally, actually update the tree with the new chunks.
 //! Note that it's likely needed to do the prepare_update and do_update cycle a number of times before no new chunks need to be added, as the tree only adds one lod level at a time.
+//! To do the whole update in one cycle instead, use `tree.set_update_mode(UpdateMode::SinglePass)`.
+//! In that case, chunks to add that are subdivided in the same update have `active` set to false.
+//! To keep neighbouring chunks within one lod level of each other, use `tree.set_balanced(true)`.
+//! After do_update, the chunks that are next to a chunk that changed lod level are in the `chunks_to_restitch` group,
+//! so the seams between them can be fixed. `tree.get_face_mask_of_chunk_to_restitch(index)` tells which faces border a coarser or finer chunk.
 //! ```rust
 //! # use lodtree::*;
 //! # use lodtree::coords::QuadVec;
@@ -114,6 +126,10 @@
 //! # let mut tree = Tree::<Chunk, QuadVec>::new(64);
 //! tree.do_update();
 //! ```
+//! If the targets moved before the chunks to add are done, `tree.revalidate_update` prepares the update again for the new targets.
+//! Chunks to add that are still needed are kept, and the rest is returned without being added to the tree.
+//! `tree.cancel_update()` discards the prepared update entirely, and returns all chunks to add.
+//! Either can be put in the cache with `tree.insert_into_cache`, to be used again later.
 //! But we're not done yet!
 //! After this step there's a number of chunks that are removed from the cache, and will not be added back into the tree
 //! We'll want to clean those up now
@@ -133,6 +149,11 @@
 //! // and finally, complete the entire update
 //! tree.complete_update();
 //! ```
+//!
+//! # Lifecycle
+//! Instead of going through all these steps by hand, implement `ChunkLifecycle` for something, and pass it to `tree.update(targets, detail, &mut lifecycle)`.
+//! This runs the whole cycle until no more updates are needed, calling `create`, `on_add`, `on_activate`, `on_deactivate`, `on_cache` and `on_delete` along the way.
+//! With the `rayon` feature, `tree.update_par` runs `on_add` for all chunks to add in parallel.
 //! # Caching
 //! When making a new tree, you can specify an internal cache size as follows:
 //! ```rust
@@ -149,6 +170,81 @@
 //! Caching is most effective with a larger cache size as well as the target position moving around in roughly the same area.
 //! Of course, it comes at a memory tradeoff, as it will keep all chunks in the cache stored in memory
 //!
+//! By default the least recently used chunk is dropped from the cache first.
+//! To pick which chunks are dropped, pass any `ChunkCache` to `Tree::with_cache`, such as `FifoCache` or `CostAwareCache`, which keeps the chunks that are the most expensive to make.
+//! If chunks differ a lot in size, `WeightedCache` limits the cache by the total weight of the chunks, such as the number of bytes they take up, instead of the number of chunks.
+//! Chunks dropped from the cache end up in `chunks_to_delete`.
+//!
+//! Chunks evicted from the cache are normally gone, to keep them around on disk instead, use a `TieredCache` with a `ChunkStore` such as `DirectoryStore`.
+//! Evicted chunks are then written to the store, and loaded from it before they are made again.
+//! `DirectoryStore` names the file of each chunk after it's position, for any position that implements `StoreKey`.
+//!
+//! # Serialization
+//! With the `serde` feature enabled, QuadVec, OctVec and Tree implement Serialize and Deserialize.
+//! The tree is stored with all chunks in it and in the cache, but without any pending update, so serialize it after complete_update().
+//!
+//! For large trees, there's also a compact binary snapshot format, see `Tree::write_snapshot` and `Tree::read_snapshot`.
+//! This stores which nodes have children as a single bit per node, and leaves writing the chunks themselves to a callback.
+//!
+//! # Deferred generation
+//! If chunks are made on another thread, use `tree.set_update_mode(UpdateMode::Deferred)`.
+//! The chunk creator then only needs to start making the chunk, and new chunks are pending until `tree.poll_pending` reports them as ready.
+//! The children of a node are only added once all of them are ready, so the node stays visible until then, and there are no holes in the tree.
+//! ```rust
+//! # use lodtree::*;
+//! # use lodtree::coords::QuadVec;
+//! # struct Job {}
+//! # impl Job {
+//! #     fn spawn(pos: QuadVec) -> Self { Job {} }
+//! #     fn is_done(&mut self) -> bool { true }
+//! # }
+//! let mut tree = Tree::<Job, QuadVec>::new(64);
+//! tree.set_update_mode(UpdateMode::Deferred);
+//!
+//! // every frame
+//! tree.poll_pending(&mut |_, job| job.is_done());
+//! if tree.prepare_update(&[QuadVec::new(8, 8, 4)], 4, &mut |pos| Job::spawn(pos)) {
+//!     tree.do_update();
+//! }
+//! // chunks that were cancelled before they were done are in chunks_to_delete
+//! tree.complete_update();
+//! ```
+//!
+//! # Editing
+//! When part of the world changes, `tree.prepare_edit(min, max)` finds all chunks in the tree that overlap the edited region, at every depth, and puts them in `chunks_to_edit`.
+//! Chunks in the cache that overlap the region are put in `chunks_to_delete`, so outdated chunks don't come back later.
+//! ```rust
+//! # use lodtree::*;
+//! # use lodtree::coords::QuadVec;
+//! # struct Chunk {}
+//! # impl Chunk {
+//! #     fn rebuild(&mut self, pos: QuadVec) {}
+//! # }
+//! # let mut tree = Tree::<Chunk, QuadVec>::new(64);
+//! if tree.prepare_edit(QuadVec::new(2, 2, 4), QuadVec::new(3, 3, 4)) {
+//!     for (chunk, position) in tree.iter_chunks_to_edit_and_positions_mut() {
+//!         chunk.rebuild(position);
+//!     }
+//! }
+//! tree.complete_update();
+//! ```
+//!
+//! # World space
+//! QuadVec and OctVec only cover the unit square and cube. To place the root in the world, use a `WorldMapping`,
+//! which converts signed world positions to QuadVec and OctVec, and positions in the tree back to world space boxes.
+//! Positions outside of the root are rejected, instead of being truncated.
+//!
+//! # Floating origin
+//! When the world origin moves, `tree.shift_origin(offset, depth, chunk_creator)` moves the origin of the tree by a whole number of nodes at the given depth.
+//! Chunks that are still inside the root keep their chunk and get a new position, and the ones that fell out end up in `chunks_to_delete`.
+//! New chunks that came into the root are put in `chunks_to_activate` if they are leaves, and in `chunks_to_deactivate` otherwise.
+//!
+//! # Grids
+//! A single tree has a fixed root, so it can only cover a limited part of the world.
+//! For bigger or infinite worlds, `TreeGrid` keeps a tree for each region around the targets, keyed by integer region coordinates.
+//! Targets are passed along with the region they are in, and regions are made and dropped as the targets move.
+//! The chunk groups of all regions are merged, so they can be handled the same way as a single tree.
+//!
 //! # Chunk groups
 //! There's several groups of chunks that can be accessed inside the tree.
 //! - `chunks`: All chunks currently stored inside the tree
@@ -157,20 +253,59 @@
 //! - `chunks_to_activate`: Chunks that were previously subdivided, but are now going to be leaf nodes. This means they should be visible again
 //! - `chunks_to_remove`: Chunks that will be removed from the tree after the next `tree.do_update()`. Note that these can be put in the chunk cache and appear in `chunks_to_add` at a later point
 //! - `chunks_to_delete`: Chunks that are permanently removed from the tree, as they were removed from the tree itself, and will now also be removed from the chunk cache
+//! - `chunks_to_edit`: Chunks in the tree that overlap the region of the last `tree.prepare_edit(min, max)`, at every depth, so they can be made again after an edit
+//! - `chunks_to_restitch`: Leaf chunks that border a chunk that changed lod level after the last `tree.do_update()`, along with which faces border a coarser or finer chunk
 //!
-//! Cached chunks are also stored seperate from the tree, inside a HashMap. These can't be accessed.
+//! Cached chunks are also stored seperate from the tree, inside the chunk cache.
+//! These can be accessed with `tree.iter_cached_chunks()` and `tree.get_cached_chunk(position)`,
+//! and `tree.insert_into_cache`, `tree.take_cached_chunk` and `tree.evict_cached` allow filling the cache ahead of time or removing outdated chunks from it.
 //!
 //! # Iterators
 //! Iterators are provided for each chunk group, in the flavour of chunks, mutable chunks, chunk and positions and mutable chunk and positions.
+//! With the `rayon` feature, there's also parallel iterators for each chunk group, such as `tree.par_iter_chunks_to_activate_mut()`,
+//! in the flavour of chunks, mutable chunks, chunk and positions and mutable chunk and positions.
 //!
 //! # Getters
 //! Getters are also given for all chunk groups, in the flavor of get a chunk, get a mutable chunk, get a mutable pointer to a chunk and get the position of a chunk.
+//! To mutate several chunks at once without raw pointers, use `tree.get_many_mut([positions])`,
+//! or `tree.split_mut()` to get seperate mutable views of the chunks in the tree, the pending chunks, and the chunks to add and to delete.
+//! get_many_mut returns a `GetManyMutError` if a position isn't in the tree, or is given twice.
+//! These panic on an index that's out of bounds, the `try_` variants, such as `tree.try_get_chunk_to_add(index)`, return a `lodtree::Error` instead.
+//!
+//! # Errors
+//! `tree.try_prepare_update` and `tree.try_do_update` check that the prepare_update, do_update, complete_update cycle is followed,
+//! such as do_update being called without a prepared update, and return an `Error` if it isn't.
+//! try_prepare_update also rejects targets deeper than `TREE_MAX_DEPTH`.
+//! `SnapshotError` wraps an `Error` for snapshots the tree can't hold, such as `Error::TooDeep`, while `WorldMapping` has it's own `WorldMappingError`.
+//!
+//! # Neighbours
+//! `LodVec::neighbor` gets the node next to another node at the same depth, across a face, edge or corner.
+//! To get the chunks that are actually next to a chunk in the tree, use `tree.get_neighbors(position)`.
+//! This returns the leaf chunks across each face, which can be bigger or smaller than the chunk itself.
 
+pub mod cache;
 pub mod coords;
+pub mod error;
+pub mod grid;
 pub mod iter;
+pub mod lifecycle;
+#[cfg(feature = "rayon")]
+pub mod par;
+pub mod snapshot;
+pub mod split;
+pub mod store;
 pub mod traits;
 pub mod tree;
+pub mod world;
 
+pub use crate::cache::*;
+pub use crate::error::*;
+pub use crate::grid::*;
 pub use crate::iter::*;
+pub use crate::lifecycle::*;
+pub use crate::snapshot::*;
+pub use crate::split::*;
+pub use crate::store::*;
 pub use crate::traits::*;
 pub use crate::tree::*;
+pub use crate::world::*;
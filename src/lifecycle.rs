@@ -0,0 +1,308 @@
+//! Contains the ChunkLifecycle trait, which has a hook for everything that happens to a chunk during an update,
+//! and Tree::update, which runs the whole update cycle with it.
+
+use crate::cache::*;
+use crate::traits::*;
+use crate::tree::*;
+
+/// hooks for everything that happens to chunks during an update, used by Tree::update.
+/// All hooks except create do nothing by default.
+///
+/// As an example, a lifecycle for chunks that are made visible and invisible:
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::QuadVec;
+/// struct Chunk {
+///     visible: bool,
+/// }
+///
+/// struct Lifecycle;
+///
+/// impl ChunkLifecycle<QuadVec> for Lifecycle {
+///     type Chunk = Chunk;
+///
+///     fn create(&mut self, _: QuadVec) -> Chunk {
+///         Chunk { visible: true }
+///     }
+///
+///     fn on_activate(&mut self, _: QuadVec, chunk: &mut Chunk) {
+///         chunk.visible = true;
+///     }
+///
+///     fn on_deactivate(&mut self, _: QuadVec, chunk: &mut Chunk) {
+///         chunk.visible = false;
+///     }
+/// }
+///
+/// let mut tree = Tree::<Chunk, QuadVec>::new(64);
+/// tree.update(&[QuadVec::new(8, 8, 4)], 4, &mut Lifecycle);
+/// ```
+pub trait ChunkLifecycle<L: LodVec> {
+    /// the chunks in the tree
+    type Chunk;
+
+    /// creates a new chunk at the given position.
+    /// This should be cheap, as the expensive work can be done in on_add.
+    /// It's not called for chunks that come from the cache
+    fn create(&mut self, position: L) -> Self::Chunk;
+
+    /// called for every chunk before it's added to the tree, including the ones that come from the cache.
+    /// New chunks are visible, unless they are subdivided in the same update, in which case on_deactivate is called after this.
+    ///
+    /// This takes &self, so Tree::update_par can run it in parallel
+    #[inline]
+    fn on_add(&self, _position: L, _chunk: &mut Self::Chunk) {}
+
+    /// called for every chunk that lost it's children, and needs to be visible again
+    #[inline]
+    fn on_activate(&mut self, _position: L, _chunk: &mut Self::Chunk) {}
+
+    /// called for every chunk that was subdivided, and needs to be invisible
+    #[inline]
+    fn on_deactivate(&mut self, _position: L, _chunk: &mut Self::Chunk) {}
+
+    /// called for every chunk that is removed from the tree, and is put in the cache.
+    /// It can come back later, in which case on_add is called for it again
+    #[inline]
+    fn on_cache(&mut self, _position: L, _chunk: &mut Self::Chunk) {}
+
+    /// called for every chunk that is permanently removed, such as when it didn't fit in the cache anymore
+    #[inline]
+    fn on_delete(&mut self, _position: L, _chunk: Self::Chunk) {}
+}
+
+impl<C, L, K> Tree<C, L, K>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+{
+    /// runs the prepare_update, do_update, complete_update cycle until no more updates are needed,
+    /// calling the hooks of the lifecycle for everything that happens to the chunks.
+    ///
+    /// With UpdateMode::Deferred, this stops once no pending chunks are ready, so it still needs to be called again once they are.
+    /// The max subdivisions apply to every cycle, so use prepare_update directly to spread the work over multiple frames.
+    /// # Params
+    /// * `targets` The target positions to generate the lod around, see prepare_update
+    /// * `metric` Decides wether a node subdivides for a target, see prepare_update
+    /// * `lifecycle` The hooks to call
+    ///
+    /// returns the number of updates that were done.
+    pub fn update<T: Copy, M: LodMetric<L, T>, H: ChunkLifecycle<L, Chunk = C>>(
+        &mut self,
+        targets: &[T],
+        metric: M,
+        lifecycle: &mut H,
+    ) -> usize {
+        self.update_with(
+            targets,
+            &metric,
+            lifecycle,
+            |chunks_to_add: &mut [ToAddContainer<C, L>], lifecycle: &H| {
+                for ToAddContainer {
+                    position, chunk, ..
+                } in chunks_to_add.iter_mut()
+                {
+                    lifecycle.on_add(*position, chunk);
+                }
+            },
+        )
+    }
+
+    /// same as update, but runs on_add for all chunks to add in parallel, with rayon
+    #[cfg(feature = "rayon")]
+    pub fn update_par<T: Copy, M: LodMetric<L, T>, H: ChunkLifecycle<L, Chunk = C> + Sync>(
+        &mut self,
+        targets: &[T],
+        metric: M,
+        lifecycle: &mut H,
+    ) -> usize
+    where
+        C: Send,
+    {
+        use rayon::prelude::*;
+
+        self.update_with(
+            targets,
+            &metric,
+            lifecycle,
+            |chunks_to_add: &mut [ToAddContainer<C, L>], lifecycle: &H| {
+                chunks_to_add.par_iter_mut().for_each(
+                    |ToAddContainer {
+                         position, chunk, ..
+                     }| lifecycle.on_add(*position, chunk),
+                );
+            },
+        )
+    }
+
+    // runs the update cycle, with the given function to call on_add with
+    fn update_with<T: Copy, M: LodMetric<L, T>, H: ChunkLifecycle<L, Chunk = C>, A>(
+        &mut self,
+        targets: &[T],
+        metric: &M,
+        lifecycle: &mut H,
+        mut add: A,
+    ) -> usize
+    where
+        A: FnMut(&mut [ToAddContainer<C, L>], &H),
+    {
+        let mut updates = 0;
+        loop {
+            let needs_update = self
+                .prepare_update_by_ref(targets, metric, &mut |position| lifecycle.create(position));
+
+            if needs_update {
+                add(self.get_chunks_to_add_slice_mut(), lifecycle);
+
+                // chunks that are subdivided in the same update aren't visible
+                for ToAddContainer {
+                    position,
+                    chunk,
+                    active,
+                    ..
+                } in self.get_chunks_to_add_slice_mut()
+                {
+                    if !*active {
+                        lifecycle.on_deactivate(*position, chunk);
+                    }
+                }
+
+                for (chunk, position) in self.iter_chunks_to_activate_and_positions_mut() {
+                    lifecycle.on_activate(position, chunk);
+                }
+                for (chunk, position) in self.iter_chunks_to_deactivate_and_positions_mut() {
+                    lifecycle.on_deactivate(position, chunk);
+                }
+                for (chunk, position) in self.iter_chunks_to_remove_and_positions_mut() {
+                    lifecycle.on_cache(position, chunk);
+                }
+
+                self.do_update();
+                updates += 1;
+            }
+
+            // chunks can also be deleted when preparing the update, so always clean those up
+            for ToDeleteContainer { position, chunk } in self.drain_chunks_to_delete() {
+                lifecycle.on_delete(position, chunk);
+            }
+            self.complete_update();
+
+            if !needs_update {
+                return updates;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::*;
+
+    // chunk that knows if it's visible
+    struct Chunk {
+        position: QuadVec,
+        visible: bool,
+        cached: bool,
+    }
+
+    // counts how many chunks exist
+    #[derive(Default)]
+    struct Counter {
+        created: usize,
+        deleted: usize,
+    }
+
+    impl ChunkLifecycle<QuadVec> for Counter {
+        type Chunk = Chunk;
+
+        fn create(&mut self, position: QuadVec) -> Chunk {
+            self.created += 1;
+            Chunk {
+                position,
+                visible: false,
+                cached: false,
+            }
+        }
+
+        fn on_add(&self, position: QuadVec, chunk: &mut Chunk) {
+            assert_eq!(chunk.position, position);
+            chunk.visible = true;
+            chunk.cached = false;
+        }
+
+        fn on_activate(&mut self, _: QuadVec, chunk: &mut Chunk) {
+            chunk.visible = true;
+        }
+
+        fn on_deactivate(&mut self, _: QuadVec, chunk: &mut Chunk) {
+            chunk.visible = false;
+        }
+
+        fn on_cache(&mut self, _: QuadVec, chunk: &mut Chunk) {
+            chunk.cached = true;
+        }
+
+        fn on_delete(&mut self, position: QuadVec, chunk: Chunk) {
+            assert_eq!(chunk.position, position);
+            self.deleted += 1;
+        }
+    }
+
+    // checks that exactly the leaves are visible, and that all chunks are accounted for
+    fn check(tree: &Tree<Chunk, QuadVec>, counter: &Counter) {
+        for i in 0..tree.get_num_chunks() {
+            let (chunk, position) = (tree.get_chunk(i), tree.get_chunk_position(i));
+            let leaf = tree
+                .get_chunk_from_position(position.get_child(0))
+                .is_none();
+            assert_eq!(chunk.visible, leaf);
+            assert!(!chunk.cached);
+        }
+
+        assert_eq!(
+            counter.created - counter.deleted,
+            tree.get_num_chunks() + tree.get_num_cached_chunks()
+        );
+    }
+
+    #[test]
+    fn update() {
+        for mode in [UpdateMode::OneLevel, UpdateMode::SinglePass] {
+            let mut tree = Tree::new(8);
+            tree.set_update_mode(mode);
+            let mut counter = Counter::default();
+
+            // runs until there's nothing left to do
+            assert!(tree.update(&[QuadVec::new(0, 0, 4)], 1, &mut counter) > 0);
+            check(&tree, &counter);
+            assert_eq!(tree.update(&[QuadVec::new(0, 0, 4)], 1, &mut counter), 0);
+
+            // moving the target removes chunks, and fills the cache
+            tree.update(&[QuadVec::new(15, 15, 4)], 1, &mut counter);
+            check(&tree, &counter);
+            assert!(counter.deleted > 0);
+            assert_eq!(tree.get_num_cached_chunks(), 8);
+
+            // which are used when moving back
+            let created = counter.created;
+            tree.update(&[QuadVec::new(0, 0, 4)], 1, &mut counter);
+            check(&tree, &counter);
+            assert!(counter.created - created < tree.get_num_chunks());
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn update_par() {
+        let mut tree = Tree::new(8);
+        let mut counter = Counter::default();
+        tree.update_par(&[QuadVec::new(3, 5, 4)], 1, &mut counter);
+        check(&tree, &counter);
+
+        let mut expected = Tree::new(8);
+        expected.update(&[QuadVec::new(3, 5, 4)], 1, &mut Counter::default());
+        assert_eq!(tree.get_num_chunks(), expected.get_num_chunks());
+    }
+}
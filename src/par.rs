@@ -0,0 +1,312 @@
+//! Parallel iterators over chunks, with rayon
+//!
+//! These are the same as the iterators in iter.rs, but are indexed parallel iterators.
+//! The mutable iterators over chunks that are stored in the tree first split the chunks in the group off from the tree, as the tree does not store them together.
+
+use crate::cache::*;
+use crate::split::*;
+use crate::traits::*;
+use crate::tree::*;
+
+use rayon::prelude::*;
+
+// implements all parallel iterators for a group of chunks in the tree, stored as node indices
+macro_rules! impl_par_iterators {
+    (
+		$group:ident,
+		|$x:ident| $node:expr,
+		$(#[$doc:meta])*
+		$func_name:ident,
+		$(#[$doc_mut:meta])*
+		$func_name_mut:ident,
+		$(#[$doc_chunk_and_pos:meta])*
+		$func_name_chunk_and_pos:ident,
+		$(#[$doc_chunk_and_pos_mut:meta])*
+		$func_name_chunk_and_pos_mut:ident,
+	) => {
+        impl<C, L, K> Tree<C, L, K>
+        where
+            C: Sized,
+            L: LodVec,
+            K: ChunkCache<C, L>,
+        {
+			#[inline]
+			$(#[$doc])*
+			pub fn $func_name(&self) -> impl IndexedParallelIterator<Item = &C> + '_
+			where
+				C: Sync,
+			{
+				self.$func_name_chunk_and_pos().map(|(chunk, _)| chunk)
+			}
+
+			#[inline]
+			$(#[$doc_mut])*
+			pub fn $func_name_mut(&mut self) -> impl IndexedParallelIterator<Item = &mut C> + '_
+			where
+				C: Send,
+			{
+				self.$func_name_chunk_and_pos_mut().map(|(chunk, _)| chunk)
+			}
+
+			#[inline]
+			$(#[$doc_chunk_and_pos])*
+			pub fn $func_name_chunk_and_pos(&self) -> impl IndexedParallelIterator<Item = (&C, L)> + '_
+			where
+				C: Sync,
+			{
+				let (nodes, chunks) = (&self.nodes, &self.chunks);
+				self.$group.par_iter().map(move |$x| {
+					let container = &chunks[nodes[$node as usize].chunk as usize];
+					(&container.chunk, container.position)
+				})
+			}
+
+			#[inline]
+			$(#[$doc_chunk_and_pos_mut])*
+			pub fn $func_name_chunk_and_pos_mut(
+				&mut self,
+			) -> impl IndexedParallelIterator<Item = (&mut C, L)> + '_
+			where
+				C: Send,
+			{
+				chunks_of_nodes_mut(
+					&self.nodes,
+					&mut self.chunks,
+					&mut self.group_order,
+					self.$group.iter().map(|$x| $node),
+				)
+				.collect::<Vec<_>>()
+				.into_par_iter()
+			}
+        }
+    };
+}
+
+// implements all parallel iterators for a group of chunks that are stored with their position
+macro_rules! impl_par_container_iterators {
+    (
+		$group:ident,
+		$(#[$doc:meta])*
+		$func_name:ident,
+		$(#[$doc_mut:meta])*
+		$func_name_mut:ident,
+		$(#[$doc_chunk_and_pos:meta])*
+		$func_name_chunk_and_pos:ident,
+		$(#[$doc_chunk_and_pos_mut:meta])*
+		$func_name_chunk_and_pos_mut:ident,
+	) => {
+        impl<C, L, K> Tree<C, L, K>
+        where
+            C: Sized,
+            L: LodVec,
+            K: ChunkCache<C, L>,
+        {
+			#[inline]
+			$(#[$doc])*
+			pub fn $func_name(&self) -> impl IndexedParallelIterator<Item = &C> + '_
+			where
+				C: Sync,
+			{
+				self.$group.par_iter().map(|x| &x.chunk)
+			}
+
+			#[inline]
+			$(#[$doc_mut])*
+			pub fn $func_name_mut(&mut self) -> impl IndexedParallelIterator<Item = &mut C> + '_
+			where
+				C: Send,
+			{
+				self.$group.par_iter_mut().map(|x| &mut x.chunk)
+			}
+
+			#[inline]
+			$(#[$doc_chunk_and_pos])*
+			pub fn $func_name_chunk_and_pos(&self) -> impl IndexedParallelIterator<Item = (&C, L)> + '_
+			where
+				C: Sync,
+			{
+				self.$group.par_iter().map(|x| (&x.chunk, x.position))
+			}
+
+			#[inline]
+			$(#[$doc_chunk_and_pos_mut])*
+			pub fn $func_name_chunk_and_pos_mut(
+				&mut self,
+			) -> impl IndexedParallelIterator<Item = (&mut C, L)> + '_
+			where
+				C: Send,
+			{
+				self.$group.par_iter_mut().map(|x| (&mut x.chunk, x.position))
+			}
+        }
+    };
+}
+
+// chunks
+impl_par_container_iterators!(
+    chunks,
+    /// returns a parallel iterator over all chunks
+    par_iter_chunks,
+    /// returns a parallel iterator over all chunks, mutable
+    par_iter_chunks_mut,
+    /// returns a parallel iterator over all chunks and their positions
+    par_iter_chunks_and_positions,
+    /// returns a parallel iterator over all chunks as mutable and their positions
+    par_iter_chunks_and_positions_mut,
+);
+
+// to activate
+impl_par_iterators!(
+    chunks_to_activate,
+    |x| *x,
+    /// returns a parallel iterator over all chunks to activate
+    par_iter_chunks_to_activate,
+    /// returns a parallel iterator over all chunks to activate, mutable
+    par_iter_chunks_to_activate_mut,
+    /// returns a parallel iterator over all chunks to activate and their positions
+    par_iter_chunks_to_activate_and_positions,
+    /// returns a parallel iterator over all chunks to activate as mutable and their positions
+    par_iter_chunks_to_activate_and_positions_mut,
+);
+
+// to deactivate
+impl_par_iterators!(
+    chunks_to_deactivate,
+    |x| *x,
+    /// returns a parallel iterator over all chunks to deactivate
+    par_iter_chunks_to_deactivate,
+    /// returns a parallel iterator over all chunks to deactivate, mutable
+    par_iter_chunks_to_deactivate_mut,
+    /// returns a parallel iterator over all chunks to deactivate and their positions
+    par_iter_chunks_to_deactivate_and_positions,
+    /// returns a parallel iterator over all chunks to deactivate as mutable and their positions
+    par_iter_chunks_to_deactivate_and_positions_mut,
+);
+
+// to restitch
+impl_par_iterators!(
+    chunks_to_restitch,
+    |x| *x,
+    /// returns a parallel iterator over all chunks to restitch
+    par_iter_chunks_to_restitch,
+    /// returns a parallel iterator over all chunks to restitch, mutable
+    par_iter_chunks_to_restitch_mut,
+    /// returns a parallel iterator over all chunks to restitch and their positions
+    par_iter_chunks_to_restitch_and_positions,
+    /// returns a parallel iterator over all chunks to restitch as mutable and their positions
+    par_iter_chunks_to_restitch_and_positions_mut,
+);
+
+// to edit
+impl_par_iterators!(
+    chunks_to_edit,
+    |x| *x,
+    /// returns a parallel iterator over all chunks to edit
+    par_iter_chunks_to_edit,
+    /// returns a parallel iterator over all chunks to edit, mutable
+    par_iter_chunks_to_edit_mut,
+    /// returns a parallel iterator over all chunks to edit and their positions
+    par_iter_chunks_to_edit_and_positions,
+    /// returns a parallel iterator over all chunks to edit as mutable and their positions
+    par_iter_chunks_to_edit_and_positions_mut,
+);
+
+// to remove
+impl_par_iterators!(
+    chunks_to_remove,
+    |x| x.chunk,
+    /// returns a parallel iterator over all chunks to remove
+    par_iter_chunks_to_remove,
+    /// returns a parallel iterator over all chunks to remove, mutable
+    par_iter_chunks_to_remove_mut,
+    /// returns a parallel iterator over all chunks to remove and their positions
+    par_iter_chunks_to_remove_and_positions,
+    /// returns a parallel iterator over all chunks to remove as mutable and their positions
+    par_iter_chunks_to_remove_and_positions_mut,
+);
+
+// to add
+impl_par_container_iterators!(
+    chunks_to_add,
+    /// returns a parallel iterator over all chunks to add
+    par_iter_chunks_to_add,
+    /// returns a parallel iterator over all chunks to add, mutable
+    par_iter_chunks_to_add_mut,
+    /// returns a parallel iterator over all chunks to add and their positions
+    par_iter_chunks_to_add_and_positions,
+    /// returns a parallel iterator over all chunks to add as mutable and their positions
+    par_iter_chunks_to_add_and_positions_mut,
+);
+
+// to delete
+impl_par_container_iterators!(
+    chunks_to_delete,
+    /// returns a parallel iterator over all chunks to delete
+    par_iter_chunks_to_delete,
+    /// returns a parallel iterator over all chunks to delete, mutable
+    par_iter_chunks_to_delete_mut,
+    /// returns a parallel iterator over all chunks to delete and their positions
+    par_iter_chunks_to_delete_and_positions,
+    /// returns a parallel iterator over all chunks to delete as mutable and their positions
+    par_iter_chunks_to_delete_and_positions_mut,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::*;
+
+    #[test]
+    fn matches_iterators() {
+        let mut tree = Tree::<u64, QuadVec>::new(16);
+        let mut targets = [QuadVec::new(0, 0, 4), QuadVec::new(15, 15, 4)];
+        for _ in 0..2 {
+            while tree.prepare_update(&targets, 1, &mut |_| 0) {
+                // every group gives the same chunks as the normal iterators
+                tree.par_iter_chunks_to_add_mut().for_each(|x| *x += 1);
+                assert_eq!(
+                    tree.par_iter_chunks_to_add().sum::<u64>(),
+                    tree.get_num_chunks_to_add() as u64
+                );
+
+                let activate = tree.iter_chunks_to_activate_positions().collect::<Vec<_>>();
+                let par_activate = tree
+                    .par_iter_chunks_to_activate_and_positions_mut()
+                    .map(|(_, pos)| pos)
+                    .collect::<Vec<_>>();
+                assert_eq!(activate, par_activate);
+
+                let remove = tree.iter_chunks_to_remove_positions().collect::<Vec<_>>();
+                let par_remove = tree
+                    .par_iter_chunks_to_remove_and_positions()
+                    .map(|(_, pos)| pos)
+                    .collect::<Vec<_>>();
+                assert_eq!(remove, par_remove);
+
+                tree.par_iter_chunks_to_deactivate_mut()
+                    .for_each(|x| *x += 10);
+                assert_eq!(
+                    tree.par_iter_chunks_to_deactivate().len(),
+                    tree.get_num_chunks_to_deactivate()
+                );
+
+                tree.do_update();
+                tree.complete_update();
+            }
+
+            targets.reverse();
+        }
+
+        // all chunks went through the same changes
+        let sequential = tree.iter_chunks().copied().collect::<Vec<_>>();
+        let parallel = tree.par_iter_chunks().copied().collect::<Vec<_>>();
+        assert_eq!(sequential, parallel);
+        assert!(tree.par_iter_chunks().any(|x| *x > 10));
+
+        tree.par_iter_chunks_and_positions_mut()
+            .for_each(|(chunk, pos)| *chunk = pos.depth as u64);
+        assert!(tree
+            .par_iter_chunks_and_positions()
+            .all(|(chunk, pos)| *chunk == pos.depth as u64));
+    }
+}
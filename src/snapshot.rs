@@ -0,0 +1,408 @@
+//! Compact binary snapshots of a tree
+//!
+//! The format is as follows, with all numbers in little endian:
+//! - magic, the bytes `LODT`
+//! - format version, one byte
+//! - `NUM_CHILDREN` of the LodVec the tree was made with, as u32
+//! - depth of the deepest node in the tree, one byte
+//! - number of nodes in the tree, as u64
+//! - one bit per node, in breadth first order, that is set if the node has children. Padded to whole bytes
+//! - the chunk payloads, in the same breadth first order, written by the given callback
+
+use crate::cache::*;
+use crate::error::*;
+use crate::traits::*;
+use crate::tree::*;
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::num::NonZeroU32;
+
+/// magic bytes at the start of every snapshot
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"LODT";
+
+/// current version of the snapshot format
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// deepest depth a snapshot may contain, which is the same as TREE_MAX_DEPTH
+pub const SNAPSHOT_MAX_DEPTH: u8 = TREE_MAX_DEPTH;
+
+/// Error returned when a snapshot can't be written or read
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// error from the underlying reader or writer, or from one of the chunk callbacks
+    Io(std::io::Error),
+
+    /// the snapshot does not start with the magic bytes
+    BadMagic,
+
+    /// the snapshot was made with a version of the format that is not supported
+    UnsupportedVersion(u8),
+
+    /// the snapshot was made for a tree with a different number of children per node
+    ChildCountMismatch {
+        /// number of children the tree that is read has
+        expected: u32,
+        /// number of children in the snapshot
+        found: u32,
+    },
+
+    /// the tree can't hold what's in the snapshot, such as Error::TooDeep if it contains nodes deeper than SNAPSHOT_MAX_DEPTH
+    Tree(Error),
+
+    /// the node structure in the snapshot is not a valid tree
+    Corrupt(&'static str),
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::BadMagic => write!(f, "not a lodtree snapshot"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported snapshot version {}", v),
+            Self::ChildCountMismatch { expected, found } => write!(
+                f,
+                "snapshot has {} children per node, expected {}",
+                found, expected
+            ),
+            Self::Tree(e) => write!(f, "snapshot doesn't fit in the tree: {}", e),
+            Self::Corrupt(reason) => write!(f, "corrupt snapshot: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Tree(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<Error> for SnapshotError {
+    fn from(e: Error) -> Self {
+        Self::Tree(e)
+    }
+}
+
+impl<C, L, K> Tree<C, L, K>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+{
+    /// Writes the nodes and chunks in the tree to a compact snapshot.
+    /// The cache and any pending update are not written.
+    /// It's a good idea to pass a buffered writer here, as the snapshot is written in small pieces.
+    /// # Params
+    /// * `writer` where to write the snapshot to
+    /// * `chunk_writer` function to write a chunk at the given position, called for every chunk in breadth first order
+    pub fn write_snapshot<W: Write>(
+        &self,
+        writer: &mut W,
+        chunk_writer: &mut dyn FnMut(&mut W, L, &C) -> std::io::Result<()>,
+    ) -> Result<(), SnapshotError> {
+        // walk the tree breadth first, to get the node order
+        // holds the node index, position and depth
+        let mut order = Vec::with_capacity(self.chunks.len());
+        if !self.nodes.is_empty() {
+            order.push((0u32, L::root(), 0u8));
+        }
+
+        let mut i = 0;
+        while let Some(&(node, position, depth)) = order.get(i) {
+            if let Some(children) = self.nodes[node as usize].children {
+                for j in 0..position.num_children() {
+                    order.push((children.get() + j, position.get_child(j), depth + 1));
+                }
+            }
+            i += 1;
+        }
+
+        // the deepest node determines the depth
+        let depth = order.last().map_or(0, |x| x.2);
+        if depth > SNAPSHOT_MAX_DEPTH {
+            return Err(Error::TooDeep(depth).into());
+        }
+
+        // header
+        writer.write_all(&SNAPSHOT_MAGIC)?;
+        writer.write_all(&[SNAPSHOT_VERSION])?;
+        writer.write_all(&L::NUM_CHILDREN.to_le_bytes())?;
+        writer.write_all(&[depth])?;
+        writer.write_all(&(order.len() as u64).to_le_bytes())?;
+
+        // which nodes have children, packed in bits
+        let mut flags = vec![0u8; order.len().div_ceil(8)];
+        for (i, (node, _, _)) in order.iter().enumerate() {
+            if self.nodes[*node as usize].children.is_some() {
+                flags[i / 8] |= 1 << (i % 8);
+            }
+        }
+        writer.write_all(&flags)?;
+
+        // and the chunks themselves
+        for (node, position, _) in order {
+            chunk_writer(
+                writer,
+                position,
+                &self.chunks[self.nodes[node as usize].chunk as usize].chunk,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a tree from a snapshot made with write_snapshot.
+    /// Returns an error if the snapshot is invalid, instead of panicking.
+    /// # Params
+    /// * `reader` where to read the snapshot from
+    /// * `cache` the cache of the new tree, see Tree::with_cache
+    /// * `chunk_reader` function to read a chunk at the given position, called for every chunk in breadth first order
+    pub fn read_snapshot<R: Read>(
+        reader: &mut R,
+        cache: K,
+        chunk_reader: &mut dyn FnMut(&mut R, L) -> std::io::Result<C>,
+    ) -> Result<Self, SnapshotError> {
+        // header
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+        if version[0] != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version[0]));
+        }
+
+        let mut num_children = [0; 4];
+        reader.read_exact(&mut num_children)?;
+        let num_children = u32::from_le_bytes(num_children);
+        if num_children != L::NUM_CHILDREN {
+            return Err(SnapshotError::ChildCountMismatch {
+                expected: L::NUM_CHILDREN,
+                found: num_children,
+            });
+        }
+
+        let mut depth = [0; 1];
+        reader.read_exact(&mut depth)?;
+        let depth = depth[0];
+        if depth > SNAPSHOT_MAX_DEPTH {
+            return Err(Error::TooDeep(depth).into());
+        }
+
+        let mut num_nodes = [0; 8];
+        reader.read_exact(&mut num_nodes)?;
+        let num_nodes = u64::from_le_bytes(num_nodes);
+        if num_nodes > u32::MAX as u64 {
+            return Err(SnapshotError::Corrupt("too many nodes"));
+        }
+        let num_nodes = num_nodes as usize;
+
+        // flags, read without trusting the length up front
+        let mut flags = Vec::new();
+        reader
+            .take(num_nodes.div_ceil(8) as u64)
+            .read_to_end(&mut flags)?;
+        if flags.len() != num_nodes.div_ceil(8) {
+            return Err(SnapshotError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        // rebuild the nodes, breadth first
+        // because children are written right after each other, the node index is the same as the index in the snapshot
+        let mut tree = Self::with_cache(cache);
+        let mut queue = VecDeque::new();
+        if num_nodes > 0 {
+            queue.push_back((L::root(), 0u8));
+        }
+
+        // index of the next node to be given out as child
+        let mut next = 1usize;
+
+        while let Some((position, node_depth)) = queue.pop_front() {
+            let index = tree.nodes.len();
+            let has_children = flags[index / 8] & (1 << (index % 8)) != 0;
+
+            let children = if has_children {
+                if node_depth >= depth {
+                    return Err(SnapshotError::Corrupt(
+                        "node deeper than the snapshot depth",
+                    ));
+                }
+                let num_children = position.num_children() as usize;
+                if next + num_children > num_nodes {
+                    return Err(SnapshotError::Corrupt("more children than nodes"));
+                }
+
+                for i in 0..num_children as u32 {
+                    queue.push_back((position.get_child(i), node_depth + 1));
+                }
+
+                next += num_children;
+                NonZeroU32::new((next - num_children) as u32)
+            } else {
+                None
+            };
+
+            tree.nodes.push(TreeNode {
+                children,
+                chunk: index as u32,
+            });
+            tree.chunks.push(ChunkContainer {
+                chunk: chunk_reader(reader, position)?,
+                index: index as u32,
+                position,
+            });
+        }
+
+        if tree.nodes.len() != num_nodes {
+            return Err(SnapshotError::Corrupt("fewer children than nodes"));
+        }
+
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::*;
+
+    fn write_chunk<W: Write>(writer: &mut W, _: OctVec, chunk: &u64) -> std::io::Result<()> {
+        writer.write_all(&chunk.to_le_bytes())
+    }
+
+    fn read_chunk<R: Read>(reader: &mut R, _: OctVec) -> std::io::Result<u64> {
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn make_tree() -> Tree<u64, OctVec> {
+        let mut tree = Tree::new(8);
+        for tgt in [OctVec::new(3, 12, 7, 4), OctVec::new(14, 1, 9, 4)] {
+            while tree.prepare_update(&[tgt], 1, &mut |p| p.x + p.y * 16 + p.z * 256) {
+                tree.do_update();
+                tree.complete_update();
+            }
+        }
+        tree
+    }
+
+    #[test]
+    fn round_trip() {
+        let tree = make_tree();
+
+        let mut bytes = Vec::new();
+        tree.write_snapshot(&mut bytes, &mut write_chunk).unwrap();
+
+        let restored =
+            Tree::<u64, OctVec>::read_snapshot(&mut &bytes[..], LruCache::new(8), &mut read_chunk)
+                .unwrap();
+
+        assert_eq!(tree.get_num_chunks(), restored.get_num_chunks());
+        for position in Tree::<(), OctVec>::iter_all_chunks_in_bounds(
+            OctVec::new(0, 0, 0, 4),
+            OctVec::new(15, 15, 15, 4),
+            4,
+        ) {
+            assert_eq!(
+                tree.get_chunk_from_position(position),
+                restored.get_chunk_from_position(position)
+            );
+        }
+
+        // and an empty tree works too
+        let mut bytes = Vec::new();
+        Tree::<u64, OctVec>::new(0)
+            .write_snapshot(&mut bytes, &mut write_chunk)
+            .unwrap();
+        let restored =
+            Tree::<u64, OctVec>::read_snapshot(&mut &bytes[..], LruCache::new(0), &mut read_chunk)
+                .unwrap();
+        assert_eq!(restored.get_num_chunks(), 0);
+
+        // into a tree with any cache
+        let mut bytes = Vec::new();
+        tree.write_snapshot(&mut bytes, &mut write_chunk).unwrap();
+        let restored = Tree::<u64, OctVec, _>::read_snapshot(
+            &mut &bytes[..],
+            FifoCache::new(4),
+            &mut read_chunk,
+        )
+        .unwrap();
+        assert_eq!(tree.get_num_chunks(), restored.get_num_chunks());
+        assert_eq!(restored.get_cache().size(), 4);
+    }
+
+    #[test]
+    fn corrupt() {
+        let mut bytes = Vec::new();
+        make_tree()
+            .write_snapshot(&mut bytes, &mut write_chunk)
+            .unwrap();
+
+        let read = |bytes: &[u8]| {
+            Tree::<u64, OctVec>::read_snapshot(&mut &bytes[..], LruCache::new(0), &mut read_chunk)
+                .map(|_| ())
+        };
+
+        // magic
+        let mut broken = bytes.clone();
+        broken[0] = b'X';
+        assert!(matches!(read(&broken), Err(SnapshotError::BadMagic)));
+
+        // version
+        let mut broken = bytes.clone();
+        broken[4] = SNAPSHOT_VERSION + 1;
+        assert!(matches!(
+            read(&broken),
+            Err(SnapshotError::UnsupportedVersion(_))
+        ));
+
+        // wrong tree type
+        assert!(matches!(
+            Tree::<u64, QuadVec>::read_snapshot(&mut &bytes[..], LruCache::new(0), &mut |r, _| {
+                read_chunk(r, OctVec::default())
+            }),
+            Err(SnapshotError::ChildCountMismatch {
+                expected: 4,
+                found: 8
+            })
+        ));
+
+        // depth
+        let mut broken = bytes.clone();
+        broken[9] = 61;
+        assert!(matches!(
+            read(&broken),
+            Err(SnapshotError::Tree(Error::TooDeep(61)))
+        ));
+
+        // node count
+        let mut broken = bytes.clone();
+        broken[10] += 1;
+        assert!(matches!(read(&broken), Err(SnapshotError::Corrupt(_))));
+
+        // flags
+        let mut broken = bytes.clone();
+        broken[18] ^= 0b10;
+        assert!(matches!(read(&broken), Err(SnapshotError::Corrupt(_))));
+
+        // and cut off
+        for len in [0, 3, 12, 19, bytes.len() - 1] {
+            assert!(matches!(read(&bytes[..len]), Err(SnapshotError::Io(_))));
+        }
+    }
+}
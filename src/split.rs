@@ -0,0 +1,486 @@
+//! Split borrows of the tree, to mutate several chunks at once without raw pointers
+
+use crate::cache::*;
+use crate::traits::*;
+use crate::tree::*;
+
+use std::collections::HashMap;
+
+/// Error returned by get_many_mut when the chunks at the given positions can't all be borrowed
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GetManyMutError {
+    /// the position at this index in the given positions is not in the tree
+    PositionNotFound(usize),
+
+    /// the positions at these indices in the given positions are the same, so they can't both be borrowed mutably
+    Overlapping(usize, usize),
+}
+
+impl std::fmt::Display for GetManyMutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PositionNotFound(i) => write!(f, "position {} is not in the tree", i),
+            Self::Overlapping(a, b) => write!(f, "positions {} and {} are the same", a, b),
+        }
+    }
+}
+
+impl std::error::Error for GetManyMutError {}
+
+/// mutable view of the chunks in the tree, as returned by Tree::split_mut
+pub struct TreeChunksMut<'a, C: Sized, L: LodVec> {
+    nodes: &'a [TreeNode],
+    chunks: &'a mut [ChunkContainer<C, L>],
+}
+
+impl<'a, C: Sized, L: LodVec> TreeChunksMut<'a, C, L> {
+    /// get the number of chunks in the tree
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// wether there are no chunks in the tree
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// get a chunk by position, or none if it's not in the tree
+    #[inline]
+    pub fn get(&self, position: L) -> Option<&C> {
+        let index = chunk_index_from_position(self.nodes, position)?;
+        Some(&self.chunks[index].chunk)
+    }
+
+    /// get a mutable chunk by position, or none if it's not in the tree
+    #[inline]
+    pub fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        let index = chunk_index_from_position(self.nodes, position)?;
+        Some(&mut self.chunks[index].chunk)
+    }
+
+    /// get mutable chunks at several positions at once, see Tree::get_many_mut
+    #[inline]
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        positions: [L; N],
+    ) -> Result<[&mut C; N], GetManyMutError> {
+        get_many_mut(self.nodes, self.chunks, positions)
+    }
+
+    /// iterate over all chunks as mutable and their positions
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&mut C, L)> + '_ {
+        self.chunks.iter_mut().map(|x| (&mut x.chunk, x.position))
+    }
+}
+
+/// mutable view of the pending chunks, see UpdateMode::Deferred, as returned by Tree::split_mut
+pub struct PendingChunksMut<'a, C: Sized, L: LodVec> {
+    pending: &'a mut HashMap<L, PendingGroup<C, L>>,
+}
+
+impl<'a, C: Sized, L: LodVec> PendingChunksMut<'a, C, L> {
+    /// get the number of pending chunks
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pending.values().map(|x| x.chunks.len()).sum()
+    }
+
+    /// wether there are no pending chunks
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pending.values().all(|x| x.chunks.is_empty())
+    }
+
+    // the pending chunk at the position, found in the group of it's parent, or it's own group for the root
+    #[inline]
+    fn find(&self, position: L) -> Option<&PendingContainer<C, L>> {
+        self.pending
+            .get(&position.get_parent().unwrap_or(position))?
+            .chunks
+            .iter()
+            .find(|x| x.position == position)
+    }
+
+    /// get a pending chunk by position, or none if it's not pending
+    #[inline]
+    pub fn get(&self, position: L) -> Option<&C> {
+        self.find(position).map(|x| &x.chunk)
+    }
+
+    /// get a mutable pending chunk by position, or none if it's not pending
+    #[inline]
+    pub fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        self.pending
+            .get_mut(&position.get_parent().unwrap_or(position))?
+            .chunks
+            .iter_mut()
+            .find(|x| x.position == position)
+            .map(|x| &mut x.chunk)
+    }
+
+    /// wether the pending chunk at the position was marked as ready by Tree::poll_pending, or none if it's not pending
+    #[inline]
+    pub fn is_ready(&self, position: L) -> Option<bool> {
+        self.find(position).map(|x| x.ready)
+    }
+
+    /// iterate over all pending chunks as mutable and their positions, in no particular order
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&mut C, L)> + '_ {
+        self.pending
+            .values_mut()
+            .flat_map(|x| x.chunks.iter_mut())
+            .map(|x| (&mut x.chunk, x.position))
+    }
+}
+
+/// mutable view of the chunks to add and to delete in the prepared update, as returned by Tree::split_mut.
+/// These aren't in the tree, so they can be used together with TreeChunksMut, such as to copy data from a chunk into it's children that are going to be added
+pub struct UpdateChunksMut<'a, C: Sized, L: LodVec> {
+    to_add: &'a mut [ToAddContainer<C, L>],
+    to_delete: &'a mut [ToDeleteContainer<C, L>],
+}
+
+impl<'a, C: Sized, L: LodVec> UpdateChunksMut<'a, C, L> {
+    /// get the number of chunks to add
+    #[inline]
+    pub fn get_num_chunks_to_add(&self) -> usize {
+        self.to_add.len()
+    }
+
+    /// get the number of chunks to delete
+    #[inline]
+    pub fn get_num_chunks_to_delete(&self) -> usize {
+        self.to_delete.len()
+    }
+
+    /// get a mutable chunk to add by position, or none if it's not going to be added
+    #[inline]
+    pub fn get_chunk_to_add_mut(&mut self, position: L) -> Option<&mut C> {
+        self.to_add
+            .iter_mut()
+            .find(|x| x.position == position)
+            .map(|x| &mut x.chunk)
+    }
+
+    /// get a mutable chunk to delete by position, or none if it's not going to be deleted
+    #[inline]
+    pub fn get_chunk_to_delete_mut(&mut self, position: L) -> Option<&mut C> {
+        self.to_delete
+            .iter_mut()
+            .find(|x| x.position == position)
+            .map(|x| &mut x.chunk)
+    }
+
+    /// get the chunks to add as a mutable slice, see Tree::get_chunks_to_add_slice_mut
+    #[inline]
+    pub fn get_chunks_to_add_slice_mut(&mut self) -> &mut [ToAddContainer<C, L>] {
+        self.to_add
+    }
+
+    /// iterate over all chunks to add as mutable and their positions
+    #[inline]
+    pub fn iter_chunks_to_add_mut(&mut self) -> impl Iterator<Item = (&mut C, L)> + '_ {
+        self.to_add.iter_mut().map(|x| (&mut x.chunk, x.position))
+    }
+
+    /// iterate over all chunks to delete as mutable and their positions
+    #[inline]
+    pub fn iter_chunks_to_delete_mut(&mut self) -> impl Iterator<Item = (&mut C, L)> + '_ {
+        self.to_delete
+            .iter_mut()
+            .map(|x| (&mut x.chunk, x.position))
+    }
+}
+
+// gets the chunks at several positions from the nodes and chunks of a tree
+fn get_many_mut<'a, C: Sized, L: LodVec, const N: usize>(
+    nodes: &[TreeNode],
+    mut chunks: &'a mut [ChunkContainer<C, L>],
+    positions: [L; N],
+) -> Result<[&'a mut C; N], GetManyMutError> {
+    // find where all chunks are
+    let mut indices = [0; N];
+    for (i, position) in positions.into_iter().enumerate() {
+        indices[i] = chunk_index_from_position(nodes, position)
+            .ok_or(GetManyMutError::PositionNotFound(i))?;
+    }
+
+    // sort them, so the same chunks end up next to each other, and can be split off in order
+    let mut order: [usize; N] = std::array::from_fn(|i| i);
+    order.sort_unstable_by_key(|i| (indices[*i], *i));
+
+    for pair in order.windows(2) {
+        if indices[pair[0]] == indices[pair[1]] {
+            return Err(GetManyMutError::Overlapping(pair[0], pair[1]));
+        }
+    }
+
+    // then take them from the front of the chunks, one at a time
+    let mut found: [Option<&'a mut C>; N] = std::array::from_fn(|_| None);
+    let mut offset = 0;
+    for i in order {
+        let (container, rest) = std::mem::take(&mut chunks)[indices[i] - offset..]
+            .split_first_mut()
+            .expect("chunk index is in the tree");
+
+        found[i] = Some(&mut container.chunk);
+        offset = indices[i] + 1;
+        chunks = rest;
+    }
+
+    Ok(found.map(|x| x.expect("all positions are found")))
+}
+
+// mutable chunks of a group of nodes, in the order the chunks are stored in the tree
+// the chunks are split off from the front one at a time, so nothing is collected
+pub(crate) struct GroupChunksMut<'a, C: Sized, L: LodVec> {
+    chunks: &'a mut [ChunkContainer<C, L>],
+    offset: usize,
+    indices: std::slice::Iter<'a, u32>,
+}
+
+impl<'a, C: Sized, L: LodVec> Iterator for GroupChunksMut<'a, C, L> {
+    type Item = (&'a mut C, L);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = *self.indices.next()? as usize;
+        let (container, rest) = std::mem::take(&mut self.chunks)[index - self.offset..]
+            .split_first_mut()
+            .expect("chunk index is in the tree");
+
+        self.offset = index + 1;
+        self.chunks = rest;
+        Some((&mut container.chunk, container.position))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'a, C: Sized, L: LodVec> ExactSizeIterator for GroupChunksMut<'a, C, L> {}
+
+// gets the chunks of the given nodes as mutable, along with their positions, in the order the chunks are stored in.
+// The chunk indices are sorted in the given buffer, so they can be split off in order.
+// A node that's in the group more than once only has it's chunk handed out once
+pub(crate) fn chunks_of_nodes_mut<'a, C: Sized, L: LodVec>(
+    nodes: &[TreeNode],
+    chunks: &'a mut [ChunkContainer<C, L>],
+    order: &'a mut Vec<u32>,
+    group: impl Iterator<Item = u32>,
+) -> GroupChunksMut<'a, C, L> {
+    order.clear();
+    order.extend(group.map(|node| nodes[node as usize].chunk));
+    order.sort_unstable();
+    order.dedup();
+
+    GroupChunksMut {
+        chunks,
+        offset: 0,
+        indices: order.iter(),
+    }
+}
+
+impl<C, L, K> Tree<C, L, K>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+{
+    /// splits the tree into mutable views of the chunks in the tree, the chunks that are pending,
+    /// and the chunks to add and to delete in the prepared update.
+    /// All can be used at the same time, such as to copy data from a chunk in the tree into it's pending children, or the children it's going to get
+    #[inline]
+    pub fn split_mut(
+        &mut self,
+    ) -> (
+        TreeChunksMut<'_, C, L>,
+        PendingChunksMut<'_, C, L>,
+        UpdateChunksMut<'_, C, L>,
+    ) {
+        (
+            TreeChunksMut {
+                nodes: &self.nodes,
+                chunks: &mut self.chunks,
+            },
+            PendingChunksMut {
+                pending: &mut self.pending,
+            },
+            UpdateChunksMut {
+                to_add: &mut self.chunks_to_add,
+                to_delete: &mut self.chunks_to_delete,
+            },
+        )
+    }
+
+    /// get mutable chunks at several positions at once.
+    /// This is the safe alternative to holding on to several pointers from get_chunk_pointer_mut.
+    ///
+    /// Returns an error if any position isn't in the tree, or if the same position is given twice.
+    /// # Params
+    /// * `positions` The positions of the chunks to get
+    #[inline]
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        positions: [L; N],
+    ) -> Result<[&mut C; N], GetManyMutError> {
+        get_many_mut(&self.nodes, &mut self.chunks, positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::*;
+
+    #[test]
+    fn many_mut() {
+        let mut tree = Tree::<QuadVec, QuadVec>::new(0);
+        while tree.prepare_update(&[QuadVec::new(1, 1, 2)], 0, &mut |pos| pos) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        let root = QuadVec::root();
+        let a = QuadVec::new(0, 0, 1);
+        let b = QuadVec::new(1, 1, 2);
+
+        // any order works, and the chunks are the right ones
+        let [x, y, z] = tree.get_many_mut([b, root, a]).unwrap();
+        assert_eq!((*x, *y, *z), (b, root, a));
+        std::mem::swap(x, z);
+        assert_eq!(tree.get_chunk_from_position(a), Some(&b));
+        assert_eq!(tree.get_chunk_from_position(b), Some(&a));
+
+        // but the positions need to be in the tree, and can't be the same
+        assert_eq!(
+            tree.get_many_mut([root, QuadVec::new(3, 3, 2)]).err(),
+            Some(GetManyMutError::PositionNotFound(1))
+        );
+        assert_eq!(
+            tree.get_many_mut([a, root, a]).err(),
+            Some(GetManyMutError::Overlapping(0, 2))
+        );
+        assert!(tree.get_many_mut([]).is_ok());
+    }
+
+    #[test]
+    fn group_mut() {
+        let mut tree = Tree::<QuadVec, QuadVec>::new(0);
+        while tree.prepare_update(&[QuadVec::new(1, 1, 2)], 0, &mut |pos| pos) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        // a node that's in a group twice only has it's chunk handed out once
+        tree.chunks_to_deactivate.extend([2, 0, 2, 1]);
+        let mut chunks = tree
+            .iter_chunks_to_deactivate_and_positions_mut()
+            .map(|(chunk, position)| {
+                assert_eq!(*chunk, position);
+                position
+            })
+            .collect::<Vec<_>>();
+        chunks.sort_by_key(|x| (x.depth, x.x, x.y));
+        assert_eq!(
+            chunks,
+            [
+                QuadVec::root(),
+                QuadVec::new(0, 0, 1),
+                QuadVec::new(1, 0, 1)
+            ]
+        );
+        assert_eq!(tree.iter_chunks_to_deactivate_mut().len(), 3);
+    }
+
+    #[test]
+    fn split() {
+        // pending chunks don't get made until they are ready
+        let mut tree = Tree::<u32, QuadVec>::new(0);
+        tree.set_update_mode(UpdateMode::Deferred);
+        let target = QuadVec::new(0, 0, 1);
+        for _ in 0..3 {
+            tree.poll_pending(&mut |pos, _| pos == QuadVec::root());
+            if tree.prepare_update(&[target], 0, &mut |_| 1) {
+                tree.do_update();
+            }
+            tree.complete_update();
+        }
+
+        // the children of the root are pending, and get their data from it
+        let (mut chunks, mut pending, _) = tree.split_mut();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(pending.len(), 4);
+        assert_eq!(pending.is_ready(target), Some(false));
+        assert_eq!(pending.get(QuadVec::root()), None);
+        assert_eq!(pending.get(target), Some(&1));
+        assert!(pending.get_mut(QuadVec::new(1, 1, 1)).is_some());
+        assert!(pending.get_mut(QuadVec::new(1, 1, 2)).is_none());
+
+        let root = chunks.get_mut(QuadVec::root()).unwrap();
+        *root += 1;
+        for (chunk, _) in pending.iter_mut() {
+            *chunk += *root;
+        }
+
+        // and once ready they end up in the tree
+        tree.poll_pending(&mut |_, _| true);
+        while tree.prepare_update(&[target], 0, &mut |_| 1) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        assert_eq!(tree.get_chunk_from_position(target), Some(&3));
+        let (mut chunks, pending, _) = tree.split_mut();
+        assert!(pending.is_empty());
+        assert_eq!(chunks.iter_mut().count(), 5);
+    }
+
+    #[test]
+    fn split_update() {
+        // chunks to add get their data from their parent in the tree
+        let mut tree = Tree::<u32, QuadVec>::new(0);
+        tree.set_update_mode(UpdateMode::OneLevel);
+        let target = QuadVec::new(0, 0, 2);
+        let mut updates = 0;
+        while tree.prepare_update(&[target], 0, &mut |_| 0) {
+            let (mut chunks, _, mut update) = tree.split_mut();
+
+            // the chunks in the tree can be changed while the update is borrowed
+            if let Some(root) = chunks.get_mut(QuadVec::root()) {
+                *root = 10;
+            }
+
+            for (chunk, position) in update.iter_chunks_to_add_mut() {
+                if position.depth > 0 {
+                    let parent = QuadVec::new(position.x >> 1, position.y >> 1, position.depth - 1);
+                    *chunk = *chunks.get(parent).unwrap() + 1;
+                }
+            }
+
+            tree.do_update();
+            tree.complete_update();
+            updates += 1;
+        }
+
+        assert_eq!(updates, 3);
+        assert_eq!(tree.get_chunk_from_position(target), Some(&12));
+        assert_eq!(
+            tree.get_chunk_from_position(QuadVec::new(1, 1, 1)),
+            Some(&11)
+        );
+
+        // and the chunks to delete are there as well
+        tree.prepare_update(&[QuadVec::new(3, 3, 2)], 0, &mut |_| 0);
+        let (_, _, mut update) = tree.split_mut();
+        assert_eq!(update.get_num_chunks_to_add(), 4);
+        assert_eq!(update.get_num_chunks_to_delete(), 0);
+        assert!(update.get_chunk_to_add_mut(QuadVec::new(3, 3, 2)).is_some());
+        assert!(update.get_chunk_to_delete_mut(target).is_none());
+    }
+}
@@ -0,0 +1,860 @@
+//! Contains the ChunkStore trait, a second tier for chunks that were evicted from the cache,
+//! and DirectoryStore, which keeps them in files in a local directory.
+//!
+//! TieredCache puts a store behind any ChunkCache, so evicted chunks are written to the store,
+//! and loaded from it again when they are added back to the tree, instead of being made again.
+
+use crate::cache::*;
+use crate::coords::{CubeFaceVec, LodVecN, OctVec, QuadVec};
+use crate::traits::LodVec;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// trait for a store of chunks, that holds chunks evicted from the cache so they don't need to be made again.
+pub trait ChunkStore<C: Sized, L: LodVec> {
+    /// writes a chunk to the store, replacing any chunk that was at the same position
+    fn store(&mut self, position: L, chunk: &C) -> std::io::Result<()>;
+
+    /// reads a chunk from the store, or none if there's no chunk at that position
+    fn load(&mut self, position: L) -> std::io::Result<Option<C>>;
+
+    /// removes a chunk from the store, such as when it's outdated after an edit
+    fn remove(&mut self, position: L) -> std::io::Result<()>;
+
+    /// moves a chunk in the store to another position, replacing any chunk that was there, such as when the origin of the tree moves.
+    /// Moving a chunk that isn't in the store does nothing.
+    ///
+    /// The default removes the chunk instead, so it's made again.
+    #[inline]
+    fn rename(&mut self, from: L, _to: L) -> std::io::Result<()> {
+        self.remove(from)
+    }
+
+    /// calls the function for the position of every chunk in the store, in no particular order.
+    /// This is used to find the chunks that are already in the store when a TieredCache is made
+    fn for_each_position(&mut self, func: &mut dyn FnMut(L)) -> std::io::Result<()>;
+}
+
+/// trait for positions that can be used as the file name of a chunk in a DirectoryStore
+pub trait StoreKey: LodVec {
+    /// the position as a list of numbers, which is different for every position
+    fn to_key(self) -> Vec<u64>;
+
+    /// the position from the numbers returned by to_key, or none if they aren't a valid position
+    fn from_key(key: &[u64]) -> Option<Self>;
+}
+
+// wether a coordinate is inside the root at the given depth
+#[inline]
+fn in_root(x: u64, depth: u8) -> bool {
+    x.checked_shr(depth as u32).unwrap_or(0) == 0
+}
+
+impl StoreKey for QuadVec {
+    /// the depth, x and y
+    #[inline]
+    fn to_key(self) -> Vec<u64> {
+        vec![self.depth as u64, self.x, self.y]
+    }
+
+    #[inline]
+    fn from_key(key: &[u64]) -> Option<Self> {
+        let [depth, x, y] = *key else {
+            return None;
+        };
+        let depth = u8::try_from(depth).ok()?;
+        (in_root(x, depth) && in_root(y, depth)).then_some(Self { x, y, depth })
+    }
+}
+
+impl StoreKey for OctVec {
+    /// the depth, x, y and z
+    #[inline]
+    fn to_key(self) -> Vec<u64> {
+        vec![self.depth as u64, self.x, self.y, self.z]
+    }
+
+    #[inline]
+    fn from_key(key: &[u64]) -> Option<Self> {
+        let [depth, x, y, z] = *key else {
+            return None;
+        };
+        let depth = u8::try_from(depth).ok()?;
+        (in_root(x, depth) && in_root(y, depth) && in_root(z, depth)).then_some(Self {
+            x,
+            y,
+            z,
+            depth,
+        })
+    }
+}
+
+impl<const D: usize> StoreKey for LodVecN<D> {
+    /// the depth, and then the position along every axis
+    #[inline]
+    fn to_key(self) -> Vec<u64> {
+        std::iter::once(self.depth as u64).chain(self.pos).collect()
+    }
+
+    #[inline]
+    fn from_key(key: &[u64]) -> Option<Self> {
+        let (depth, pos) = key.split_first()?;
+        let depth = u8::try_from(*depth).ok()?;
+        let pos: [u64; D] = pos.try_into().ok()?;
+        pos.iter()
+            .all(|x| in_root(*x, depth))
+            .then_some(Self { pos, depth })
+    }
+}
+
+impl StoreKey for CubeFaceVec {
+    /// the face, and then the key of the position on the face
+    #[inline]
+    fn to_key(self) -> Vec<u64> {
+        let mut key = self.quad.to_key();
+        key.insert(0, self.face as u64);
+        key
+    }
+
+    #[inline]
+    fn from_key(key: &[u64]) -> Option<Self> {
+        let (face, quad) = key.split_first()?;
+        let vec = Self {
+            face: u8::try_from(*face).ok()?,
+            quad: QuadVec::from_key(quad)?,
+        };
+
+        // the root has no position on a face
+        match vec.face {
+            Self::ROOT_FACE if vec.quad == QuadVec::root() => Some(vec),
+            face if face < Self::ROOT_FACE => Some(vec),
+            _ => None,
+        }
+    }
+}
+
+// version of the file names in a DirectoryStore, so files named in another way are never loaded as the wrong chunk
+const FILE_NAME_VERSION: u32 = 1;
+
+/// Store that keeps every chunk in it's own file in a directory.
+///
+/// The chunks are written and read with the given functions, and the files are named after the position of the chunk.
+/// The name is `v1_` followed by the numbers from StoreKey::to_key, separated by `_`, such as `v1_4_1_2_3.chunk` for `OctVec::new(1, 2, 3, 4)`.
+/// Other files in the directory are ignored.
+///
+/// Loading a chunk doesn't remove it's file, so the directory can be reused for a later run.
+/// A TieredCache does remove the file once it takes the chunk out of the store.
+///
+/// Chunks are written to a temporary file first, and then moved into place, so a chunk file is never half written if the program stops.
+/// Files aren't synced to disk unless enabled with with_sync, as that's slow when many chunks are evicted at once.
+pub struct DirectoryStore<C, L, W, R>
+where
+    C: Sized,
+    L: StoreKey,
+    W: FnMut(&mut dyn Write, L, &C) -> std::io::Result<()>,
+    R: FnMut(&mut dyn Read, L) -> std::io::Result<C>,
+{
+    path: PathBuf,
+    chunk_writer: W,
+    chunk_reader: R,
+    sync: bool,
+    phantom: PhantomData<fn(L) -> C>,
+}
+
+impl<C, L, W, R> DirectoryStore<C, L, W, R>
+where
+    C: Sized,
+    L: StoreKey,
+    W: FnMut(&mut dyn Write, L, &C) -> std::io::Result<()>,
+    R: FnMut(&mut dyn Read, L) -> std::io::Result<C>,
+{
+    /// creates a new store in the given directory, creating it if it doesn't exist
+    /// # Args
+    /// * `path` The directory to keep the chunks in
+    /// * `chunk_writer` Writes a chunk at the given position
+    /// * `chunk_reader` Reads a chunk at the given position, written by chunk_writer
+    pub fn new(path: impl AsRef<Path>, chunk_writer: W, chunk_reader: R) -> std::io::Result<Self> {
+        std::fs::create_dir_all(path.as_ref())?;
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            chunk_writer,
+            chunk_reader,
+            sync: false,
+            phantom: PhantomData,
+        })
+    }
+
+    /// sets wether every chunk is synced to disk before it's moved into place.
+    /// This makes sure stored chunks survive the machine crashing, at the cost of a lot of time for every stored chunk
+    #[inline]
+    pub fn with_sync(self, sync: bool) -> Self {
+        Self { sync, ..self }
+    }
+
+    /// the directory the chunks are kept in
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// path of the file for the chunk at the given position
+    pub fn chunk_path(&self, position: L) -> PathBuf {
+        let mut name = format!("v{}", FILE_NAME_VERSION);
+        for x in position.to_key() {
+            name.push_str(&format!("_{}", x));
+        }
+
+        self.path.join(name).with_extension("chunk")
+    }
+
+    // position of the chunk in the file with the given name, if it's a chunk file with the current version
+    fn position_from_file_name(name: &str) -> Option<L> {
+        let mut parts = name.strip_suffix(".chunk")?.split('_');
+        if parts.next()? != format!("v{}", FILE_NAME_VERSION) {
+            return None;
+        }
+
+        let key = parts
+            .map(|x| x.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        L::from_key(&key)
+    }
+}
+
+impl<C, L, W, R> std::fmt::Debug for DirectoryStore<C, L, W, R>
+where
+    C: Sized,
+    L: StoreKey,
+    W: FnMut(&mut dyn Write, L, &C) -> std::io::Result<()>,
+    R: FnMut(&mut dyn Read, L) -> std::io::Result<C>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirectoryStore")
+            .field("path", &self.path)
+            .field("sync", &self.sync)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, L, W, R> ChunkStore<C, L> for DirectoryStore<C, L, W, R>
+where
+    C: Sized,
+    L: StoreKey,
+    W: FnMut(&mut dyn Write, L, &C) -> std::io::Result<()>,
+    R: FnMut(&mut dyn Read, L) -> std::io::Result<C>,
+{
+    fn store(&mut self, position: L, chunk: &C) -> std::io::Result<()> {
+        let path = self.chunk_path(position);
+
+        // write to a temporary file first, so there's never a half written chunk in the store
+        let temp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        (self.chunk_writer)(&mut writer, position, chunk)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        if self.sync {
+            file.sync_all()?;
+        }
+
+        std::fs::rename(temp_path, path)
+    }
+
+    fn load(&mut self, position: L) -> std::io::Result<Option<C>> {
+        let file = match File::open(self.chunk_path(position)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        (self.chunk_reader)(&mut BufReader::new(file), position).map(Some)
+    }
+
+    fn remove(&mut self, position: L) -> std::io::Result<()> {
+        match std::fs::remove_file(self.chunk_path(position)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn rename(&mut self, from: L, to: L) -> std::io::Result<()> {
+        match std::fs::rename(self.chunk_path(from), self.chunk_path(to)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn for_each_position(&mut self, func: &mut dyn FnMut(L)) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(&self.path)? {
+            let name = entry?.file_name();
+            if let Some(position) = name.to_str().and_then(Self::position_from_file_name) {
+                func(position);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Cache with a store behind it.
+/// Chunks evicted from the cache are written to the store before they end up in chunks_to_delete,
+/// and chunks that aren't in the cache are loaded from the store before they are made again.
+/// A chunk is only in one of the two, so a chunk taken out of the store is removed from it.
+///
+/// The positions of the chunks in the store are kept in memory, found with ChunkStore::for_each_position when the cache is made,
+/// so the store is only used for chunks that are in it.
+///
+/// Errors from the store don't stop the tree from updating, a chunk that can't be stored or loaded is made again instead.
+/// The last error is kept, and can be taken with take_error.
+///
+/// The store is keyed by position in the tree, so chunks in it are removed when they are outdated, such as by Tree::prepare_edit and Tree::evict_cached,
+/// and moved when the origin moves with Tree::shift_origin. See ChunkCache::invalidate and ChunkCache::shift.
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::QuadVec;
+/// # use std::io::{Read, Write};
+/// let path = std::env::temp_dir().join(format!("lodtree_doc_store_{}", std::process::id()));
+/// let store = DirectoryStore::new(
+///     &path,
+///     |writer: &mut dyn Write, _, chunk: &u64| writer.write_all(&chunk.to_le_bytes()),
+///     |reader: &mut dyn Read, _| {
+///         let mut bytes = [0; 8];
+///         reader.read_exact(&mut bytes)?;
+///         Ok(u64::from_le_bytes(bytes))
+///     },
+/// )?;
+///
+/// let mut tree = Tree::<u64, QuadVec, _>::with_cache(TieredCache::new(LruCache::new(64), store));
+/// # drop(tree);
+/// # std::fs::remove_dir_all(path)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TieredCache<C, L, K, S>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+    S: ChunkStore<C, L>,
+{
+    cache: K,
+    store: S,
+    stored: HashSet<L>,
+    error: Option<std::io::Error>,
+    phantom: PhantomData<fn(L) -> C>,
+}
+
+impl<C, L, K, S> TieredCache<C, L, K, S>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+    S: ChunkStore<C, L>,
+{
+    /// creates a new cache, with the store behind the given cache.
+    /// The chunks that are already in the store can be loaded, if the store can't list them, the error can be taken with take_error
+    pub fn new(cache: K, mut store: S) -> Self {
+        let mut stored = HashSet::new();
+        let error = store
+            .for_each_position(&mut |position| {
+                stored.insert(position);
+            })
+            .err();
+
+        Self {
+            cache,
+            store,
+            stored,
+            error,
+            phantom: PhantomData,
+        }
+    }
+
+    /// gets the cache in front of the store
+    #[inline]
+    pub fn get_cache(&self) -> &K {
+        &self.cache
+    }
+
+    /// gets the store
+    #[inline]
+    pub fn get_store(&self) -> &S {
+        &self.store
+    }
+
+    /// gets the store mutably.
+    /// Chunks put in the store directly are not loaded, as the cache only knows about the chunks it stored itself,
+    /// so use Tree::evict_cached or ChunkCache::invalidate to remove outdated chunks instead of removing them here
+    #[inline]
+    pub fn get_store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// number of chunks in the store
+    #[inline]
+    pub fn get_num_stored(&self) -> usize {
+        self.stored.len()
+    }
+
+    // removes a chunk from the store, if it's in it
+    #[inline]
+    fn remove_stored(&mut self, position: L) {
+        if self.stored.remove(&position) {
+            if let Err(e) = self.store.remove(position) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// takes the last error the store returned, if any
+    #[inline]
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+impl<C, L, K, S> ChunkCache<C, L> for TieredCache<C, L, K, S>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+    S: ChunkStore<C, L>,
+{
+    /// puts a chunk in the cache, and removes any older chunk at the same position from the store
+    #[inline]
+    fn insert(&mut self, position: L, chunk: C, evict: &mut dyn FnMut(L, C)) {
+        self.remove_stored(position);
+
+        let store = &mut self.store;
+        let stored = &mut self.stored;
+        let error = &mut self.error;
+        self.cache.insert(position, chunk, &mut |position, chunk| {
+            match store.store(position, &chunk) {
+                Ok(()) => {
+                    stored.insert(position);
+                }
+                Err(e) => *error = Some(e),
+            }
+            evict(position, chunk);
+        });
+    }
+
+    /// takes a chunk out of the cache, or loads it from the store and removes it from there
+    #[inline]
+    fn remove(&mut self, position: L) -> Option<C> {
+        if let Some(chunk) = self.cache.remove(position) {
+            return Some(chunk);
+        }
+        if !self.stored.contains(&position) {
+            return None;
+        }
+
+        let chunk = match self.store.load(position) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                self.error = Some(e);
+                return None;
+            }
+        };
+
+        self.remove_stored(position);
+        chunk
+    }
+
+    /// removes a chunk from the cache and the store, without loading it
+    #[inline]
+    fn evict(&mut self, position: L) -> Option<C> {
+        self.remove_stored(position);
+        self.cache.evict(position)
+    }
+
+    /// gets a chunk in the cache, if it's in it. This does not look in the store
+    #[inline]
+    fn get(&self, position: L) -> Option<&C> {
+        self.cache.get(position)
+    }
+
+    /// gets a mutable chunk in the cache, if it's in it. This does not look in the store
+    #[inline]
+    fn get_mut(&mut self, position: L) -> Option<&mut C> {
+        self.cache.get_mut(position)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.cache.size()
+    }
+
+    #[inline]
+    fn set_size(&mut self, size: usize) {
+        self.cache.set_size(size);
+    }
+
+    #[inline]
+    fn max_len(&self) -> Option<usize> {
+        self.cache.max_len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (L, &C)> + '_> {
+        self.cache.iter()
+    }
+
+    fn drain(&mut self, func: &mut dyn FnMut(L, C)) {
+        self.cache.drain(func);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.cache.shrink_to_fit();
+    }
+
+    /// removes the outdated chunks from the store
+    fn invalidate(&mut self, is_outdated: &mut dyn FnMut(L) -> bool) {
+        self.cache.invalidate(is_outdated);
+
+        let outdated = self
+            .stored
+            .iter()
+            .copied()
+            .filter(|x| is_outdated(*x))
+            .collect::<Vec<_>>();
+
+        for position in outdated {
+            self.remove_stored(position);
+        }
+    }
+
+    /// moves the chunks in the store to their new position, and removes the outdated ones
+    fn shift(&mut self, new_position: &mut dyn FnMut(L) -> Option<L>) {
+        self.cache.shift(new_position);
+
+        let mut moves = HashMap::new();
+        let mut outdated = Vec::new();
+        for position in self.stored.iter() {
+            match new_position(*position) {
+                Some(moved) => {
+                    moves.insert(*position, moved);
+                }
+                None => outdated.push(*position),
+            }
+        }
+
+        for position in outdated {
+            self.remove_stored(position);
+        }
+
+        // a chunk can move to where another chunk still is, so follow the moves from every chunk,
+        // and move the last chunk first, so no chunk is replaced before it's moved itself
+        let mut moved = HashSet::new();
+        let mut chain = Vec::new();
+        for start in moves.keys() {
+            let mut position = *start;
+            while let Some(to) = moves.get(&position) {
+                if !moved.insert(position) {
+                    break;
+                }
+                chain.push((position, *to));
+                position = *to;
+            }
+
+            for (from, to) in chain.drain(..).rev() {
+                if from != to {
+                    if let Err(e) = self.store.rename(from, to) {
+                        self.error = Some(e);
+                    }
+                }
+            }
+        }
+
+        self.stored = moves.into_values().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::coords::*;
+    use crate::traits::GridVec;
+    use crate::tree::*;
+
+    // store that keeps chunks in memory, and counts how often it's used
+    #[derive(Default)]
+    struct MemoryStore {
+        chunks: HashMap<QuadVec, QuadVec>,
+        loads: usize,
+        removes: usize,
+    }
+
+    impl ChunkStore<QuadVec, QuadVec> for MemoryStore {
+        fn store(&mut self, position: QuadVec, chunk: &QuadVec) -> std::io::Result<()> {
+            self.chunks.insert(position, *chunk);
+            Ok(())
+        }
+
+        fn load(&mut self, position: QuadVec) -> std::io::Result<Option<QuadVec>> {
+            self.loads += 1;
+            Ok(self.chunks.get(&position).copied())
+        }
+
+        fn remove(&mut self, position: QuadVec) -> std::io::Result<()> {
+            self.removes += 1;
+            self.chunks.remove(&position);
+            Ok(())
+        }
+
+        fn rename(&mut self, from: QuadVec, to: QuadVec) -> std::io::Result<()> {
+            if let Some(chunk) = self.chunks.remove(&from) {
+                self.chunks.insert(to, chunk);
+            }
+            Ok(())
+        }
+
+        fn for_each_position(&mut self, func: &mut dyn FnMut(QuadVec)) -> std::io::Result<()> {
+            self.chunks.keys().for_each(|x| func(*x));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tiered_tree() {
+        // no room in the cache, so everything goes to the store
+        let cache = TieredCache::new(LruCache::new(0), MemoryStore::default());
+        let mut tree = Tree::<QuadVec, QuadVec, _>::with_cache(cache);
+
+        for target in [QuadVec::new(0, 0, 3), QuadVec::new(7, 7, 3)] {
+            while tree.prepare_update(&[target], 0, &mut |pos| pos) {
+                tree.do_update();
+                tree.complete_update();
+            }
+        }
+        assert!(!tree.get_cache().get_store().chunks.is_empty());
+
+        // going back loads the chunks from the store
+        let mut created = 0;
+        let mut added = 0;
+        while tree.prepare_update(&[QuadVec::new(0, 0, 3)], 0, &mut |pos| {
+            created += 1;
+            pos
+        }) {
+            added += tree.get_num_chunks_to_add();
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        assert!(added > 0);
+        assert_eq!(created, 0);
+        assert!(tree.get_cache().get_store().loads >= added);
+        for i in 0..tree.get_num_chunks() {
+            assert_eq!(*tree.get_chunk(i), tree.get_chunk_position(i));
+        }
+
+        // editing removes the outdated chunks from the store
+        let (min, max) = (QuadVec::new(4, 4, 3), QuadVec::new(7, 7, 3));
+        let inside = |store: &MemoryStore| {
+            store
+                .chunks
+                .keys()
+                .filter(|pos| pos.is_inside_bounds(min, max, u8::MAX))
+                .count()
+        };
+        assert!(inside(tree.get_cache().get_store()) > 0);
+        tree.prepare_edit(min, max);
+        tree.complete_update();
+        assert_eq!(inside(tree.get_cache().get_store()), 0);
+
+        // moving the origin moves the chunks in the store along, and removes the ones that end up outside of the root
+        for target in [QuadVec::new(7, 0, 3), QuadVec::new(0, 7, 3)] {
+            while tree.prepare_update(&[target], 0, &mut |pos| pos) {
+                tree.do_update();
+                tree.complete_update();
+            }
+        }
+
+        let stored: Vec<_> = tree
+            .get_cache()
+            .get_store()
+            .chunks
+            .iter()
+            .map(|(pos, chunk)| (*pos, *chunk))
+            .collect();
+        assert!(stored
+            .iter()
+            .any(|(pos, _)| pos.shift_origin([1, 0], 1).is_some()));
+        assert!(stored
+            .iter()
+            .any(|(pos, _)| pos.shift_origin([1, 0], 1).is_none()));
+
+        tree.shift_origin([1, 0], 1, &mut |pos| pos);
+        tree.complete_update();
+
+        let mut kept = 0;
+        for (pos, chunk) in stored {
+            if let Some(moved) = pos.shift_origin([1, 0], 1) {
+                // it's either still in the store, or loaded into the tree at it's new position
+                let loaded = (0..tree.get_num_chunks())
+                    .any(|i| tree.get_chunk_position(i) == moved && *tree.get_chunk(i) == chunk);
+                if !loaded {
+                    assert_eq!(
+                        tree.get_cache().get_store().chunks.get(&moved),
+                        Some(&chunk)
+                    );
+                    kept += 1;
+                }
+            }
+        }
+        assert_eq!(tree.get_cache().get_store().chunks.len(), kept);
+        assert!(kept > 0);
+
+        // and taking it out of the store loads it from the new position
+        let (&moved, &chunk) = tree.get_cache().get_store().chunks.iter().next().unwrap();
+        assert_eq!(tree.take_cached_chunk(moved), Some(chunk));
+        assert!(!tree.get_cache().get_store().chunks.contains_key(&moved));
+    }
+
+    #[test]
+    fn evict_stored() {
+        let cache = TieredCache::new(LruCache::new(0), MemoryStore::default());
+        let mut tree = Tree::<QuadVec, QuadVec, _>::with_cache(cache);
+
+        for target in [QuadVec::new(0, 0, 3), QuadVec::new(7, 7, 3)] {
+            while tree.prepare_update(&[target], 0, &mut |pos| pos) {
+                tree.do_update();
+                tree.complete_update();
+            }
+        }
+
+        // the chunk is only in the store, and evicting it removes it from there
+        let position = QuadVec::new(0, 0, 3);
+        assert!(tree.get_cache().get_store().chunks.contains_key(&position));
+        assert!(tree.get_cached_chunk(position).is_none());
+        assert!(!tree.evict_cached(position));
+        assert!(!tree.get_cache().get_store().chunks.contains_key(&position));
+        tree.complete_update();
+
+        // so it's made again when it's added back
+        let mut created = Vec::new();
+        while tree.prepare_update(&[position], 0, &mut |pos| {
+            created.push(pos);
+            pos
+        }) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        assert_eq!(created, [position]);
+    }
+
+    #[test]
+    fn stored_positions() {
+        // chunks that are already in the store are found when the cache is made
+        let mut store = MemoryStore::default();
+        let position = QuadVec::new(1, 1, 1);
+        store.chunks.insert(position, position);
+        let mut cache = TieredCache::new(LruCache::new(1), store);
+        assert_eq!(cache.get_num_stored(), 1);
+
+        // the store is only used for the chunks that are in it
+        let other = QuadVec::new(0, 0, 1);
+        assert_eq!(cache.remove(other), None);
+        cache.insert(other, other, &mut |_, _| {});
+        assert_eq!(cache.get_store().loads, 0);
+        assert_eq!(cache.get_store().removes, 0);
+
+        // inserting over a stored chunk removes it from the store
+        cache.insert(position, position, &mut |_, _| {});
+        assert_eq!(cache.get_store().removes, 1);
+        assert_eq!(cache.get_num_stored(), 1);
+        assert!(cache.get_store().chunks.contains_key(&other));
+        assert!(!cache.get_store().chunks.contains_key(&position));
+
+        // and loading one takes it out
+        assert_eq!(cache.remove(other), Some(other));
+        assert_eq!(cache.get_store().loads, 1);
+        assert_eq!(cache.get_num_stored(), 0);
+        assert!(cache.take_error().is_none());
+    }
+
+    #[test]
+    fn directory_store() {
+        let path = std::env::temp_dir().join(format!("lodtree_store_{}", std::process::id()));
+        let mut store = DirectoryStore::new(
+            &path,
+            |writer: &mut dyn Write, _, chunk: &Vec<u8>| writer.write_all(chunk),
+            |reader: &mut dyn Read, _| {
+                let mut chunk = Vec::new();
+                reader.read_to_end(&mut chunk)?;
+                Ok(chunk)
+            },
+        )
+        .unwrap();
+
+        let position = OctVec::new(1, 2, 3, 4);
+        assert!(store.load(position).unwrap().is_none());
+
+        store.store(position, &vec![1, 2, 3]).unwrap();
+        store.store(OctVec::new(3, 2, 1, 4), &vec![4]).unwrap();
+        assert_eq!(
+            store.chunk_path(position).file_name().unwrap(),
+            "v1_4_1_2_3.chunk"
+        );
+        assert_eq!(store.load(position).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(store.load(OctVec::new(3, 2, 1, 4)).unwrap(), Some(vec![4]));
+
+        // other files are not chunks in the store
+        for name in [
+            "v0_4_1_2_3.chunk",
+            "v1_4_1_2.chunk",
+            "v1_1_2_0_0.chunk",
+            "v1_4_1_2_3.tmp",
+        ] {
+            std::fs::write(path.join(name), [0]).unwrap();
+        }
+        let mut positions = Vec::new();
+        store.for_each_position(&mut |x| positions.push(x)).unwrap();
+        positions.sort_by_key(|x| x.x);
+        assert_eq!(positions, [position, OctVec::new(3, 2, 1, 4)]);
+
+        // moving a chunk replaces the one that was there
+        store.rename(OctVec::new(3, 2, 1, 4), position).unwrap();
+        store.rename(OctVec::new(3, 2, 1, 4), position).unwrap();
+        assert!(store.load(OctVec::new(3, 2, 1, 4)).unwrap().is_none());
+        assert_eq!(store.load(position).unwrap(), Some(vec![4]));
+
+        store.remove(position).unwrap();
+        store.remove(position).unwrap();
+        assert!(store.load(position).unwrap().is_none());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn store_keys() {
+        // every position can be found from it's key
+        let quad = QuadVec::new(5, 2, 3);
+        let oct = OctVec::new(1, 2, 3, 60);
+        let n = LodVecN::new([1, 0, 3, 2], 2);
+        let face = CubeFaceVec::new(4, quad);
+        assert_eq!(QuadVec::from_key(&quad.to_key()), Some(quad));
+        assert_eq!(OctVec::from_key(&oct.to_key()), Some(oct));
+        assert_eq!(LodVecN::from_key(&n.to_key()), Some(n));
+        assert_eq!(CubeFaceVec::from_key(&face.to_key()), Some(face));
+        assert_eq!(
+            CubeFaceVec::from_key(&CubeFaceVec::root().to_key()),
+            Some(CubeFaceVec::root())
+        );
+
+        // but not from keys that aren't a position
+        assert_eq!(QuadVec::from_key(&[2, 4, 0]), None);
+        assert_eq!(QuadVec::from_key(&[256, 0, 0]), None);
+        assert_eq!(LodVecN::<4>::from_key(&[2, 1, 0, 3]), None);
+        assert_eq!(CubeFaceVec::from_key(&[6, 1, 0, 1]), None);
+        assert_eq!(CubeFaceVec::from_key(&[7, 0, 0, 0]), None);
+    }
+}
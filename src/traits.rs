@@ -1,5 +1,8 @@
 //! Contains LodVec trait, which is needed for the coordinate system to be used in a tree.
 //! Sample implementations for this are in coords.rs.
+//! Also contains the LodMetric trait, which decides how much detail is generated around targets,
+//! and the Target descriptor, to give each target it's own detail.
+//! GridVec and TargetPosition are used by TreeGrid, to split the world into regions.
 
 /// trait for defining a Level of Detail vector.
 /// such a vector contains the current position in the octree (3d coords), as well as the lod level it's at, in integer coords.
@@ -11,9 +14,68 @@ pub trait LodVec:
     /// the number of child nodes a node can have in the tree.
     const NUM_CHILDREN: u32;
 
+    /// the number of child nodes the root has, if it's different from the other nodes, such as the six faces of a cube.
+    const NUM_ROOT_CHILDREN: u32 = Self::NUM_CHILDREN;
+
+    /// the number of child nodes this node has, which is NUM_ROOT_CHILDREN for the root, and NUM_CHILDREN otherwise.
+    #[inline]
+    fn num_children(self) -> u32 {
+        if self == Self::root() {
+            Self::NUM_ROOT_CHILDREN
+        } else {
+            Self::NUM_CHILDREN
+        }
+    }
+
     /// returns the lod vector as if it's at the root of the tree.
     fn root() -> Self;
 
+    /// depth of this node in the tree, with the root being at 0.
+    ///
+    /// The default finds it by walking down from the root with contains_child_node, so override it if the depth is stored.
+    #[inline]
+    fn depth(self) -> u8 {
+        let mut current = Self::root();
+        let mut depth = 0;
+
+        // go to the child that contains us, until we're there
+        while current != self && depth < u8::MAX {
+            match (0..current.num_children())
+                .map(|i| current.get_child(i))
+                .find(|x| *x == self || x.contains_child_node(self))
+            {
+                Some(child) => {
+                    current = child;
+                    depth += 1;
+                }
+                None => break,
+            }
+        }
+
+        depth
+    }
+
+    /// position of the parent node, or none for the root.
+    ///
+    /// The default finds it by walking down from the root with contains_child_node, so override it if it can be found directly.
+    #[inline]
+    fn get_parent(self) -> Option<Self> {
+        let mut current = Self::root();
+
+        // go to the child that contains us, until the next one is us
+        while current != self {
+            let child = (0..current.num_children())
+                .map(|i| current.get_child(i))
+                .find(|x| *x == self || x.contains_child_node(self))?;
+            if child == self {
+                return Some(current);
+            }
+            current = child;
+        }
+
+        None
+    }
+
     /// wether the node can subdivide, compared to another node and the required detail.
     ///
     /// Assumes self is the target position for a lod.
@@ -99,4 +161,245 @@ pub trait LodVec:
 
     /// Wether this node contains a child node
     fn contains_child_node(self, child: Self) -> bool;
+
+    /// the number of neighbours a node has, across faces, edges and corners.
+    ///
+    /// The default is 0, for nodes that don't know their neighbours.
+    /// Tree::get_neighbors, balancing and restitching then don't find any neighbours.
+    const NUM_NEIGHBORS: u32 = 0;
+
+    /// the number of neighbours that are across a face.
+    /// These are the first directions, and come in pairs of opposite faces on a flat grid.
+    /// Use opposite_direction to get the direction back from a neighbour, as that's not always the opposite face.
+    ///
+    /// The default is 0, see NUM_NEIGHBORS.
+    const NUM_FACE_NEIGHBORS: u32 = 0;
+
+    /// gets the neighbouring node at the same depth in the given direction.
+    /// The direction is an index below NUM_NEIGHBORS, with the face neighbours first.
+    ///
+    /// Returns None if the neighbour is outside of the root node, or if the direction is not below NUM_NEIGHBORS.
+    /// The default always returns None.
+    #[inline]
+    fn neighbor(self, _direction: u32) -> Option<Self> {
+        None
+    }
+
+    /// gets the direction from the neighbour in the given direction back to this node,
+    /// so the neighbour of the neighbour in that direction is this node again.
+    /// On a flat grid this is the opposite direction, but it can be different when the neighbour is rotated, such as across the edge of a cube.
+    ///
+    /// Returns None if there's no neighbour in the given direction, or if this node isn't a neighbour of it.
+    /// The default looks through all directions of the neighbour.
+    #[inline]
+    fn opposite_direction(self, direction: u32) -> Option<u32> {
+        let neighbor = self.neighbor(direction)?;
+        (0..Self::NUM_NEIGHBORS).find(|x| neighbor.neighbor(*x) == Some(self))
+    }
+
+    /// distance between this node and another one, in nodes at the depth of the deepest of the two, with 0 meaning they overlap.
+    /// This is used to subdivide the nodes closest to the targets first.
+    ///
+    /// The default returns 0 for all nodes.
+    #[inline]
+    fn distance(self, _other: Self) -> u64 {
+        0
+    }
+}
+
+/// trait for deciding wether a node should subdivide, compared to a target.
+/// This is what decides how much detail is generated around the targets passed to prepare_update.
+///
+/// It's implemented for u32, which uses the u32 as detail for LodVec::can_subdivide,
+/// and for closures taking the node, its depth and the target.
+///
+/// The target is usually the same LodVec as the nodes,
+/// but can be anything that describes a target, such as Target, which is used with TargetDetail.
+///
+/// As an example, a metric that subdivides nodes in a sphere around the target, with the radius in chunks at the target's depth:
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::QuadVec;
+/// struct Sphere {
+///     radius: f64,
+/// }
+///
+/// impl LodMetric<QuadVec> for Sphere {
+///     fn can_subdivide(&self, node: QuadVec, depth: u8, target: QuadVec) -> bool {
+///         // don't go deeper than the target
+///         if depth >= target.depth {
+///             return false;
+///         }
+///
+///         // distance from the center of the node to the center of the target
+///         let (node_x, node_y) = node.get_float_coords();
+///         let (target_x, target_y) = target.get_float_coords();
+///         let dx = node_x + node.get_size() * 0.5 - target_x - target.get_size() * 0.5;
+///         let dy = node_y + node.get_size() * 0.5 - target_y - target.get_size() * 0.5;
+///
+///         // the sphere grows with the size of the node
+///         let radius = (self.radius + 1.0) * node.get_size();
+///         dx * dx + dy * dy < radius * radius
+///     }
+/// }
+///
+/// let mut tree = Tree::<(), QuadVec>::new(0);
+/// while tree.prepare_update(&[QuadVec::new(8, 8, 4)], Sphere { radius: 2.0 }, &mut |_| ()) {
+///     tree.do_update();
+///     tree.complete_update();
+/// }
+/// ```
+pub trait LodMetric<L: LodVec, T = L> {
+    /// wether the node can subdivide, compared to the target.
+    ///
+    /// depth is the depth of the node in the tree, with the root being at 0.
+    fn can_subdivide(&self, node: L, depth: u8, target: T) -> bool;
+
+    /// priority of subdividing the node for the target, nodes with a lower priority are subdivided first.
+    /// The priority of a node is the lowest it has for any of the targets, and nodes with the same priority are subdivided from the root down.
+    /// See Tree::set_max_subdivisions.
+    ///
+    /// The default gives all nodes the same priority.
+    #[inline]
+    fn priority(&self, _node: L, _depth: u8, _target: T) -> u64 {
+        0
+    }
+}
+
+impl<L: LodVec> LodMetric<L> for u32 {
+    #[inline]
+    fn can_subdivide(&self, node: L, _depth: u8, target: L) -> bool {
+        target.can_subdivide(node, *self)
+    }
+
+    #[inline]
+    fn priority(&self, node: L, _depth: u8, target: L) -> u64 {
+        target.distance(node)
+    }
+}
+
+impl<L: LodVec, T, F: Fn(L, u8, T) -> bool> LodMetric<L, T> for F {
+    #[inline]
+    fn can_subdivide(&self, node: L, depth: u8, target: T) -> bool {
+        self(node, depth, target)
+    }
+}
+
+/// A target to generate the lod around, with it's own detail and max depth.
+/// This allows different targets, like the camera and a distant light, to request a different amount of detail in the same update.
+/// Use it together with the TargetDetail metric.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Target<L: LodVec> {
+    /// position of the target (QuadVec and OctVec define the center position and max lod in depth for this)
+    pub position: L,
+
+    /// detail for this target, as used by LodVec::can_subdivide (QuadVec and OctVec define this as amount of chunks around this point)
+    pub detail: u32,
+
+    /// no chunks deeper than this are generated for this target
+    pub max_depth: u8,
+
+    /// weight of this target, used by TargetDetail to prioritize targets.
+    /// The priority of a node is it's distance to the target divided by the weight,
+    /// so nodes around a target with a higher weight are subdivided first. Targets with a weight of 0 or less come last
+    pub weight: f32,
+}
+
+impl<L: LodVec> Target<L> {
+    /// creates a new target with the given detail, no extra depth limit and a weight of 1
+    /// # Args
+    /// * `position` The position of the target
+    /// * `detail` The detail for this target
+    #[inline]
+    pub fn new(position: L, detail: u32) -> Self {
+        Self {
+            position,
+            detail,
+            max_depth: u8::MAX,
+            weight: 1.0,
+        }
+    }
+}
+
+/// Metric that subdivides using the detail and max depth of each Target
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TargetDetail;
+
+impl<L: LodVec> LodMetric<L, Target<L>> for TargetDetail {
+    #[inline]
+    fn can_subdivide(&self, node: L, depth: u8, target: Target<L>) -> bool {
+        depth < target.max_depth && target.position.can_subdivide(node, target.detail)
+    }
+
+    #[inline]
+    fn priority(&self, node: L, _depth: u8, target: Target<L>) -> u64 {
+        if target.weight > 0.0 {
+            (target.position.distance(node) as f64 / target.weight as f64) as u64
+        } else {
+            u64::MAX
+        }
+    }
+}
+
+/// trait for LodVecs that can be used in a TreeGrid, where each region of the grid is it's own tree.
+pub trait GridVec: LodVec {
+    /// integer coordinates of a region in the grid
+    type Region: std::hash::Hash + Eq + Copy + Send + Sync + std::fmt::Debug;
+
+    /// distance between two regions, as the largest difference along any axis
+    fn region_distance(a: Self::Region, b: Self::Region) -> u64;
+
+    /// calls the function for every region with a distance of at most radius to the center, including the center itself
+    fn for_each_region_around(
+        center: Self::Region,
+        radius: u32,
+        func: &mut dyn FnMut(Self::Region),
+    );
+
+    /// moves this node from one region into another, as the node in the other region that's closest to it.
+    /// The node goes up one depth for every doubling of the distance to the other region, so it has less effect on regions further away.
+    fn project_to_region(self, from: Self::Region, to: Self::Region) -> Self;
+
+    /// position of this node after moving the origin of the tree by offset nodes at the given depth,
+    /// so the node that was at the offset ends up at 0.
+    ///
+    /// Returns None if the node ends up outside of the root,
+    /// or if the node is above the given depth and the offset isn't a whole number of nodes at it's own depth.
+    fn shift_origin(self, offset: Self::Region, depth: u8) -> Option<Self>;
+}
+
+/// trait for targets that have a position in the tree, so they can be moved to another position.
+/// TreeGrid uses this to pass targets in one region to the regions around it.
+pub trait TargetPosition<L: LodVec>: Copy {
+    /// position of the target
+    fn position(self) -> L;
+
+    /// the same target, at another position
+    fn with_position(self, position: L) -> Self;
+}
+
+impl<L: LodVec> TargetPosition<L> for L {
+    #[inline]
+    fn position(self) -> L {
+        self
+    }
+
+    #[inline]
+    fn with_position(self, position: L) -> Self {
+        position
+    }
+}
+
+impl<L: LodVec> TargetPosition<L> for Target<L> {
+    #[inline]
+    fn position(self) -> L {
+        self.position
+    }
+
+    #[inline]
+    fn with_position(self, position: L) -> Self {
+        Self { position, ..self }
+    }
 }
@@ -1,14 +1,22 @@
 //! Contains the tree struct, which is used to hold all chunks
 
+use crate::cache::*;
+use crate::error::*;
 use crate::traits::*;
 
-use std::collections::{HashMap, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::num::NonZeroU32;
 
+/// deepest depth a target may have in try_prepare_update.
+/// This is also the limit for snapshots and world mappings
+pub const TREE_MAX_DEPTH: u8 = 60;
+
 // struct for keeping track of chunks
 // keeps track of the parent and child indices
 #[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct TreeNode {
     // children, these can't be the root (index 0), so we can use Some and Nonzero for slightly more compact memory
     // children are also contiguous, so we can assume that this to this + num children - 1 are all the children of this node
@@ -35,6 +43,7 @@ pub(crate) struct TreeNode {
 
 // utility struct for holding actual chunks and the node that owns them
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct ChunkContainer<C: Sized, L: LodVec> {
     pub(crate) chunk: C,    // actual data inside the chunk
     pub(crate) index: u32,  // index of the node that holds this chunk
@@ -51,15 +60,24 @@ pub struct ToAddContainer<C: Sized, L: LodVec> {
 
     /// Position of the chunk to add
     pub position: L,
+
+    /// Wether the chunk will be a leaf node after the update, and thus needs to be visible.
+    /// This is only false for chunks that are subdivided in the same update, see UpdateMode::SinglePass.
+    /// Modifying this won't have any effect on the tree
+    pub active: bool,
+
     /// Index of the parent node
     parent_node_index: u32,
+
+    /// wether the parent is a chunk to add as well, in which case parent_node_index is an index into chunks_to_add
+    parent_pending: bool,
 }
 
 // utility struct for holding chunks to remove
 #[derive(Clone, Debug)]
-struct ToRemoveContainer {
-    chunk: u32,  // chunk index
-    parent: u32, // parent index
+pub(crate) struct ToRemoveContainer {
+    pub(crate) chunk: u32, // chunk index
+    parent: u32,           // parent index
 }
 
 /// holds a chunk that's going to be deleted and it's position
@@ -72,18 +90,231 @@ pub struct ToDeleteContainer<C: Sized, L: LodVec> {
     pub position: L,
 }
 
+/// How prepare_update computes the changes to the tree
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UpdateMode {
+    /// Subdivide or merge at most one lod level per update.
+    /// The prepare_update, do_update, complete_update cycle needs to be repeated until prepare_update returns false
+    #[default]
+    OneLevel,
+
+    /// Compute the entire target tree in one traversal, so a single update cycle is enough, unless the max subdivisions is reached.
+    /// Chunks to add are ordered parents first, chunks to remove are ordered children first.
+    /// Chunks to add that get subdivided in the same update are marked as not active
+    SinglePass,
+
+    /// Same as OneLevel, but new chunks are pending until they are ready, so they can be made on another thread.
+    /// The children of a node are only added once all of them are ready, and the node stays a leaf until then.
+    /// Use Tree::poll_pending to mark pending chunks as ready.
+    ///
+    /// Pending chunks that aren't wanted anymore are put in chunks_to_delete during prepare_update if they aren't ready yet,
+    /// and in the cache if they are. Chunks from the cache are ready right away.
+    Deferred,
+}
+
+/// what happened to the chunks to add of a prepared update, as returned by revalidate_update
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Revalidation<C: Sized, L: LodVec> {
+    /// wether an update is still needed, same as what prepare_update returns
+    pub needs_update: bool,
+
+    /// number of chunks to add that are still needed, and are in chunks_to_add again
+    pub kept: usize,
+
+    /// chunks to add that aren't needed anymore, and their positions.
+    /// These can be put in the cache with insert_into_cache, so they are used when they are needed again
+    pub dropped: Vec<(L, C)>,
+}
+
+/// a chunk adjacent to another chunk across a face, as returned by get_neighbors
+#[derive(Debug)]
+pub struct Neighbor<'a, C: Sized, L: LodVec> {
+    /// direction of the face this chunk is across, see LodVec::neighbor
+    pub direction: u32,
+
+    /// index of the chunk, for use with get_chunk and friends
+    pub index: usize,
+
+    /// position of the chunk
+    pub position: L,
+
+    /// depth of the chunk in the tree, with the root being at 0
+    pub depth: u8,
+
+    /// the chunk itself
+    pub chunk: &'a C,
+}
+
+/// which faces of a chunk border a coarser or finer chunk.
+/// Both are bitmasks, with bit n set for face direction n, see LodVec::neighbor
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Hash)]
+pub struct FaceMask {
+    /// faces that border a chunk with a lower lod level
+    pub coarser: u32,
+
+    /// faces that border chunks with a higher lod level
+    pub finer: u32,
+}
+
+impl FaceMask {
+    /// wether the face in the given direction borders a coarser chunk
+    #[inline]
+    pub fn is_coarser(self, direction: u32) -> bool {
+        self.coarser & (1 << direction) != 0
+    }
+
+    /// wether the face in the given direction borders finer chunks
+    #[inline]
+    pub fn is_finer(self, direction: u32) -> bool {
+        self.finer & (1 << direction) != 0
+    }
+}
+
+// utility struct for holding pending chunks
+#[derive(Clone, Debug)]
+pub(crate) struct PendingContainer<C: Sized, L: LodVec> {
+    pub(crate) chunk: C,    // chunk that's being made
+    pub(crate) position: L, // and it's position
+    pub(crate) ready: bool, // wether it's done
+}
+
+// group of pending chunks that are added together, once all of them are ready
+#[derive(Clone, Debug)]
+pub(crate) struct PendingGroup<C: Sized, L: LodVec> {
+    pub(crate) chunks: Vec<PendingContainer<C, L>>,
+    visited: bool, // wether the last prepare_update still wanted these chunks
+}
+
+// utility struct for holding nodes that are going to be subdivided
+#[derive(Clone, Debug)]
+struct SubdivisionContainer<L: LodVec> {
+    priority: u64, // lowest priority for any target
+    depth: u8,     // how deep it is in the tree
+    node: u32,     // node index, or the index in chunks_to_add if pending
+    position: L,   // and it's position
+    pending: bool, // wether this is a chunk to add, which is subdivided in the same update in single pass mode
+}
+
+// where the tree is in the prepare_update, do_update, complete_update cycle
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+enum UpdateState {
+    #[default]
+    Idle, // no update is in progress
+    Prepared, // prepare_update found an update to do
+    Done,     // do_update ran, but complete_update didn't yet
+}
+
 // utility struct for holding chunks in the queue
 #[derive(Clone, Debug)]
 struct QueueContainer<L: LodVec> {
     node: u32,   // chunk index
     position: L, // and it's position
+    depth: u8,   // and how deep it is in the tree
+}
+
+// gets the index of the chunk at a position, from the nodes of a tree
+// if position is not pointing to a node, None is returned
+pub(crate) fn chunk_index_from_position<L: LodVec>(
+    nodes: &[TreeNode],
+    position: L,
+) -> Option<usize> {
+    let (node, found_position, _) = deepest_node_from_position(nodes, position)?;
+
+    // only if the node we found is the one we are looking for
+    if found_position == position {
+        Some(nodes[node as usize].chunk as usize)
+    } else {
+        None
+    }
+}
+
+// gets the deepest node in the tree that contains the position, or the node at the position itself
+// returns the node index, it's position and it's depth, or None if the tree is empty
+pub(crate) fn deepest_node_from_position<L: LodVec>(
+    nodes: &[TreeNode],
+    position: L,
+) -> Option<(u32, L, u8)> {
+    // the current node
+    let mut current_index = 0;
+    let mut current = *nodes.first()?;
+
+    // and position
+    let mut current_position = L::root();
+    let mut current_depth = 0;
+
+    // then loop
+    loop {
+        // if the current node is the one we are looking for, return
+        if current_position == position {
+            return Some((current_index, current_position, current_depth));
+        }
+
+        // if the current node does not have children, this is the deepest one
+        let Some(children) = current.children else {
+            return Some((current_index, current_position, current_depth));
+        };
+
+        // if not, go over the node children
+        if let Some((index, found_position)) = (0..current_position.num_children())
+            .map(|i| (i, current_position.get_child(i)))
+            .find(|(_, x)| x.contains_child_node(position))
+        {
+            // we found the position to go to
+            current_position = found_position;
+            current_depth += 1;
+
+            // and the node is at the index of the child nodes + index
+            current_index = children.get() + index;
+            current = nodes[current_index as usize];
+        } else {
+            // if no child got found that matched the item, return none
+            return None;
+        }
+    }
+}
+
+// checks if an index is inside a chunk group of the given length
+#[inline]
+fn check_index(index: usize, len: usize) -> Result<(), Error> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(Error::IndexOutOfBounds { index, len })
+    }
+}
+
+// generates the try_ variants of the getters of a chunk group, which return an error instead of panicking on a bad index
+macro_rules! impl_try_getters {
+    ($group:literal, $num:ident, $get:ident, $get_mut:ident, $get_position:ident, $try_get:ident, $try_get_mut:ident, $try_get_position:ident) => {
+        #[doc = concat!("get a ", $group, ", or an error if the index is out of bounds")]
+        #[inline]
+        pub fn $try_get(&self, index: usize) -> Result<&C, Error> {
+            check_index(index, self.$num())?;
+            Ok(self.$get(index))
+        }
+
+        #[doc = concat!("get a mutable ", $group, ", or an error if the index is out of bounds")]
+        #[inline]
+        pub fn $try_get_mut(&mut self, index: usize) -> Result<&mut C, Error> {
+            check_index(index, self.$num())?;
+            Ok(self.$get_mut(index))
+        }
+
+        #[doc = concat!("get the position of a ", $group, ", or an error if the index is out of bounds")]
+        #[inline]
+        pub fn $try_get_position(&self, index: usize) -> Result<L, Error> {
+            check_index(index, self.$num())?;
+            Ok(self.$get_position(index))
+        }
+    };
 }
 
 // Tree holding all chunks
 // partially based on: https://stackoverflow.com/questions/41946007/efficient-and-well-explained-implementation-of-a-quadtree-for-2d-collision-det
 // assumption here is that because of the fact that we need to keep inactive chunks in memory for later use, we can keep them together with the actual nodes.
 #[derive(Clone, Debug)]
-pub struct Tree<C: Sized, L: LodVec> {
+pub struct Tree<C: Sized, L: LodVec, K: ChunkCache<C, L> = LruCache<C, L>> {
     /// All chunks in the tree
     pub(crate) chunks: Vec<ChunkContainer<C, L>>,
 
@@ -94,31 +325,80 @@ pub struct Tree<C: Sized, L: LodVec> {
     free_list: VecDeque<u32>,
 
     /// actual chunks to add during next update
-    chunks_to_add: Vec<ToAddContainer<C, L>>,
+    pub(crate) chunks_to_add: Vec<ToAddContainer<C, L>>,
 
     /// chunk indices to be removed, tuple of index, parent index
-    chunks_to_remove: Vec<ToRemoveContainer>,
+    pub(crate) chunks_to_remove: Vec<ToRemoveContainer>,
 
     /// indices of the chunks that need to be activated (i.e. the chunks that have just lost children)
-    chunks_to_activate: Vec<u32>,
+    pub(crate) chunks_to_activate: Vec<u32>,
 
     /// indices of the chunks that need to be deactivated (i.e. chunks that have been subdivided in this iteration)
-    chunks_to_deactivate: Vec<u32>,
+    pub(crate) chunks_to_deactivate: Vec<u32>,
 
     /// internal queue for processing, that way we won't need to reallocate it
     processing_queue: Vec<QueueContainer<L>>,
 
-    /// cache size, determines the max amount of elements in the cache
-    cache_size: usize,
+    /// internal queue for walking subtrees that are added or removed as a whole, holds the index and depth
+    subtree_queue: Vec<(u32, u8)>,
+
+    /// node indices of the chunks added during do_update, to find parents that were added in the same update
+    added_nodes: Vec<u32>,
+
+    /// internal list of nodes to subdivide, to do the most important ones first
+    subdivisions: Vec<SubdivisionContainer<L>>,
+
+    /// internal queue of the subdivisions in single pass mode, as the priority, depth and index in the subdivisions list
+    subdivision_queue: BinaryHeap<Reverse<(u64, u8, u32)>>,
+
+    /// max number of nodes subdivided in one update, if any
+    max_subdivisions: Option<usize>,
 
-    /// internal chunk cache
-    chunk_cache: HashMap<L, C>,
+    /// how prepare_update computes the changes to the tree
+    update_mode: UpdateMode,
 
-    /// tracking queue, to see which chunks are oldest
-    cache_queue: VecDeque<L>,
+    /// where the tree is in the update cycle, so the try_ functions can check it
+    update_state: UpdateState,
+
+    /// wether neighbouring leaves are kept within one lod level of each other
+    balanced: bool,
+
+    /// internal set of nodes that should be subdivided in a balanced tree
+    balance_set: HashSet<L>,
+
+    /// internal set of nodes the metric subdivided in the last balanced update, the balance set is kept as long as these don't change
+    balance_targets: HashSet<L>,
+
+    /// internal set of nodes the metric subdivides in the current balanced update
+    balance_scratch: HashSet<L>,
+
+    /// internal queue for balancing, holds the position and depth
+    balance_queue: Vec<(L, u8)>,
+
+    /// indices of the leaf chunks that border a part of the tree that changed during the last do_update
+    pub(crate) chunks_to_restitch: Vec<u32>,
+
+    /// which faces of the chunks to restitch border a coarser or finer chunk
+    restitch_masks: Vec<FaceMask>,
+
+    /// indices of the chunks that overlap the region of the last prepare_edit
+    pub(crate) chunks_to_edit: Vec<u32>,
+
+    /// groups of chunks that are waiting to be ready, by the position of their parent, see UpdateMode::Deferred
+    /// the root is pending under it's own position, as it can't have pending children at the same time
+    pub(crate) pending: HashMap<L, PendingGroup<C, L>>,
+
+    /// chunks to add from an update that's being revalidated, these are used before the cache
+    reusable: HashMap<L, C>,
+
+    /// chunk indices of a group, sorted, used by the mutable iterators so they don't need to allocate
+    pub(crate) group_order: Vec<u32>,
+
+    /// chunk cache, which holds chunks removed from the tree for later
+    cache: K,
 
     /// chunks that are going to be permamently removed, due to not fitting in the cache anymore
-    chunks_to_delete: Vec<ToDeleteContainer<C, L>>,
+    pub(crate) chunks_to_delete: Vec<ToDeleteContainer<C, L>>,
 }
 
 impl<C, L> Tree<C, L>
@@ -126,46 +406,40 @@ where
     C: Sized,
     L: LodVec,
 {
-    /// Gets an index in self.nodes vector from a position.
-    /// If position is not pointing to a node, None is returned.
-    fn get_node_index_from_position(&self, position: L) -> Option<usize> {
-        // the current node
-        let mut current = *self.nodes.get(0)?;
-
-        // and position
-        let mut current_position = L::root();
-
-        // then loop
-        loop {
-            // if the current node is the one we are looking for, return
-            if current_position == position {
-                return Some(current.chunk as usize);
-            }
+    /// Create a new, empty tree, with a cache of given size
+    ///  Set cache to zero to disable it entirely (may speed up certain workloads by ~50%)
+    pub fn new(cache_size: usize) -> Self {
+        Self::with_cache(LruCache::new(cache_size))
+    }
 
-            // if the current node does not have children, stop
-            // this works according to clippy
-            current.children?;
+    /// create a tree with preallocated memory for chunks and nodes
+    ///  Set cache to zero to disable it entirely (may speed up certain workloads by ~50%)
+    pub fn with_capacity(capacity: usize, cache_size: usize) -> Self {
+        Self::with_capacity_and_cache(capacity, LruCache::new(cache_size))
+    }
+}
 
-            // if not, go over the node children
-            if let Some((index, found_position)) = (0..L::NUM_CHILDREN)
-                .map(|i| (i, current_position.get_child(i)))
-                .find(|(_, x)| x.contains_child_node(position))
-            {
-                // we found the position to go to
-                current_position = found_position;
+impl<C, L, K> Tree<C, L, K>
+where
+    C: Sized,
+    L: LodVec,
+    K: ChunkCache<C, L>,
+{
+    /// Gets an index in self.chunks vector from a position.
+    /// If position is not pointing to a node, None is returned.
+    fn get_node_index_from_position(&self, position: L) -> Option<usize> {
+        chunk_index_from_position(&self.nodes, position)
+    }
 
-                // and the node is at the index of the child nodes + index
-                current = self.nodes[(current.children.unwrap().get() + index) as usize];
-            } else {
-                // if no child got found that matched the item, return none
-                return None;
-            }
-        }
+    /// Gets the deepest node in the tree that contains the position, or the node at the position itself.
+    /// Returns the node index, it's position and it's depth, or None if the tree is empty.
+    fn get_deepest_node_from_position(&self, position: L) -> Option<(u32, L, u8)> {
+        deepest_node_from_position(&self.nodes, position)
     }
 
-    /// Create a new, empty tree, with a cache of given size
-    ///  Set cache to zero to disable it entirely (may speed up certain workloads by ~50%)
-    pub fn new(cache_size: usize) -> Self {
+    /// Create a new, empty tree, with the given cache.
+    /// This allows picking how the cache evicts chunks, see ChunkCache
+    pub fn with_cache(cache: K) -> Self {
         // make a new Tree
         // also allocate some room for nodes
         Self {
@@ -177,16 +451,31 @@ where
             nodes: Vec::new(),
             free_list: VecDeque::new(),
             processing_queue: Vec::new(),
-            cache_size,
-            chunk_cache: HashMap::with_capacity(cache_size),
-            cache_queue: VecDeque::with_capacity(cache_size),
-            chunks_to_delete: Vec::with_capacity(cache_size),
+            subtree_queue: Vec::new(),
+            added_nodes: Vec::new(),
+            subdivisions: Vec::new(),
+            subdivision_queue: BinaryHeap::new(),
+            max_subdivisions: None,
+            update_mode: UpdateMode::OneLevel,
+            update_state: UpdateState::Idle,
+            balanced: false,
+            balance_set: HashSet::new(),
+            balance_targets: HashSet::new(),
+            balance_scratch: HashSet::new(),
+            balance_queue: Vec::new(),
+            chunks_to_restitch: Vec::new(),
+            restitch_masks: Vec::new(),
+            chunks_to_edit: Vec::new(),
+            pending: HashMap::new(),
+            reusable: HashMap::new(),
+            group_order: Vec::new(),
+            chunks_to_delete: Vec::with_capacity(cache.max_len().unwrap_or(0)),
+            cache,
         }
     }
 
-    /// create a tree with preallocated memory for chunks and nodes
-    ///  Set cache to zero to disable it entirely (may speed up certain workloads by ~50%)
-    pub fn with_capacity(capacity: usize, cache_size: usize) -> Self {
+    /// create a tree with preallocated memory for chunks and nodes, and the given cache
+    pub fn with_capacity_and_cache(capacity: usize, cache: K) -> Self {
         // make a new Tree
         // also allocate some room for nodes
         Self {
@@ -198,10 +487,26 @@ where
             nodes: Vec::with_capacity(capacity),
             free_list: VecDeque::with_capacity(capacity),
             processing_queue: Vec::with_capacity(capacity),
-            cache_size,
-            chunk_cache: HashMap::with_capacity(cache_size),
-            cache_queue: VecDeque::with_capacity(cache_size),
-            chunks_to_delete: Vec::with_capacity(cache_size),
+            subtree_queue: Vec::new(),
+            added_nodes: Vec::with_capacity(capacity),
+            subdivisions: Vec::new(),
+            subdivision_queue: BinaryHeap::new(),
+            max_subdivisions: None,
+            update_mode: UpdateMode::OneLevel,
+            update_state: UpdateState::Idle,
+            balanced: false,
+            balance_set: HashSet::new(),
+            balance_targets: HashSet::new(),
+            balance_scratch: HashSet::new(),
+            balance_queue: Vec::new(),
+            chunks_to_restitch: Vec::new(),
+            restitch_masks: Vec::new(),
+            chunks_to_edit: Vec::new(),
+            pending: HashMap::new(),
+            reusable: HashMap::new(),
+            group_order: Vec::new(),
+            chunks_to_delete: Vec::with_capacity(cache.max_len().unwrap_or(0)),
+            cache,
         }
     }
 
@@ -227,6 +532,113 @@ where
         Some(&self.chunks[chunk_index].chunk)
     }
 
+    /// gets the chunks adjacent to the chunk at the given position, across each face.
+    /// These are always leaf chunks, so they can be at the same depth, one bigger chunk, or several smaller ones.
+    /// Faces at the edge of the root node have no neighbours.
+    ///
+    /// Returns None if the position is not in the tree.
+    pub fn get_neighbors(&self, position: L) -> Option<Vec<Neighbor<'_, C, L>>> {
+        // the position itself needs to be in the tree
+        self.get_node_index_from_position(position)?;
+
+        let mut neighbors = Vec::new();
+        let mut stack = Vec::new();
+
+        for direction in 0..L::NUM_FACE_NEIGHBORS {
+            self.for_each_neighbor_leaf(
+                position,
+                direction,
+                &mut stack,
+                &mut |node_index, node_position, depth| {
+                    let chunk = self.nodes[node_index as usize].chunk as usize;
+                    neighbors.push(Neighbor {
+                        direction,
+                        index: chunk,
+                        position: node_position,
+                        depth,
+                        chunk: &self.chunks[chunk].chunk,
+                    });
+                },
+            );
+        }
+
+        Some(neighbors)
+    }
+
+    /// gets which faces of the chunk at the given position border a coarser or finer chunk.
+    /// Returns None if the position is not in the tree.
+    pub fn get_face_mask(&self, position: L) -> Option<FaceMask> {
+        let (_, found, depth) = self.get_deepest_node_from_position(position)?;
+        if found != position {
+            return None;
+        }
+
+        Some(self.get_face_mask_at_depth(position, depth, &mut Vec::new()))
+    }
+
+    // gets the face mask of a node in the tree, with it's depth already known
+    fn get_face_mask_at_depth(
+        &self,
+        position: L,
+        depth: u8,
+        stack: &mut Vec<(u32, L, u8)>,
+    ) -> FaceMask {
+        let mut mask = FaceMask::default();
+
+        for direction in 0..L::NUM_FACE_NEIGHBORS {
+            self.for_each_neighbor_leaf(position, direction, stack, &mut |_, _, neighbor_depth| {
+                if neighbor_depth < depth {
+                    mask.coarser |= 1 << direction;
+                } else if neighbor_depth > depth {
+                    mask.finer |= 1 << direction;
+                }
+            });
+        }
+
+        mask
+    }
+
+    // calls the function with the node index, position and depth of every leaf across the given face of the position
+    fn for_each_neighbor_leaf(
+        &self,
+        position: L,
+        direction: u32,
+        stack: &mut Vec<(u32, L, u8)>,
+        func: &mut dyn FnMut(u32, L, u8),
+    ) {
+        // neighbour at the same depth, which may not be a node in the tree
+        let Some(neighbor) = position.neighbor(direction) else {
+            return;
+        };
+
+        // and the direction from it back to us, which is the same for all nodes inside of it
+        let Some(back) = position.opposite_direction(direction) else {
+            return;
+        };
+
+        // the node at that position, or the leaf containing it if it's bigger
+        let Some(found) = self.get_deepest_node_from_position(neighbor) else {
+            return;
+        };
+
+        // then walk down to all leaves touching the face
+        stack.clear();
+        stack.push(found);
+        while let Some((node_index, node_position, depth)) = stack.pop() {
+            if let Some(children) = self.nodes[node_index as usize].children {
+                for i in 0..node_position.num_children() {
+                    // only children that are directly across the face from us
+                    let child = node_position.get_child(i);
+                    if Self::touches(child, position, back) {
+                        stack.push((children.get() + i, child, depth + 1));
+                    }
+                }
+            } else {
+                func(node_index, node_position, depth);
+            }
+        }
+    }
+
     /// get a mutable chunk by position, or none if it's not in the tree
     #[inline]
     pub fn get_chunk_from_position_mut(&mut self, position: L) -> Option<&mut C> {
@@ -245,6 +657,9 @@ where
 
     /// gets a mutable pointer to a chunk
     /// This casts get_chunk_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
     #[inline]
     pub fn get_chunk_pointer_mut(&mut self, index: usize) -> *mut C {
         self.get_chunk_mut(index)
@@ -276,6 +691,9 @@ where
 
     /// gets a mutable pointer to a chunk that is pending activation
     /// This casts get_chunk_to_activate_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
     #[inline]
     pub fn get_chunk_to_activate_pointer_mut(&mut self, index: usize) -> *mut C {
         self.get_chunk_to_activate_mut(index)
@@ -307,6 +725,9 @@ where
 
     /// gets a mutable pointer to a chunk that is pending deactivation
     /// This casts get_chunk_to_deactivate_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
     #[inline]
     pub fn get_chunk_to_deactivate_pointer_mut(&mut self, index: usize) -> *mut C {
         self.get_chunk_to_deactivate_mut(index)
@@ -318,6 +739,80 @@ where
         self.chunks[self.nodes[self.chunks_to_deactivate[index] as usize].chunk as usize].position
     }
 
+    /// get the number of chunks to edit
+    #[inline]
+    pub fn get_num_chunks_to_edit(&self) -> usize {
+        self.chunks_to_edit.len()
+    }
+
+    /// get a chunk to edit
+    #[inline]
+    pub fn get_chunk_to_edit(&self, index: usize) -> &C {
+        &self.chunks[self.nodes[self.chunks_to_edit[index] as usize].chunk as usize].chunk
+    }
+
+    /// get a mutable chunk to edit
+    #[inline]
+    pub fn get_chunk_to_edit_mut(&mut self, index: usize) -> &mut C {
+        &mut self.chunks[self.nodes[self.chunks_to_edit[index] as usize].chunk as usize].chunk
+    }
+
+    /// gets a mutable pointer to a chunk to edit
+    /// This casts get_chunk_to_edit_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
+    #[inline]
+    pub fn get_chunk_to_edit_pointer_mut(&mut self, index: usize) -> *mut C {
+        self.get_chunk_to_edit_mut(index)
+    }
+
+    /// get the position of a chunk to edit
+    #[inline]
+    pub fn get_position_of_chunk_to_edit(&self, index: usize) -> L {
+        self.chunks[self.nodes[self.chunks_to_edit[index] as usize].chunk as usize].position
+    }
+
+    /// get the number of chunks to restitch
+    #[inline]
+    pub fn get_num_chunks_to_restitch(&self) -> usize {
+        self.chunks_to_restitch.len()
+    }
+
+    /// get a chunk to restitch
+    #[inline]
+    pub fn get_chunk_to_restitch(&self, index: usize) -> &C {
+        &self.chunks[self.nodes[self.chunks_to_restitch[index] as usize].chunk as usize].chunk
+    }
+
+    /// get a mutable chunk to restitch
+    #[inline]
+    pub fn get_chunk_to_restitch_mut(&mut self, index: usize) -> &mut C {
+        &mut self.chunks[self.nodes[self.chunks_to_restitch[index] as usize].chunk as usize].chunk
+    }
+
+    /// get a mutable pointer to a chunk to restitch
+    /// This casts get_chunk_to_restitch_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
+    #[inline]
+    pub fn get_chunk_to_restitch_pointer_mut(&mut self, index: usize) -> *mut C {
+        self.get_chunk_to_restitch_mut(index)
+    }
+
+    /// get the position of a chunk to restitch
+    #[inline]
+    pub fn get_position_of_chunk_to_restitch(&self, index: usize) -> L {
+        self.chunks[self.nodes[self.chunks_to_restitch[index] as usize].chunk as usize].position
+    }
+
+    /// get which faces of a chunk to restitch now border a coarser or finer chunk
+    #[inline]
+    pub fn get_face_mask_of_chunk_to_restitch(&self, index: usize) -> FaceMask {
+        self.restitch_masks[index]
+    }
+
     /// get the number of chunks pending removal
     #[inline]
     pub fn get_num_chunks_to_remove(&self) -> usize {
@@ -339,6 +834,9 @@ where
 
     /// gets a mutable pointer to a chunk that is pending removal
     /// This casts get_chunk_to_remove_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
     #[inline]
     pub fn get_chunk_to_remove_pointer_mut(&mut self, index: usize) -> *mut C {
         self.get_chunk_to_remove_mut(index)
@@ -370,6 +868,9 @@ where
 
     /// gets a mutable pointer to a chunk that is pending to be added
     /// This casts get_chunk_to_add_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
     #[inline]
     pub fn get_chunk_to_add_pointer_mut(&mut self, index: usize) -> *mut C {
         self.get_chunk_to_add_mut(index)
@@ -413,6 +914,9 @@ where
 
     /// gets a mutable pointer to a chunk that is pending deletion
     /// This casts get_chunk_to_delete_mut to a pointer underneath the hood
+    #[deprecated(
+        note = "use the mutable iterators, get_many_mut or split_mut to mutate several chunks at once without raw pointers"
+    )]
     #[inline]
     pub fn get_chunk_to_delete_pointer_mut(&mut self, index: usize) -> *mut C {
         self.get_chunk_to_delete_mut(index)
@@ -436,13 +940,115 @@ where
         &mut self.chunks_to_delete[..]
     }
 
+    /// takes all chunks to be deleted out of the tree, so they can be dropped or reused
+    #[inline]
+    pub fn drain_chunks_to_delete(&mut self) -> std::vec::Drain<'_, ToDeleteContainer<C, L>> {
+        self.chunks_to_delete.drain(..)
+    }
+
+    impl_try_getters!(
+        "chunk",
+        get_num_chunks,
+        get_chunk,
+        get_chunk_mut,
+        get_chunk_position,
+        try_get_chunk,
+        try_get_chunk_mut,
+        try_get_chunk_position
+    );
+
+    impl_try_getters!(
+        "chunk pending activation",
+        get_num_chunks_to_activate,
+        get_chunk_to_activate,
+        get_chunk_to_activate_mut,
+        get_position_of_chunk_to_activate,
+        try_get_chunk_to_activate,
+        try_get_chunk_to_activate_mut,
+        try_get_position_of_chunk_to_activate
+    );
+
+    impl_try_getters!(
+        "chunk pending deactivation",
+        get_num_chunks_to_deactivate,
+        get_chunk_to_deactivate,
+        get_chunk_to_deactivate_mut,
+        get_position_of_chunk_to_deactivate,
+        try_get_chunk_to_deactivate,
+        try_get_chunk_to_deactivate_mut,
+        try_get_position_of_chunk_to_deactivate
+    );
+
+    impl_try_getters!(
+        "chunk to edit",
+        get_num_chunks_to_edit,
+        get_chunk_to_edit,
+        get_chunk_to_edit_mut,
+        get_position_of_chunk_to_edit,
+        try_get_chunk_to_edit,
+        try_get_chunk_to_edit_mut,
+        try_get_position_of_chunk_to_edit
+    );
+
+    impl_try_getters!(
+        "chunk to restitch",
+        get_num_chunks_to_restitch,
+        get_chunk_to_restitch,
+        get_chunk_to_restitch_mut,
+        get_position_of_chunk_to_restitch,
+        try_get_chunk_to_restitch,
+        try_get_chunk_to_restitch_mut,
+        try_get_position_of_chunk_to_restitch
+    );
+
+    impl_try_getters!(
+        "chunk pending removal",
+        get_num_chunks_to_remove,
+        get_chunk_to_remove,
+        get_chunk_to_remove_mut,
+        get_position_of_chunk_to_remove,
+        try_get_chunk_to_remove,
+        try_get_chunk_to_remove_mut,
+        try_get_position_of_chunk_to_remove
+    );
+
+    impl_try_getters!(
+        "chunk pending addition",
+        get_num_chunks_to_add,
+        get_chunk_to_add,
+        get_chunk_to_add_mut,
+        get_position_of_chunk_to_add,
+        try_get_chunk_to_add,
+        try_get_chunk_to_add_mut,
+        try_get_position_of_chunk_to_add
+    );
+
+    impl_try_getters!(
+        "chunk to delete",
+        get_num_chunks_to_delete,
+        get_chunk_to_delete,
+        get_chunk_to_delete_mut,
+        get_position_of_chunk_to_delete,
+        try_get_chunk_to_delete,
+        try_get_chunk_to_delete_mut,
+        try_get_position_of_chunk_to_delete
+    );
+
+    /// get the face mask of a chunk to restitch, or an error if the index is out of bounds
+    #[inline]
+    pub fn try_get_face_mask_of_chunk_to_restitch(&self, index: usize) -> Result<FaceMask, Error> {
+        check_index(index, self.get_num_chunks_to_restitch())?;
+        Ok(self.get_face_mask_of_chunk_to_restitch(index))
+    }
+
     /// Adds chunks at and around specified locations.
     /// This operation will also add chunks at other locations around the target to fullfill the
     /// datastructure constraints (such that no partially filled nodes exist).
-    pub fn prepare_insert(
+    /// The metric decides how far around the targets chunks are added, see prepare_update
+    pub fn prepare_insert<M: LodMetric<L>>(
         &mut self,
         targets: &[L],
-        detail: u32,
+        metric: M,
         chunk_creator: &mut dyn FnMut(L) -> C,
     ) -> bool {
         //FIXME: this function currently will dry-run once for every update to make sure
@@ -463,10 +1069,13 @@ where
             self.chunks_to_add.push(ToAddContainer {
                 position: L::root(),
                 chunk: chunk_to_add,
-                parent_node_index:0,
+                active: true,
+                parent_node_index: 0,
+                parent_pending: false,
             });
 
             // and an update is needed
+            self.update_state = UpdateState::Prepared;
             return true;
         }
 
@@ -477,12 +1086,14 @@ where
         self.processing_queue.push(QueueContainer {
             position: L::root(),
             node: 0,
+            depth: 0,
         });
 
         // then, traverse the tree, as long as something is inside the queue
         while let Some(QueueContainer {
             position: current_position,
             node: current_node_index,
+            depth: current_depth,
         }) = self.processing_queue.pop()
         {
             // fetch the current node
@@ -492,7 +1103,7 @@ where
             if current_node.children.is_none() {
                 //println!("adding children");
                 // add children to be added
-                for i in 0..L::NUM_CHILDREN {
+                for i in 0..current_position.num_children() {
                     // chunk to add
                     let chunk_to_add =
                         self.get_chunk_from_cache(current_position.get_child(i), chunk_creator);
@@ -501,9 +1112,10 @@ where
                     self.chunks_to_add.push(ToAddContainer {
                         position: current_position.get_child(i),
                         chunk: chunk_to_add,
-                        parent_node_index:current_node_index,
+                        active: true,
+                        parent_node_index: current_node_index,
+                        parent_pending: false,
                     });
-
                 }
 
                 // and add ourselves for deactivation
@@ -511,7 +1123,7 @@ where
             } else if let Some(index) = current_node.children {
                 //println!("has children at {index:?}");
                 // queue child nodes for processing
-                for i in 0..L::NUM_CHILDREN {
+                for i in 0..current_position.num_children() {
                     // wether we can subdivide
                     let child_pos = current_position.get_child(i);
                     //dbg!(child_pos);
@@ -522,10 +1134,11 @@ where
                                 chunk_creator(child_pos);
                             continue;
                         }
-                        if t.can_subdivide(child_pos, detail) {
+                        if metric.can_subdivide(child_pos, current_depth + 1, *t) {
                             self.processing_queue.push(QueueContainer {
                                 position: child_pos,
                                 node: index.get() + i,
+                                depth: current_depth + 1,
                             });
                             break;
                         }
@@ -535,7 +1148,13 @@ where
         }
 
         // and return whether an update needs to be done
-        !self.chunks_to_add.is_empty()
+        let needs_update = !self.chunks_to_add.is_empty();
+        self.update_state = if needs_update {
+            UpdateState::Prepared
+        } else {
+            UpdateState::Idle
+        };
+        needs_update
     }
 
     // how it works:
@@ -555,95 +1174,567 @@ where
     /// adds chunks around specified locations (targets) while also erasing all other chunks.
     /// this fills the internal lists of what chunks need to be added or removed as appropriate.
     /// # Params
-    /// * `targets` The target positions to generate the lod around (QuadVec and OctVec define the center position and max lod in depth for this)
-    /// * `detail` The detail for these targets (QuadVec and OctVec define this as amount of chunks around this point)
+    /// * `targets` The target positions to generate the lod around (QuadVec and OctVec define the center position and max lod in depth for this).
+    ///   These can also be Target descriptors, to give each target it's own detail and max depth
+    /// * `metric` Decides wether a node subdivides for a target, see LodMetric.
+    ///   Passing a u32 uses it as the detail for these targets (QuadVec and OctVec define this as amount of chunks around this point).
+    ///   Passing TargetDetail uses the detail and max depth of each Target
     /// * `chunk_creator` function to create a new chunk from a given position
+    ///
     /// returns whether any update is needed.
-    pub fn prepare_update(
+    /// With UpdateMode::Deferred, chunks that are still pending don't count.
+    pub fn prepare_update<T: Copy, M: LodMetric<L, T>>(
         &mut self,
-        targets: &[L],
-        detail: u32,
+        targets: &[T],
+        metric: M,
         chunk_creator: &mut dyn FnMut(L) -> C,
     ) -> bool {
-        //FIXME: this function currently will dry-run once for every update to make sure
-        // there is nothing left to update. This is a waste of CPU time, especially for many targets
+        self.prepare_update_by_ref(targets, &metric, chunk_creator)
+    }
 
-        // first, clear the previous arrays
-        self.chunks_to_add.clear();
-        self.chunks_to_remove.clear();
-        self.chunks_to_activate.clear();
-        self.chunks_to_deactivate.clear();
+    // same as prepare_update, but borrows the metric, so it can be used for multiple updates
+    pub(crate) fn prepare_update_by_ref<T: Copy, M: LodMetric<L, T>>(
+        &mut self,
+        targets: &[T],
+        metric: &M,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) -> bool {
+        // pending chunks are only kept if this update still wants them
+        for group in self.pending.values_mut() {
+            group.visited = false;
+        }
 
-        // if we don't have a root, make one pending for creation
-        if self.nodes.is_empty() {
-            // chunk to add
-            let chunk_to_add = self.get_chunk_from_cache(L::root(), chunk_creator);
+        // when balancing, work out which nodes are subdivided in the balanced tree first, and use that as the metric
+        let needs_update = if self.balanced {
+            let mut subdivided = std::mem::take(&mut self.balance_set);
+            let mut queue = std::mem::take(&mut self.balance_queue);
+
+            Self::find_balanced_subdivisions(
+                targets,
+                metric,
+                &mut subdivided,
+                &mut self.balance_targets,
+                &mut self.balance_scratch,
+                &mut queue,
+            );
 
-            // we need to add the root as pending
-            self.chunks_to_add.push(ToAddContainer {
-                position: L::root(),
-                chunk: chunk_to_add,
-                parent_node_index:0,
-            });
+            // priorities still come from the original metric
+            let needs_update = self.prepare_update_with_metric(
+                targets,
+                &|node: L, _: u8, _: T| subdivided.contains(&node),
+                metric,
+                chunk_creator,
+            );
 
+            self.balance_set = subdivided;
+            self.balance_queue = queue;
+            needs_update
+        } else {
+            self.prepare_update_with_metric(targets, metric, metric, chunk_creator)
+        };
+
+        self.cancel_pending(false);
+
+        self.update_state = if needs_update {
+            UpdateState::Prepared
+        } else {
+            UpdateState::Idle
+        };
+        needs_update
+    }
 
-            // and an update is needed
-            return true;
+    /// same as prepare_update, but checks that the update can be prepared first.
+    /// Returns an error if an update was prepared but not done, if do_update ran but complete_update didn't,
+    /// or if any target is deeper than TREE_MAX_DEPTH
+    pub fn try_prepare_update<T: TargetPosition<L>, M: LodMetric<L, T>>(
+        &mut self,
+        targets: &[T],
+        metric: M,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) -> Result<bool, Error> {
+        match self.update_state {
+            UpdateState::Idle => {}
+            UpdateState::Prepared => return Err(Error::UpdatePending),
+            UpdateState::Done => return Err(Error::UpdateNotCompleted),
         }
 
-        // clear the processing queue from any previous updates
-        self.processing_queue.clear();
+        if let Some(depth) = targets
+            .iter()
+            .map(|x| x.position().depth())
+            .find(|x| *x > TREE_MAX_DEPTH)
+        {
+            return Err(Error::TooDeep(depth));
+        }
 
-        // add the root node (always at 0, if there is no root we would have returned earlier) to the processing queue
-        self.processing_queue.push(QueueContainer {
-            position: L::root(),
-            node: 0,
-        });
+        Ok(self.prepare_update(targets, metric, chunk_creator))
+    }
+
+    /// prepares an edit of everything inside the given bounds, at every depth.
+    /// This fills chunks_to_edit with all chunks in the tree that overlap the bounds, so they can be made again.
+    /// Chunks in the cache that overlap the bounds are put in chunks_to_delete, so outdated chunks are never added back to the tree,
+    /// as are pending chunks, which are made again by the next prepare_update.
+    /// Chunks the cache keeps elsewhere, like in the store of a TieredCache, are removed with ChunkCache::invalidate.
+    ///
+    /// Call this between updates, as chunks_to_edit is cleared by do_update and complete_update.
+    /// # Params
+    /// * `bound_min` The lowest corner of the bounds, as used by LodVec::is_inside_bounds
+    /// * `bound_max` The highest corner of the bounds
+    ///
+    /// returns wether any chunks in the tree need to be edited.
+    pub fn prepare_edit(&mut self, bound_min: L, bound_max: L) -> bool {
+        self.chunks_to_edit.clear();
+
+        // walk the tree, only going into the nodes that overlap the bounds
+        // the root always overlaps
+        self.processing_queue.clear();
+        if !self.nodes.is_empty() {
+            self.processing_queue.push(QueueContainer {
+                node: 0,
+                position: L::root(),
+                depth: 0,
+            });
+        }
 
-        // then, traverse the tree, as long as something is inside the queue
         while let Some(QueueContainer {
-            position: current_position,
-            node: current_node_index,
+            node,
+            position,
+            depth,
         }) = self.processing_queue.pop()
         {
-            // fetch the current node
-            let current_node = self.nodes[current_node_index as usize];
+            self.chunks_to_edit.push(node);
 
-            // wether we can subdivide
-            let can_subdivide = targets
+            if let Some(children) = self.nodes[node as usize].children {
+                for i in 0..position.num_children() {
+                    let child = position.get_child(i);
+                    if child.is_inside_bounds(bound_min, bound_max, u8::MAX) {
+                        self.processing_queue.push(QueueContainer {
+                            node: children.get() + i,
+                            position: child,
+                            depth: depth + 1,
+                        });
+                    }
+                }
+            }
+        }
+
+        // outdated chunks in the cache are deleted
+        let mut outdated = Vec::new();
+        self.cache.for_each(&mut |position, _| {
+            if position.is_inside_bounds(bound_min, bound_max, u8::MAX) {
+                outdated.push(position);
+            }
+        });
+        for position in outdated {
+            self.evict_cached(position);
+        }
+        self.cache
+            .invalidate(&mut |position| position.is_inside_bounds(bound_min, bound_max, u8::MAX));
+
+        // as are pending groups that have an outdated chunk in them
+        let chunks_to_delete = &mut self.chunks_to_delete;
+        self.pending.retain(|_, group| {
+            if !group
+                .chunks
                 .iter()
-                .any(|x| x.can_subdivide(current_position, detail));
+                .any(|x| x.position.is_inside_bounds(bound_min, bound_max, u8::MAX))
+            {
+                return true;
+            }
 
-            // if we can subdivide, and the current node does not have children, subdivide the current node
-            if can_subdivide && current_node.children.is_none() {
-                // add children to be added
-                for i in 0..L::NUM_CHILDREN {
-                    // chunk to add
-                    let chunk_to_add =
-                        self.get_chunk_from_cache(current_position.get_child(i), chunk_creator);
+            for PendingContainer {
+                chunk, position, ..
+            } in group.chunks.drain(..)
+            {
+                chunks_to_delete.push(ToDeleteContainer { position, chunk });
+            }
 
-                    // add the new chunk to be added
-                    self.chunks_to_add.push(ToAddContainer {
-                        position: current_position.get_child(i),
-                        chunk: chunk_to_add,
-                        parent_node_index:current_node_index,
-                    });
+            false
+        });
 
-                }
+        !self.chunks_to_edit.is_empty()
+    }
 
-                // and add ourselves for deactivation
-                self.chunks_to_deactivate.push(current_node_index);
-            } else if let Some(index) = current_node.children {
-                // otherwise, if we cant subdivide and have children, remove our children
-                if !can_subdivide
-                    && !(0..L::NUM_CHILDREN)
+    /// discards the prepared update, without doing any of it.
+    /// Returns the chunks to add and their positions, which can be put in the cache with insert_into_cache so they can be used by a later update
+    pub fn cancel_update(&mut self) -> Vec<(L, C)> {
+        self.chunks_to_remove.clear();
+        self.chunks_to_activate.clear();
+        self.chunks_to_deactivate.clear();
+        self.update_state = UpdateState::Idle;
+
+        self.chunks_to_add
+            .drain(..)
+            .map(
+                |ToAddContainer {
+                     chunk, position, ..
+                 }| (position, chunk),
+            )
+            .collect()
+    }
+
+    /// prepares the update again for new targets, such as when they moved before the prepared update was done.
+    /// Chunks to add that are still needed are kept, and the ones that aren't are returned in the Revalidation, without being added to the tree.
+    /// This takes the same arguments as prepare_update, and chunk_creator is only called for chunks that weren't going to be added before.
+    pub fn revalidate_update<T: Copy, M: LodMetric<L, T>>(
+        &mut self,
+        targets: &[T],
+        metric: M,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) -> Revalidation<C, L> {
+        // keep the chunks to add around, so prepare_update uses them before anything else
+        let mut reusable = std::mem::take(&mut self.reusable);
+        reusable.extend(self.chunks_to_add.drain(..).map(
+            |ToAddContainer {
+                 chunk, position, ..
+             }| (position, chunk),
+        ));
+        let num_reusable = reusable.len();
+        self.reusable = reusable;
+
+        let needs_update = self.prepare_update(targets, metric, chunk_creator);
+
+        // and the ones that weren't used are given back
+        let dropped = self.reusable.drain().collect::<Vec<_>>();
+
+        Revalidation {
+            needs_update,
+            kept: num_reusable - dropped.len(),
+            dropped,
+        }
+    }
+
+    // makes the children of a node pending, or the root if the tree is empty
+    // once they are all ready, they are queued to be added, and this returns true
+    fn prepare_pending(
+        &mut self,
+        parent: L,
+        parent_node_index: u32,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) -> bool {
+        let is_root = self.nodes.is_empty();
+        let cache = &mut self.cache;
+        let reusable = &mut self.reusable;
+        let group = self.pending.entry(parent).or_insert_with(|| {
+            let num_chunks = if is_root { 1 } else { parent.num_children() };
+            let chunks = (0..num_chunks)
+                .map(|i| {
+                    let position = if is_root { parent } else { parent.get_child(i) };
+
+                    // chunks from the cache are already done
+                    match reusable
+                        .remove(&position)
+                        .or_else(|| cache.remove(position))
+                    {
+                        Some(chunk) => PendingContainer {
+                            chunk,
+                            position,
+                            ready: true,
+                        },
+                        None => PendingContainer {
+                            chunk: chunk_creator(position),
+                            position,
+                            ready: false,
+                        },
+                    }
+                })
+                .collect();
+
+            PendingGroup {
+                chunks,
+                visited: false,
+            }
+        });
+
+        group.visited = true;
+        if !group.chunks.iter().all(|x| x.ready) {
+            return false;
+        }
+
+        // all are ready, so add them together
+        if let Some(group) = self.pending.remove(&parent) {
+            for PendingContainer {
+                chunk, position, ..
+            } in group.chunks
+            {
+                self.chunks_to_add.push(ToAddContainer {
+                    position,
+                    chunk,
+                    active: true,
+                    parent_node_index,
+                    parent_pending: false,
+                });
+            }
+        }
+
+        true
+    }
+
+    // removes the pending chunks that weren't wanted by the last prepare_update, or all of them
+    // ready chunks go in the cache, the rest is deleted
+    fn cancel_pending(&mut self, all: bool) {
+        let cache = &mut self.cache;
+        let chunks_to_delete = &mut self.chunks_to_delete;
+        self.pending.retain(|_, group| {
+            if group.visited && !all {
+                return true;
+            }
+
+            for PendingContainer {
+                chunk,
+                position,
+                ready,
+            } in group.chunks.drain(..)
+            {
+                if ready {
+                    cache.insert(position, chunk, &mut |position, chunk| {
+                        chunks_to_delete.push(ToDeleteContainer { position, chunk })
+                    });
+                } else {
+                    chunks_to_delete.push(ToDeleteContainer { position, chunk });
+                }
+            }
+
+            false
+        });
+    }
+
+    /// checks which pending chunks are ready, see UpdateMode::Deferred.
+    /// The function is called with every chunk that isn't ready yet, and returns wether it's ready now.
+    /// Returns wether any group of chunks is ready to be added on the next prepare_update
+    pub fn poll_pending(&mut self, is_ready: &mut dyn FnMut(L, &mut C) -> bool) -> bool {
+        let mut any_ready = false;
+        for group in self.pending.values_mut() {
+            for pending in group.chunks.iter_mut().filter(|x| !x.ready) {
+                pending.ready = is_ready(pending.position, &mut pending.chunk);
+            }
+
+            any_ready |= group.chunks.iter().all(|x| x.ready);
+        }
+
+        any_ready
+    }
+
+    /// get the number of chunks that are pending, see UpdateMode::Deferred
+    #[inline]
+    pub fn get_num_pending_chunks(&self) -> usize {
+        self.pending.values().map(|x| x.chunks.len()).sum()
+    }
+
+    // finds all nodes that are subdivided in the target tree, and adds subdivisions until neighbouring leaves differ by at most one lod level
+    // the balanced tree only depends on the nodes the metric subdivides, so it's kept from the last update if those are the same,
+    // and only the new nodes are balanced if the metric only subdivides more nodes than before
+    fn find_balanced_subdivisions<T: Copy, M: LodMetric<L, T>>(
+        targets: &[T],
+        metric: &M,
+        subdivided: &mut HashSet<L>,
+        wanted: &mut HashSet<L>,
+        scratch: &mut HashSet<L>,
+        queue: &mut Vec<(L, u8)>,
+    ) {
+        scratch.clear();
+        queue.clear();
+
+        // walk the target tree, same as the single pass update does
+        queue.push((L::root(), 0));
+        while let Some((position, depth)) = queue.pop() {
+            if targets
+                .iter()
+                .any(|x| metric.can_subdivide(position, depth, *x))
+            {
+                scratch.insert(position);
+                queue.extend(
+                    (0..position.num_children()).map(|i| (position.get_child(i), depth + 1)),
+                );
+            }
+        }
+
+        // same nodes as last time, so the balanced tree is the same too
+        if scratch == wanted {
+            return;
+        }
+
+        // if nodes are no longer subdivided, the extra subdivisions around them might not be needed anymore, so start over
+        if !wanted.is_subset(scratch) {
+            subdivided.clear();
+        }
+        std::mem::swap(wanted, scratch);
+
+        // the children of a subdivided node are only next to leaves of at most one lod level lower
+        // if the node next to it on the same level exists, so it's parent needs to be subdivided as well
+        // depth isn't needed here
+        for position in wanted.iter() {
+            if subdivided.insert(*position) {
+                queue.push((*position, 0));
+            }
+        }
+        while let Some((position, _)) = queue.pop() {
+            for direction in 0..L::NUM_FACE_NEIGHBORS {
+                let Some(neighbor) = position.neighbor(direction) else {
+                    continue;
+                };
+
+                // subdivide everything above the neighbour
+                // the parents of a subdivided node are always subdivided, so this can stop at the first one that already is
+                let mut current = neighbor.get_parent();
+                while let Some(parent) = current {
+                    if !subdivided.insert(parent) {
+                        break;
+                    }
+                    queue.push((parent, 0));
+                    current = parent.get_parent();
+                }
+            }
+        }
+    }
+
+    // wether a node shares a face with a node that's at the same depth or higher up in the tree, across the given direction
+    // the direction is the one back from the neighbour, see LodVec::opposite_direction
+    #[inline]
+    fn touches(node: L, other: L, direction: u32) -> bool {
+        node.neighbor(direction)
+            .is_some_and(|x| other.contains_child_node(x))
+    }
+
+    // wether merging the children of this node keeps it within one lod level of the leaves next to it
+    fn can_merge_balanced(&self, position: L) -> bool {
+        (0..L::NUM_FACE_NEIGHBORS).all(|direction| {
+            // the node next to us, if it has children
+            let (Some(neighbor), Some(back)) = (
+                position.neighbor(direction),
+                position.opposite_direction(direction),
+            ) else {
+                return true;
+            };
+            let children = match self.get_deepest_node_from_position(neighbor) {
+                Some((node, found, _)) if found == neighbor => self.nodes[node as usize].children,
+                _ => None,
+            };
+            let Some(children) = children else {
+                return true;
+            };
+
+            // none of the children touching us can have children of their own
+            (0..neighbor.num_children()).all(|i| {
+                !Self::touches(neighbor.get_child(i), position, back)
+                    || self.nodes[(children.get() + i) as usize].children.is_none()
+            })
+        })
+    }
+
+    // prepares the update with the given metric, see prepare_update
+    // the priority of subdividing nodes comes from the priority metric
+    fn prepare_update_with_metric<T: Copy, M: LodMetric<L, T>, P: LodMetric<L, T>>(
+        &mut self,
+        targets: &[T],
+        metric: &M,
+        priority_metric: &P,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) -> bool {
+        //FIXME: this function currently will dry-run once for every update to make sure
+        // there is nothing left to update. This is a waste of CPU time, especially for many targets
+
+        // first, clear the previous arrays
+        self.chunks_to_add.clear();
+        self.chunks_to_remove.clear();
+        self.chunks_to_activate.clear();
+        self.chunks_to_deactivate.clear();
+        self.chunks_to_restitch.clear();
+        self.restitch_masks.clear();
+
+        // if we don't have a root, make one pending for creation
+        if self.nodes.is_empty() {
+            // in deferred mode, it's only added once it's ready
+            if self.update_mode == UpdateMode::Deferred {
+                return self.prepare_pending(L::root(), 0, chunk_creator);
+            }
+
+            // chunk to add
+            let chunk_to_add = self.get_chunk_from_cache(L::root(), chunk_creator);
+
+            // we need to add the root as pending
+            self.chunks_to_add.push(ToAddContainer {
+                position: L::root(),
+                chunk: chunk_to_add,
+                active: true,
+                parent_node_index: 0,
+                parent_pending: false,
+            });
+
+            // in single pass mode, the root is subdivided right away as well
+            if self.update_mode == UpdateMode::SinglePass {
+                self.subdivisions.clear();
+                self.queue_subdivision(L::root(), 0, 0, true, targets, metric, priority_metric);
+                self.subdivide_single_pass(targets, metric, priority_metric, chunk_creator);
+            }
+
+            // and an update is needed
+            return true;
+        }
+
+        // the single pass mode computes the changes differently
+        if self.update_mode == UpdateMode::SinglePass {
+            return self.prepare_update_single_pass(
+                targets,
+                metric,
+                priority_metric,
+                chunk_creator,
+            );
+        }
+
+        // clear the processing queue from any previous updates
+        self.processing_queue.clear();
+        self.subdivisions.clear();
+
+        // add the root node (always at 0, if there is no root we would have returned earlier) to the processing queue
+        self.processing_queue.push(QueueContainer {
+            position: L::root(),
+            node: 0,
+            depth: 0,
+        });
+
+        // then, traverse the tree, as long as something is inside the queue
+        while let Some(QueueContainer {
+            position: current_position,
+            node: current_node_index,
+            depth: current_depth,
+        }) = self.processing_queue.pop()
+        {
+            // fetch the current node
+            let current_node = self.nodes[current_node_index as usize];
+
+            // wether we can subdivide
+            let can_subdivide = targets
+                .iter()
+                .any(|x| metric.can_subdivide(current_position, current_depth, *x));
+
+            // if we can subdivide, and the current node does not have children, subdivide the current node
+            // this is done after going through the tree, so the most important nodes can be done first
+            if can_subdivide && current_node.children.is_none() {
+                self.subdivisions.push(SubdivisionContainer {
+                    priority: Self::priority_of(
+                        current_position,
+                        current_depth,
+                        targets,
+                        priority_metric,
+                    ),
+                    depth: current_depth,
+                    node: current_node_index,
+                    position: current_position,
+                    pending: false,
+                });
+            } else if let Some(index) = current_node.children {
+                // otherwise, if we cant subdivide and have children, remove our children
+                // when balancing, this waits until the leaves next to us are not too deep anymore
+                if !can_subdivide
+                    && !(0..current_position.num_children())
                         .into_iter()
                         .any(|i| self.nodes[(i + index.get()) as usize].children.is_some())
+                    && (!self.balanced || self.can_merge_balanced(current_position))
                 {
                     // first, queue ourselves for activation
                     self.chunks_to_activate.push(current_node_index);
 
-                    for i in 0..L::NUM_CHILDREN {
+                    for i in 0..current_position.num_children() {
                         // no need to do this in reverse, that way the last node removed will be added to the free list, which is also the first thing used by the adding logic
                         self.chunks_to_remove.push(ToRemoveContainer {
                             chunk: index.get() + i,
@@ -652,20 +1743,319 @@ where
                     }
                 } else {
                     // queue child nodes for processing if we didn't subdivide or clean up our children
-                    for i in 0..L::NUM_CHILDREN {
+                    for i in 0..current_position.num_children() {
+                        self.processing_queue.push(QueueContainer {
+                            position: current_position.get_child(i),
+                            node: index.get() + i,
+                            depth: current_depth + 1,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.subdivide_by_priority(chunk_creator);
+
+        // and return wether an update needs to be done
+        !self.chunks_to_add.is_empty() || !self.chunks_to_remove.is_empty()
+    }
+
+    // priority of subdividing a node, which is the lowest it has for any target
+    #[inline]
+    fn priority_of<T: Copy, P: LodMetric<L, T>>(
+        position: L,
+        depth: u8,
+        targets: &[T],
+        priority_metric: &P,
+    ) -> u64 {
+        targets
+            .iter()
+            .map(|x| priority_metric.priority(position, depth, *x))
+            .min()
+            .unwrap_or(0)
+    }
+
+    // subdivides the nodes in the subdivisions list, lowest priority first, and up to the max number of subdivisions
+    fn subdivide_by_priority(&mut self, chunk_creator: &mut dyn FnMut(L) -> C) {
+        let mut subdivisions = std::mem::take(&mut self.subdivisions);
+
+        // the node index keeps the order the same between updates
+        subdivisions.sort_unstable_by_key(|x| (x.priority, x.depth, x.node));
+
+        let mut budget = self.max_subdivisions.unwrap_or(usize::MAX);
+        for SubdivisionContainer { node, position, .. } in subdivisions.drain(..) {
+            if self.update_mode == UpdateMode::Deferred {
+                // in deferred mode, subdivide once all children are ready
+                // only making new pending chunks counts towards the budget
+                if !self.pending.contains_key(&position) {
+                    if budget == 0 {
+                        continue;
+                    }
+                    budget -= 1;
+                }
+
+                if self.prepare_pending(position, node, chunk_creator) {
+                    self.chunks_to_deactivate.push(node);
+                }
+            } else {
+                if budget == 0 {
+                    break;
+                }
+                budget -= 1;
+
+                // add children to be added
+                for i in 0..position.num_children() {
+                    // chunk to add
+                    let chunk_to_add =
+                        self.get_chunk_from_cache(position.get_child(i), chunk_creator);
+
+                    // add the new chunk to be added
+                    self.chunks_to_add.push(ToAddContainer {
+                        position: position.get_child(i),
+                        chunk: chunk_to_add,
+                        active: true,
+                        parent_node_index: node,
+                        parent_pending: false,
+                    });
+                }
+
+                // and add ourselves for deactivation
+                self.chunks_to_deactivate.push(node);
+            }
+        }
+
+        self.subdivisions = subdivisions;
+    }
+
+    // prepares the update by walking the tree once, and comparing it against the target tree
+    // a node in the target tree has children if any target allows it to subdivide
+    // existing leaves that need children get an entire new subtree queued for adding, up to the max number of subdivisions
+    // existing nodes that should not have children get their entire subtree queued for removal
+    fn prepare_update_single_pass<T: Copy, M: LodMetric<L, T>, P: LodMetric<L, T>>(
+        &mut self,
+        targets: &[T],
+        metric: &M,
+        priority_metric: &P,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) -> bool {
+        // clear the processing queue from any previous updates
+        self.processing_queue.clear();
+        self.subdivisions.clear();
+
+        // add the root node to the processing queue
+        self.processing_queue.push(QueueContainer {
+            position: L::root(),
+            node: 0,
+            depth: 0,
+        });
+
+        // then, traverse the tree, as long as something is inside the queue
+        while let Some(QueueContainer {
+            position: current_position,
+            node: current_node_index,
+            depth: current_depth,
+        }) = self.processing_queue.pop()
+        {
+            // fetch the current node
+            let current_node = self.nodes[current_node_index as usize];
+
+            // wether we can subdivide
+            let can_subdivide = targets
+                .iter()
+                .any(|x| metric.can_subdivide(current_position, current_depth, *x));
+
+            match (can_subdivide, current_node.children) {
+                // subdivide, and keep subdividing the new children as far as needed
+                // this is done after going through the tree, so the most important nodes can be done first
+                (true, None) => {
+                    self.subdivisions.push(SubdivisionContainer {
+                        priority: Self::priority_of(
+                            current_position,
+                            current_depth,
+                            targets,
+                            priority_metric,
+                        ),
+                        depth: current_depth,
+                        node: current_node_index,
+                        position: current_position,
+                        pending: false,
+                    });
+                }
+                // already subdivided, so check the children
+                (true, Some(index)) => {
+                    for i in 0..current_position.num_children() {
                         self.processing_queue.push(QueueContainer {
                             position: current_position.get_child(i),
                             node: index.get() + i,
+                            depth: current_depth + 1,
                         });
                     }
                 }
+                // should not be subdivided, so remove everything below us
+                (false, Some(_)) => {
+                    self.chunks_to_activate.push(current_node_index);
+                    self.queue_subtree_for_removal(current_node_index);
+                }
+                // leaf that stays a leaf
+                (false, None) => {}
             }
         }
 
+        self.subdivide_single_pass(targets, metric, priority_metric, chunk_creator);
+
         // and return wether an update needs to be done
         !self.chunks_to_add.is_empty() || !self.chunks_to_remove.is_empty()
     }
 
+    // adds a node to the subdivisions list, if any target allows it to subdivide
+    // used by the single pass mode for chunks to add, which are subdivided in the same update
+    #[allow(clippy::too_many_arguments)]
+    fn queue_subdivision<T: Copy, M: LodMetric<L, T>, P: LodMetric<L, T>>(
+        &mut self,
+        position: L,
+        depth: u8,
+        node: u32,
+        pending: bool,
+        targets: &[T],
+        metric: &M,
+        priority_metric: &P,
+    ) {
+        if targets
+            .iter()
+            .any(|x| metric.can_subdivide(position, depth, *x))
+        {
+            let priority = Self::priority_of(position, depth, targets, priority_metric);
+            self.subdivision_queue
+                .push(Reverse((priority, depth, self.subdivisions.len() as u32)));
+            self.subdivisions.push(SubdivisionContainer {
+                priority,
+                depth,
+                node,
+                position,
+                pending,
+            });
+        }
+    }
+
+    // subdivides the nodes in the subdivisions list for the single pass mode, lowest priority first, and up to the max number of subdivisions
+    // new children are subdivided in the same update if they need it, when it's their turn
+    // children are always queued after their parent, so the parent is in the tree by the time the children are added
+    fn subdivide_single_pass<T: Copy, M: LodMetric<L, T>, P: LodMetric<L, T>>(
+        &mut self,
+        targets: &[T],
+        metric: &M,
+        priority_metric: &P,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) {
+        // the node index keeps the order the same between updates, and the index in the list keeps parents before their children
+        self.subdivisions
+            .sort_unstable_by_key(|x| (x.priority, x.depth, x.pending, x.node));
+        self.subdivision_queue.clear();
+        self.subdivision_queue.extend(
+            self.subdivisions
+                .iter()
+                .enumerate()
+                .map(|(i, x)| Reverse((x.priority, x.depth, i as u32))),
+        );
+
+        let mut budget = self.max_subdivisions.unwrap_or(usize::MAX);
+        while let Some(Reverse((_, depth, index))) = self.subdivision_queue.pop() {
+            if budget == 0 {
+                break;
+            }
+            budget -= 1;
+
+            let SubdivisionContainer {
+                node,
+                position,
+                pending,
+                ..
+            } = self.subdivisions[index as usize];
+
+            if pending {
+                // this one won't be a leaf anymore
+                self.chunks_to_add[node as usize].active = false;
+            } else {
+                // add ourselves for deactivation
+                self.chunks_to_deactivate.push(node);
+            }
+
+            for i in 0..position.num_children() {
+                // chunk to add
+                let child = position.get_child(i);
+                let chunk_to_add = self.get_chunk_from_cache(child, chunk_creator);
+
+                // add the new chunk to be added, the parent may also be a chunk to add
+                self.chunks_to_add.push(ToAddContainer {
+                    position: child,
+                    chunk: chunk_to_add,
+                    active: true,
+                    parent_node_index: node,
+                    parent_pending: pending,
+                });
+
+                // and check if it needs to be subdivided as well
+                let child_index = self.chunks_to_add.len() as u32 - 1;
+                self.queue_subdivision(
+                    child,
+                    depth + 1,
+                    child_index,
+                    true,
+                    targets,
+                    metric,
+                    priority_metric,
+                );
+            }
+        }
+
+        self.subdivision_queue.clear();
+        self.subdivisions.clear();
+    }
+
+    // queues all nodes below the given node for removal
+    // children are removed before their parents, so the parent is still valid when the children are removed
+    fn queue_subtree_for_removal(&mut self, node_index: u32) {
+        let first = self.chunks_to_remove.len();
+
+        self.subtree_queue.clear();
+        self.subtree_queue.push((node_index, 0));
+
+        // depth isn't needed here
+        while let Some((parent, _)) = self.subtree_queue.pop() {
+            if let Some(index) = self.nodes[parent as usize].children {
+                for i in 0..Self::num_children_of_node(parent) {
+                    self.chunks_to_remove.push(ToRemoveContainer {
+                        chunk: index.get() + i,
+                        parent,
+                    });
+                    self.subtree_queue.push((index.get() + i, 0));
+                }
+            }
+        }
+
+        // groups were queued parents first, so reverse the order of the groups
+        // but keep the siblings in order, so their nodes stay contiguous when they are reused
+        // only the first group can be the children of the root, which is at the end after reversing
+        let queued = &mut self.chunks_to_remove[first..];
+        queued.reverse();
+
+        let (rest, first_group) =
+            queued.split_at_mut(queued.len() - Self::num_children_of_node(node_index) as usize);
+        first_group.reverse();
+        rest.chunks_mut(L::NUM_CHILDREN as usize)
+            .for_each(|group| group.reverse());
+    }
+
+    // the number of children of a node, the root is always at node 0
+    #[inline]
+    fn num_children_of_node(node_index: u32) -> u32 {
+        if node_index == 0 {
+            L::NUM_ROOT_CHILDREN
+        } else {
+            L::NUM_CHILDREN
+        }
+    }
+
     /// Runs the update that's stored in the internal lists.
     /// This adds and removes chunks based on that, however this assumes that chunks in the to_activate and to_deactivate list were manually activated or deactivated.
     /// This also assumes that the chunks in to_add had proper initialization, as they are added to the tree.
@@ -677,6 +2067,9 @@ where
         // this becomes useful later
         let mut chunks_to_add_iter = self.chunks_to_add.drain(..);
 
+        // keep track of where chunks got added, in case they are the parent of another chunk to add
+        self.added_nodes.clear();
+
         // then, remove old chunks, or cache them
         // we'll drain the vector, as we don't need it anymore afterward
         for ToRemoveContainer {
@@ -694,9 +2087,21 @@ where
 
             // but not so fast, because if we can overwrite it with a new chunk, do so
             // that way we can avoid a copy later on, which might be expensive
-            if let Some(ToAddContainer { position, chunk, parent_node_index:parent_index}) =
-                chunks_to_add_iter.next()
+            if let Some(ToAddContainer {
+                position,
+                chunk,
+                parent_node_index,
+                parent_pending,
+                ..
+            }) = chunks_to_add_iter.next()
             {
+                // the parent might have been added during this update
+                let parent_index = if parent_pending {
+                    self.added_nodes[parent_node_index as usize]
+                } else {
+                    parent_node_index
+                };
+
                 // add the node
                 let new_node_index = match self.free_list.pop_front() {
                     Some(x) => {
@@ -720,89 +2125,47 @@ where
                         let old_chunk = old_chunk;
 
                         // now, we can try to add this chunk into the cache
-                        // first, remove any extra nodes if they are in the cache
-                        while self.chunk_cache.len() > self.cache_size {
-                            if let Some(chunk_position) = self.cache_queue.pop_front() {
-                                // check if the chunk is inside the map
-                                if let Some(cached_chunk) = self.chunk_cache.remove(&chunk_position)
-                                {
-                                    // if it is, it's removed, so we need to push it to the chunks that are going to be deleted
-                                    self.chunks_to_delete.push(ToDeleteContainer {
-                                        position: chunk_position,
-                                        chunk: cached_chunk,
-                                    });
-                                }
-                            } else {
-                                // just break, otherwise we'll be stuck in an infinite loop
-                                break;
-                            }
-                        }
-                        if self.cache_size > 0 {
-                            // then assign this chunk into the cache
-                            if let Some(cached_chunk) =
-                                self.chunk_cache.insert(old_chunk.position, old_chunk.chunk)
-                            {
-                                // there might have been another cached chunk
-                                self.chunks_to_delete.push(ToDeleteContainer {
-                                    position: old_chunk.position,
-                                    chunk: cached_chunk,
-                                });
-                            }
-
-                            // and make sure it's tracked
-                            self.cache_queue.push_back(old_chunk.position);
-                        }
+                        // chunks that don't fit in the cache anymore are deleted
+                        let chunks_to_delete = &mut self.chunks_to_delete;
+                        self.cache.insert(
+                            old_chunk.position,
+                            old_chunk.chunk,
+                            &mut |position, chunk| {
+                                chunks_to_delete.push(ToDeleteContainer { position, chunk })
+                            },
+                        );
                         x
                     }
                     // This can't be reached due to us *always* adding a chunk to the free list before popping it
-                    None => unsafe { std::hint::unreachable_unchecked() },
+                    None => unreachable!("the free list is never empty after pushing to it"),
                 };
+                self.added_nodes.push(new_node_index);
 
                 // correctly set the children of the parent node.
                 // because the last node we come by in with ordered iteration is on num_children - 1, we need to set it as such].
                 // node 0 is the root, so the last child it has will be on num_children.
                 // then subtracting num_children - 1 from that gives us node 1, which is the first child of the root.
-                if new_node_index >= L::NUM_CHILDREN {
+                let num_children = Self::num_children_of_node(parent_index);
+                if new_node_index >= num_children {
                     // because we loop in order, and our nodes are contiguous, the first node of the children got added on index i - (num children - 1)
                     // so we need to adjust for that
                     self.nodes[parent_index as usize].children =
-                        NonZeroU32::new(new_node_index - (L::NUM_CHILDREN - 1));
+                        NonZeroU32::new(new_node_index - (num_children - 1));
                 }
             } else {
                 // otherwise we do need to do a regular swap remove
                 let old_chunk = self.chunks.swap_remove(chunk_index as usize);
 
                 // now, we can try to add this chunk into the cache
-                // first, remove any extra nodes if they are in the cache
-                while self.chunk_cache.len() > self.cache_size {
-                    if let Some(chunk_position) = self.cache_queue.pop_front() {
-                        // check if the chunk is inside the map
-                        if let Some(cached_chunk) = self.chunk_cache.remove(&chunk_position) {
-                            // if it is, it's removed, so we need to push it to the chunks that are going to be deleted
-                            self.chunks_to_delete.push(ToDeleteContainer {
-                                position: chunk_position,
-                                chunk: cached_chunk,
-                            });
-                        }
-                    } else {
-                        // just break, otherwise we'll be stuck in an infinite loop
-                        break;
-                    }
-                }
-                if self.cache_size > 0 {
-                    //then assign this chunk into the cache
-                    if let Some(cached_chunk) =
-                        self.chunk_cache.insert(old_chunk.position, old_chunk.chunk)
-                    {
-                        // there might have been another cached chunk
-                        self.chunks_to_delete.push(ToDeleteContainer {
-                            position: old_chunk.position,
-                            chunk: cached_chunk,
-                        });
-                    }
-                    // and make sure it's tracked
-                    self.cache_queue.push_back(old_chunk.position);
-                }
+                // chunks that don't fit in the cache anymore are deleted
+                let chunks_to_delete = &mut self.chunks_to_delete;
+                self.cache.insert(
+                    old_chunk.position,
+                    old_chunk.chunk,
+                    &mut |position, chunk| {
+                        chunks_to_delete.push(ToDeleteContainer { position, chunk })
+                    },
+                );
             }
 
             // and properly set the chunk pointer of the node of the chunk we just moved, if any
@@ -814,7 +2177,21 @@ where
 
         // add new chunks
         // we'll drain the vector here as well, as we won't need it anymore afterward
-        for ToAddContainer { position, chunk, parent_node_index:parent_index } in chunks_to_add_iter {
+        for ToAddContainer {
+            position,
+            chunk,
+            parent_node_index,
+            parent_pending,
+            ..
+        } in chunks_to_add_iter
+        {
+            // the parent might have been added during this update
+            let parent_index = if parent_pending {
+                self.added_nodes[parent_node_index as usize]
+            } else {
+                parent_node_index
+            };
+
             // add the node
             let new_node_index = match self.free_list.pop_front() {
                 Some(x) => {
@@ -844,16 +2221,18 @@ where
                     (self.nodes.len() - 1) as u32
                 }
             };
+            self.added_nodes.push(new_node_index);
 
             // correctly set the children of the parent node.
             // because the last node we come by in with ordered iteration is on num_children - 1, we need to set it as such].
             // node 0 is the root, so the last child it has will be on num_children.
             // then subtracting num_children - 1 from that gives us node 1, which is the first child of the root.
-            if new_node_index >= L::NUM_CHILDREN {
+            let num_children = Self::num_children_of_node(parent_index);
+            if new_node_index >= num_children {
                 // because we loop in order, and our nodes are contiguous, the first node of the children got added on index i - (num children - 1)
                 // so we need to adjust for that
                 self.nodes[parent_index as usize].children =
-                    NonZeroU32::new(new_node_index - (L::NUM_CHILDREN - 1));
+                    NonZeroU32::new(new_node_index - (num_children - 1));
             }
         }
 
@@ -869,11 +2248,67 @@ where
             );
         }
 
+        // the leaves next to nodes that were subdivided or merged now have a neighbour with a different lod level
+        self.find_chunks_to_restitch();
+
         // and clear all internal arrays, so if this method is accidentally called twice, no weird behavior would happen
+        // chunks may have moved, so the chunks to edit aren't valid anymore either
         self.chunks_to_add.clear();
         self.chunks_to_remove.clear();
         self.chunks_to_activate.clear();
         self.chunks_to_deactivate.clear();
+        self.chunks_to_edit.clear();
+
+        self.update_state = UpdateState::Done;
+    }
+
+    /// same as do_update, but returns an error if there's no update prepared by prepare_update to do.
+    /// This also catches calling do_update twice for the same update
+    pub fn try_do_update(&mut self) -> Result<(), Error> {
+        if self.update_state != UpdateState::Prepared {
+            return Err(Error::NoUpdatePrepared);
+        }
+
+        self.do_update();
+        Ok(())
+    }
+
+    // finds all leaves next to the chunks that were activated or deactivated in this update
+    // these nodes are still in the tree after the update, at the same index
+    fn find_chunks_to_restitch(&mut self) {
+        // node index, position and depth of the leaves
+        let mut leaves = Vec::new();
+        let mut stack = Vec::new();
+
+        for node_index in self
+            .chunks_to_activate
+            .iter()
+            .chain(self.chunks_to_deactivate.iter())
+        {
+            let position = self.chunks[self.nodes[*node_index as usize].chunk as usize].position;
+
+            for direction in 0..L::NUM_FACE_NEIGHBORS {
+                self.for_each_neighbor_leaf(
+                    position,
+                    direction,
+                    &mut stack,
+                    &mut |node, position, depth| leaves.push((node, position, depth)),
+                );
+            }
+        }
+
+        // a leaf can be next to multiple changed nodes
+        leaves.sort_unstable_by_key(|(node, _, _)| *node);
+        leaves.dedup_by_key(|(node, _, _)| *node);
+
+        // and find out what they border now
+        self.chunks_to_restitch.clear();
+        self.restitch_masks.clear();
+        for (node, position, depth) in leaves {
+            let mask = self.get_face_mask_at_depth(position, depth, &mut stack);
+            self.chunks_to_restitch.push(node);
+            self.restitch_masks.push(mask);
+        }
     }
 
     /// Completes the update by removing all chunks that can't be stored anymore permanently
@@ -881,6 +2316,10 @@ where
     pub fn complete_update(&mut self) {
         // just clear the chunks to be deleted
         self.chunks_to_delete.clear();
+        self.chunks_to_restitch.clear();
+        self.restitch_masks.clear();
+        self.chunks_to_edit.clear();
+        self.update_state = UpdateState::Idle;
     }
 
     /// clears the tree, removing all chunks and internal lists and cache
@@ -894,9 +2333,62 @@ where
         self.chunks_to_activate.clear();
         self.chunks_to_deactivate.clear();
         self.chunks_to_delete.clear();
+        self.chunks_to_restitch.clear();
+        self.restitch_masks.clear();
+        self.chunks_to_edit.clear();
         self.processing_queue.clear();
-        self.cache_queue.clear();
-        self.chunk_cache.clear();
+        self.subtree_queue.clear();
+        self.balance_set.clear();
+        self.balance_targets.clear();
+        self.balance_scratch.clear();
+        self.balance_queue.clear();
+        self.added_nodes.clear();
+        self.pending.clear();
+        self.cache.drain(&mut |_, _| {});
+        self.update_state = UpdateState::Idle;
+    }
+
+    // moves all chunks in the tree, the cache, the pending chunks and the chunks to add to chunks_to_delete, leaving the tree empty
+    pub(crate) fn queue_all_for_deletion(&mut self) {
+        for ChunkContainer {
+            chunk, position, ..
+        } in self.chunks.drain(..)
+        {
+            self.chunks_to_delete
+                .push(ToDeleteContainer { chunk, position });
+        }
+
+        for ToAddContainer {
+            chunk, position, ..
+        } in self.chunks_to_add.drain(..)
+        {
+            self.chunks_to_delete
+                .push(ToDeleteContainer { chunk, position });
+        }
+
+        for (_, group) in self.pending.drain() {
+            for PendingContainer {
+                chunk, position, ..
+            } in group.chunks
+            {
+                self.chunks_to_delete
+                    .push(ToDeleteContainer { chunk, position });
+            }
+        }
+
+        let chunks_to_delete = &mut self.chunks_to_delete;
+        self.cache.drain(&mut |position, chunk| {
+            chunks_to_delete.push(ToDeleteContainer { chunk, position })
+        });
+
+        self.nodes.clear();
+        self.free_list.clear();
+        self.chunks_to_remove.clear();
+        self.chunks_to_activate.clear();
+        self.chunks_to_deactivate.clear();
+        self.chunks_to_restitch.clear();
+        self.restitch_masks.clear();
+        self.chunks_to_edit.clear();
     }
 
     /// Shrinks all internal buffers to fit, reducing memory usage.
@@ -914,29 +2406,322 @@ where
         self.chunks_to_activate.shrink_to_fit();
         self.chunks_to_deactivate.shrink_to_fit();
         self.chunks_to_delete.shrink_to_fit();
+        self.chunks_to_restitch.shrink_to_fit();
+        self.restitch_masks.shrink_to_fit();
+        self.chunks_to_edit.shrink_to_fit();
         self.processing_queue.shrink_to_fit();
-        self.cache_queue.shrink_to_fit();
+        self.subtree_queue.shrink_to_fit();
+        self.balance_set.shrink_to_fit();
+        self.balance_targets.shrink_to_fit();
+        self.balance_scratch.shrink_to_fit();
+        self.balance_queue.shrink_to_fit();
+        self.added_nodes.shrink_to_fit();
+        self.subdivisions.shrink_to_fit();
+        self.subdivision_queue.shrink_to_fit();
+        self.pending.shrink_to_fit();
+        self.reusable.shrink_to_fit();
+        self.group_order.shrink_to_fit();
+        self.cache.shrink_to_fit();
+    }
+
+    /// sets how prepare_update computes the changes to the tree, see UpdateMode
+    #[inline]
+    pub fn set_update_mode(&mut self, update_mode: UpdateMode) {
+        self.update_mode = update_mode;
     }
 
-    /// resizes the current cache size
+    /// sets the max number of nodes that are subdivided in one update, or None to subdivide all nodes that need it.
+    /// The nodes with the lowest priority are subdivided first, see LodMetric::priority, and the rest is left for later updates.
+    /// Nodes close to the targets are subdivided first with the metrics that come with the crate.
+    ///
+    /// With UpdateMode::Deferred this limits how many nodes start to make pending chunks,
+    /// and with UpdateMode::SinglePass it also counts the new chunks that are subdivided in the same update
+    #[inline]
+    pub fn set_max_subdivisions(&mut self, max_subdivisions: Option<usize>) {
+        self.max_subdivisions = max_subdivisions;
+    }
+
+    /// sets wether the tree is kept balanced, so neighbouring leaves differ by at most one lod level.
+    /// This subdivides extra nodes around the ones the metric asks for,
+    /// and waits with merging nodes until the leaves next to them are no more than one lod level deeper.
+    #[inline]
+    pub fn set_balanced(&mut self, balanced: bool) {
+        self.balanced = balanced;
+    }
+
+    /// resizes the current cache size, in chunks or in the total weight of the chunks for a WeightedCache
     /// actual resizing happens on the next update
     #[inline]
     pub fn set_cache_size(&mut self, cache_size: usize) {
-        self.cache_size = cache_size;
+        self.cache.set_size(cache_size);
+    }
+
+    /// gets the cache, to see how full it is
+    #[inline]
+    pub fn get_cache(&self) -> &K {
+        &self.cache
+    }
+
+    /// gets the cache mutably, such as to get to the store of a TieredCache.
+    /// Chunks evicted by inserting into it directly don't end up in chunks_to_delete, use insert_into_cache for that
+    #[inline]
+    pub fn get_cache_mut(&mut self) -> &mut K {
+        &mut self.cache
+    }
+
+    /// get the number of chunks in the cache
+    #[inline]
+    pub fn get_num_cached_chunks(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// iterates over all chunks in the cache and their positions, in the order they would be evicted in
+    #[inline]
+    pub fn iter_cached_chunks(&self) -> impl Iterator<Item = (L, &C)> + '_ {
+        self.cache.iter()
+    }
+
+    /// gets a chunk in the cache by position, or none if it's not cached
+    #[inline]
+    pub fn get_cached_chunk(&self, position: L) -> Option<&C> {
+        self.cache.get(position)
+    }
+
+    /// gets a mutable chunk in the cache by position, or none if it's not cached
+    #[inline]
+    pub fn get_cached_chunk_mut(&mut self, position: L) -> Option<&mut C> {
+        self.cache.get_mut(position)
+    }
+
+    /// takes a chunk out of the cache, so it's made again the next time it's added to the tree.
+    /// Caches that keep chunks elsewhere, like a TieredCache, can give chunks that get_cached_chunk doesn't, see ChunkCache::remove
+    #[inline]
+    pub fn take_cached_chunk(&mut self, position: L) -> Option<C> {
+        self.cache.remove(position)
+    }
+
+    /// puts a chunk in the cache, such as one loaded from disk, so it's used the next time that position is added to the tree.
+    /// Chunks that don't fit in the cache anymore, including one that was already cached at that position, are put in chunks_to_delete,
+    /// so they need to be cleaned up before the next complete_update
+    pub fn insert_into_cache(&mut self, position: L, chunk: C) {
+        let chunks_to_delete = &mut self.chunks_to_delete;
+        self.cache.insert(position, chunk, &mut |position, chunk| {
+            chunks_to_delete.push(ToDeleteContainer { position, chunk })
+        });
+    }
+
+    /// removes a chunk from the cache and puts it in chunks_to_delete, such as when it's outdated after an edit.
+    /// The chunk is also removed from anywhere else the cache keeps it, like the store of a TieredCache, see ChunkCache::evict.
+    /// Returns wether there was a chunk in the cache at that position
+    pub fn evict_cached(&mut self, position: L) -> bool {
+        match self.cache.evict(position) {
+            Some(chunk) => {
+                self.chunks_to_delete
+                    .push(ToDeleteContainer { position, chunk });
+                true
+            }
+            None => false,
+        }
     }
 
     // gets a chunk from the cache, otehrwise generates one from the given function
+    // chunks from an update that's being revalidated are used first
     #[inline]
     fn get_chunk_from_cache(&mut self, position: L, chunk_creator: &mut dyn FnMut(L) -> C) -> C {
-        if self.cache_size > 0 {
-            if let Some(chunk) = self.chunk_cache.remove(&position) {
-                return chunk;
-            }
+        if let Some(chunk) = self.reusable.remove(&position) {
+            return chunk;
+        }
+        if let Some(chunk) = self.cache.remove(position) {
+            return chunk;
         }
         chunk_creator(position)
     }
 }
 
+impl<C, L, K> Tree<C, L, K>
+where
+    C: Sized,
+    L: GridVec,
+    K: ChunkCache<C, L>,
+{
+    /// Moves the origin of the tree by offset nodes at the given depth, such as the top level nodes at depth 1.
+    /// The node that was at the offset is at 0 afterwards, so when the world origin moves, the chunks don't need to be made again.
+    ///
+    /// Chunks that stay inside the root are kept, and their positions are moved.
+    /// Chunks that end up outside of the root are put in chunks_to_delete, as are chunks above the given depth,
+    /// as they can't be moved by a whole node, and cached chunks outside of the root.
+    /// Chunks the cache keeps elsewhere, like in the store of a TieredCache, are moved as well, see ChunkCache::shift.
+    /// The nodes that came into the root are taken from the moved cache, or made with chunk_creator otherwise.
+    /// The ones that are leaves are put in chunks_to_activate, and the ones that have children are put in chunks_to_deactivate,
+    /// so every new chunk is reported.
+    ///
+    /// Any prepared update and pending chunks are discarded, so call this between updates, and call complete_update() after handling the chunks to delete.
+    /// # Args
+    /// * `offset` How many nodes at the given depth to move the origin by
+    /// * `depth` The depth of the nodes to move by
+    /// * `chunk_creator` function to create a new chunk from a given position
+    pub fn shift_origin(
+        &mut self,
+        offset: L::Region,
+        depth: u8,
+        chunk_creator: &mut dyn FnMut(L) -> C,
+    ) {
+        // discard the prepared update
+        for ToAddContainer {
+            chunk, position, ..
+        } in self.chunks_to_add.drain(..)
+        {
+            self.chunks_to_delete
+                .push(ToDeleteContainer { chunk, position });
+        }
+        self.chunks_to_remove.clear();
+        self.chunks_to_activate.clear();
+        self.chunks_to_deactivate.clear();
+        self.chunks_to_restitch.clear();
+        self.restitch_masks.clear();
+        self.chunks_to_edit.clear();
+
+        // the chunks to delete still need to be handled
+        self.update_state = UpdateState::Done;
+
+        // pending chunks are at their old positions, so cancel them before the cache is moved
+        self.cancel_pending(true);
+
+        // everything the cache keeps elsewhere is at it's old position
+        self.cache
+            .shift(&mut |position| position.shift_origin(offset, depth));
+
+        // move the cache, keeping the order chunks are evicted in
+        let mut cached = Vec::with_capacity(self.cache.len());
+        self.cache
+            .drain(&mut |position, chunk| cached.push((position, chunk)));
+        for (position, chunk) in cached {
+            let chunks_to_delete = &mut self.chunks_to_delete;
+            match position.shift_origin(offset, depth) {
+                Some(moved) => self.cache.insert(moved, chunk, &mut |position, chunk| {
+                    chunks_to_delete.push(ToDeleteContainer { chunk, position })
+                }),
+                None => chunks_to_delete.push(ToDeleteContainer { chunk, position }),
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        // find the subtrees that stay inside the root, by their new position
+        let mut moved_subtrees = HashMap::new();
+
+        // and the nodes above them, which need to be subdivided
+        let mut subdivided = HashSet::new();
+
+        self.processing_queue.clear();
+        self.processing_queue.push(QueueContainer {
+            position: L::root(),
+            node: 0,
+            depth: 0,
+        });
+
+        while let Some(QueueContainer {
+            position,
+            node,
+            depth: node_depth,
+        }) = self.processing_queue.pop()
+        {
+            if let Some(moved) = position.shift_origin(offset, depth) {
+                // walk down from the root to the new position, to find the nodes above it
+                let mut parent = L::root();
+                while parent != moved {
+                    subdivided.insert(parent);
+                    parent = (0..parent.num_children())
+                        .map(|i| parent.get_child(i))
+                        .find(|child| *child == moved || child.contains_child_node(moved))
+                        .expect("moved node is inside the root");
+                }
+
+                moved_subtrees.insert(moved, node);
+            } else if let (true, Some(children)) =
+                (node_depth < depth, self.nodes[node as usize].children)
+            {
+                // nodes above the depth can have children that can be moved
+                for i in 0..position.num_children() {
+                    self.processing_queue.push(QueueContainer {
+                        position: position.get_child(i),
+                        node: children.get() + i,
+                        depth: node_depth + 1,
+                    });
+                }
+            }
+        }
+
+        // then build the new tree from the root down, moving the subtrees into it
+        let old_nodes = std::mem::take(&mut self.nodes);
+        let mut old_chunks: Vec<Option<ChunkContainer<C, L>>> =
+            self.chunks.drain(..).map(Some).collect();
+        self.free_list.clear();
+
+        self.nodes.push(TreeNode::default());
+        let mut queue = vec![(L::root(), 0u32, moved_subtrees.get(&L::root()).copied())];
+
+        while let Some((position, node, old_node)) = queue.pop() {
+            // take the chunk from the old tree, or the cache, or make a new one
+            let (chunk, old_children) = match old_node {
+                Some(old_node) => {
+                    let old_node = old_nodes[old_node as usize];
+                    let old_chunk = old_chunks[old_node.chunk as usize]
+                        .take()
+                        .expect("chunk is only moved once");
+                    (old_chunk.chunk, old_node.children)
+                }
+                None => match self.cache.remove(position) {
+                    Some(chunk) => (chunk, None),
+                    None => (chunk_creator(position), None),
+                },
+            };
+
+            self.nodes[node as usize].chunk = self.chunks.len() as u32;
+            self.chunks.push(ChunkContainer {
+                chunk,
+                index: node,
+                position,
+            });
+
+            if old_children.is_some() || (old_node.is_none() && subdivided.contains(&position)) {
+                // new nodes with children aren't visible
+                if old_node.is_none() {
+                    self.chunks_to_deactivate.push(node);
+                }
+
+                let first_child = self.nodes.len() as u32;
+                self.nodes[node as usize].children = NonZeroU32::new(first_child);
+
+                for i in 0..position.num_children() {
+                    let child = position.get_child(i);
+                    let old_child = match old_children {
+                        Some(old_children) => Some(old_children.get() + i),
+                        None => moved_subtrees.get(&child).copied(),
+                    };
+
+                    self.nodes.push(TreeNode::default());
+                    queue.push((child, first_child + i, old_child));
+                }
+            } else if old_node.is_none() {
+                // new leaves need to be made visible
+                self.chunks_to_activate.push(node);
+            }
+        }
+
+        // and everything that wasn't moved is deleted
+        for ChunkContainer {
+            chunk, position, ..
+        } in old_chunks.into_iter().flatten()
+        {
+            self.chunks_to_delete
+                .push(ToDeleteContainer { chunk, position });
+        }
+    }
+}
+
 impl<C, L> Default for Tree<C, L>
 where
     C: Sized,
@@ -948,6 +2733,201 @@ where
     }
 }
 
+// stable representation of the tree, used for serialization
+// this only holds what's in the tree and cache, so any pending update is not stored
+// cached chunks are stored oldest first, so the order they are evicted in is kept
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct TreeDataRef<'a, C: Sized, L: LodVec> {
+    nodes: &'a [TreeNode],
+    chunks: &'a [ChunkContainer<C, L>],
+    free_list: &'a VecDeque<u32>,
+    cache_size: usize,
+    cache: Vec<(L, &'a C)>,
+    update_mode: UpdateMode,
+    balanced: bool,
+    max_subdivisions: Option<usize>,
+}
+
+// same as above, but owned, for deserialization
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct TreeData<C: Sized, L: LodVec> {
+    nodes: Vec<TreeNode>,
+    chunks: Vec<ChunkContainer<C, L>>,
+    free_list: VecDeque<u32>,
+    cache_size: usize,
+    cache: Vec<(L, C)>,
+    update_mode: UpdateMode,
+    #[serde(default)]
+    balanced: bool,
+    #[serde(default)]
+    max_subdivisions: Option<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl<C: Sized, L: LodVec> TreeData<C, L> {
+    // checks that the nodes form a tree, and that every node and chunk point to each other
+    // this walks the tree from the root, so every node has a known position
+    fn validate(&self) -> Result<(), &'static str> {
+        // free nodes need to be in range, and only be free once
+        let mut free = vec![false; self.nodes.len()];
+        for index in self.free_list.iter() {
+            match free.get_mut(*index as usize) {
+                Some(x) if !*x => *x = true,
+                Some(_) => return Err("node is in the free list twice"),
+                None => return Err("free node index out of range"),
+            }
+        }
+
+        // and come in whole sibling groups, in order, as do_update reuses them one group at a time
+        // groups are made after the root, with the children of the root first
+        let mut i = 0;
+        while let Some(&first) = self.free_list.get(i) {
+            let (group_len, aligned) = if first == 1 {
+                (L::NUM_ROOT_CHILDREN, true)
+            } else {
+                let first_group = 1 + L::NUM_ROOT_CHILDREN;
+                (
+                    L::NUM_CHILDREN,
+                    first >= first_group && (first - first_group) % L::NUM_CHILDREN == 0,
+                )
+            };
+
+            if !aligned
+                || !(0..group_len).all(|j| self.free_list.get(i + j as usize) == Some(&(first + j)))
+            {
+                return Err("free nodes are not whole sibling groups");
+            }
+            i += group_len as usize;
+        }
+
+        // walk all nodes from the root, with the position they should have
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push((0, L::root()));
+        }
+
+        while let Some((index, position)) = stack.pop() {
+            if free[index] {
+                return Err("node in the tree is in the free list");
+            }
+            if std::mem::replace(&mut visited[index], true) {
+                return Err("node is in the tree twice");
+            }
+
+            // the chunk needs to point back to it's node, and be at the same position
+            let node = self.nodes[index];
+            match self.chunks.get(node.chunk as usize) {
+                Some(container) if container.index as usize != index => {
+                    return Err("chunk does not match it's node")
+                }
+                Some(container) if container.position != position => {
+                    return Err("chunk position does not match it's place in the tree")
+                }
+                Some(_) => {}
+                None => return Err("chunk index out of range"),
+            }
+
+            // and the children need to be in range
+            if let Some(children) = node.children {
+                let first = children.get() as usize;
+                let num_children = position.num_children() as usize;
+                if first + num_children > self.nodes.len() {
+                    return Err("child node index out of range");
+                }
+                for i in 0..num_children {
+                    stack.push((first + i, position.get_child(i as u32)));
+                }
+            }
+        }
+
+        // all nodes need to be either in the tree or free
+        if visited
+            .iter()
+            .zip(free.iter())
+            .any(|(visited, free)| !visited && !free)
+        {
+            return Err("node is not in the tree, and not free");
+        }
+
+        // every node in the tree has it's own chunk, so this means every chunk has a node
+        if self.nodes.len() - self.free_list.len() != self.chunks.len() {
+            return Err("number of nodes does not match number of chunks");
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes the chunks and cache of the tree.
+/// Any pending update is not serialized, so this is best done after complete_update
+#[cfg(feature = "serde")]
+impl<C, L, K> serde::Serialize for Tree<C, L, K>
+where
+    C: Sized + serde::Serialize,
+    L: LodVec + serde::Serialize,
+    K: ChunkCache<C, L>,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // cached chunks, in the order they are evicted
+        let mut cache = Vec::with_capacity(self.cache.len());
+        self.cache
+            .for_each(&mut |position, chunk| cache.push((position, chunk)));
+
+        TreeDataRef {
+            nodes: &self.nodes,
+            chunks: &self.chunks,
+            free_list: &self.free_list,
+            cache_size: self.cache.size(),
+            cache,
+            update_mode: self.update_mode,
+            balanced: self.balanced,
+            max_subdivisions: self.max_subdivisions,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializes a tree, checking that the nodes form a tree, and that the nodes and chunks point to each other correctly.
+/// The cache is made with Default, and then resized to the stored size
+#[cfg(feature = "serde")]
+impl<'de, C, L, K> serde::Deserialize<'de> for Tree<C, L, K>
+where
+    C: Sized + serde::Deserialize<'de>,
+    L: LodVec + serde::Deserialize<'de>,
+    K: ChunkCache<C, L> + Default,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let data = TreeData::<C, L>::deserialize(deserializer)?;
+
+        data.validate().map_err(D::Error::custom)?;
+
+        let mut cache = K::default();
+        cache.set_size(data.cache_size);
+        let mut tree = Self::with_capacity_and_cache(data.nodes.len(), cache);
+        tree.nodes = data.nodes;
+        tree.chunks = data.chunks;
+        tree.free_list = data.free_list;
+        tree.update_mode = data.update_mode;
+        tree.balanced = data.balanced;
+        tree.max_subdivisions = data.max_subdivisions;
+
+        // and restore the cache
+        for (position, chunk) in data.cache {
+            let chunks_to_delete = &mut tree.chunks_to_delete;
+            tree.cache.insert(position, chunk, &mut |position, chunk| {
+                chunks_to_delete.push(ToDeleteContainer { position, chunk })
+            });
+        }
+
+        Ok(tree)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -1065,4 +3045,961 @@ mod tests {
     pub fn alignment() {
         assert_eq!(std::mem::size_of::<TreeNode>(), 8);
     }
+
+    #[test]
+    fn metric() {
+        // a closure metric that defers to the detail based one should give the same tree
+        let mut reference = Tree::<TestChunk, OctVec>::new(0);
+        let mut tree = Tree::<TestChunk, OctVec>::new(0);
+        let tgt = OctVec::new(5, 3, 6, 4);
+
+        while reference.prepare_update(&[tgt], 2, &mut |_| TestChunk {}) {
+            reference.do_update();
+        }
+
+        let metric = |node: OctVec, depth: u8, target: OctVec| {
+            // depth is tracked by the tree
+            assert_eq!(node.depth, depth);
+            target.can_subdivide(node, 2)
+        };
+        while tree.prepare_update(&[tgt], metric, &mut |_| TestChunk {}) {
+            tree.do_update();
+        }
+
+        // the closure has no priority, so the chunks can be stored in a different order
+        let mut expected = reference.iter_chunk_positions().collect::<Vec<_>>();
+        let mut found = tree.iter_chunk_positions().collect::<Vec<_>>();
+        expected.sort_by_key(|pos| (pos.depth, pos.x, pos.y, pos.z));
+        found.sort_by_key(|pos| (pos.depth, pos.x, pos.y, pos.z));
+        assert_eq!(expected, found);
+
+        // and a metric that never subdivides only keeps the root
+        while tree.prepare_update(&[tgt], |_, _, _| false, &mut |_| TestChunk {}) {
+            tree.do_update();
+        }
+        assert_eq!(tree.get_num_chunks(), 1);
+    }
+
+    #[test]
+    fn per_target_detail() {
+        use std::collections::HashSet;
+
+        let camera = Target::new(QuadVec::new(10, 12, 5), 3);
+        let light = Target {
+            max_depth: 3,
+            ..Target::new(QuadVec::new(30, 2, 5), 1)
+        };
+
+        // build the tree for each target on it's own
+        let mut expected = HashSet::new();
+        for target in [camera, light] {
+            let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+            while tree.prepare_update(&[target], TargetDetail, &mut |_| TestChunk {}) {
+                tree.do_update();
+            }
+            expected.extend(tree.iter_chunk_positions());
+        }
+
+        // and both at once, which should give the same chunks
+        let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+        while tree.prepare_update(&[camera, light], TargetDetail, &mut |_| TestChunk {}) {
+            tree.do_update();
+        }
+        let found = tree.iter_chunk_positions().collect::<HashSet<_>>();
+        assert_eq!(expected, found);
+
+        // the light does not go past it's max depth, which it would do otherwise
+        for (target, deepest) in [
+            (light, 3),
+            (
+                Target {
+                    max_depth: 255,
+                    ..light
+                },
+                5,
+            ),
+        ] {
+            let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+            while tree.prepare_update(&[target], TargetDetail, &mut |_| TestChunk {}) {
+                tree.do_update();
+            }
+            assert_eq!(
+                tree.iter_chunk_positions().map(|x| x.depth).max(),
+                Some(deepest)
+            );
+        }
+
+        // heavier targets get their nodes subdivided first
+        let node = QuadVec::new(0, 0, 5);
+        let heavy = Target {
+            weight: 4.0,
+            ..camera
+        };
+        let weightless = Target {
+            weight: 0.0,
+            ..camera
+        };
+        assert_eq!(TargetDetail.priority(node, 5, camera), 12);
+        assert_eq!(TargetDetail.priority(node, 5, heavy), 3);
+        assert_eq!(TargetDetail.priority(node, 5, weightless), u64::MAX);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        // chunks that remember where they were made
+        let mut tree = Tree::<(u64, u64), QuadVec>::new(16);
+        for tgt in [QuadVec::new(3, 12, 4), QuadVec::new(14, 1, 4)] {
+            while tree.prepare_update(&[tgt], 1, &mut |p| (p.x, p.y)) {
+                tree.do_update();
+                tree.complete_update();
+            }
+        }
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let mut restored: Tree<(u64, u64), QuadVec> = serde_json::from_str(&json).unwrap();
+
+        // all positions give the same chunks
+        for position in Tree::<(), QuadVec>::iter_all_chunks_in_bounds(
+            QuadVec::new(0, 0, 4),
+            QuadVec::new(15, 15, 4),
+            4,
+        ) {
+            assert_eq!(
+                tree.get_chunk_from_position(position),
+                restored.get_chunk_from_position(position)
+            );
+        }
+
+        // and the cache is restored as well, so moving back needs the same amount of new chunks
+        let mut created = [0, 0];
+        for (tree, created) in [&mut tree, &mut restored]
+            .into_iter()
+            .zip(created.iter_mut())
+        {
+            while tree.prepare_update(&[QuadVec::new(3, 12, 4)], 1, &mut |p| {
+                *created += 1;
+                (p.x, p.y)
+            }) {
+                tree.do_update();
+                tree.complete_update();
+            }
+        }
+        assert_eq!(created[0], created[1]);
+        assert_eq!(
+            serde_json::to_string(&tree).unwrap(),
+            serde_json::to_string(&restored).unwrap()
+        );
+
+        // and broken trees are refused
+        let broken = json.replacen("\"index\":", "\"index\":1000", 1);
+        assert!(serde_json::from_str::<Tree<(u64, u64), QuadVec>>(&broken).is_err());
+
+        // including ones that only break the structure of the tree
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(!value["free_list"].as_array().unwrap().is_empty());
+        let breaks: [fn(&mut serde_json::Value); 6] = [
+            // node that is it's own child
+            |data| data["nodes"][1]["children"] = 1.into(),
+            // node that is free twice
+            |data| {
+                let free = data["free_list"][0].clone();
+                data["free_list"].as_array_mut().unwrap().push(free);
+            },
+            // node that is free, but still in the tree
+            |data| data["free_list"].as_array_mut().unwrap().push(1.into()),
+            // free nodes that are not in the order of their sibling group
+            |data| data["free_list"].as_array_mut().unwrap().swap(0, 1),
+            // node that can't be reached
+            |data| {
+                let node = serde_json::json!({ "children": null, "chunk": 0 });
+                data["nodes"].as_array_mut().unwrap().push(node);
+            },
+            // chunk that's not where it's node is
+            |data| data["chunks"][1]["position"]["x"] = 1000.into(),
+        ];
+        for break_tree in breaks {
+            let mut broken = value.clone();
+            break_tree(&mut broken);
+            assert!(serde_json::from_value::<Tree<(u64, u64), QuadVec>>(broken).is_err());
+        }
+        assert!(serde_json::from_value::<Tree<(u64, u64), QuadVec>>(value).is_ok());
+    }
+
+    #[test]
+    fn single_pass_update() {
+        use std::collections::HashSet;
+
+        // reference tree, updated one level at a time
+        let mut reference = Tree::<TestChunk, QuadVec>::new(64);
+
+        // and a tree that does it in one go
+        let mut tree = Tree::<TestChunk, QuadVec>::new(64);
+        tree.set_update_mode(UpdateMode::SinglePass);
+
+        // teleport the target around, including back to where it was
+        for tgt in [
+            QuadVec::new(1, 1, 2),
+            QuadVec::new(60, 3, 6),
+            QuadVec::new(2, 50, 6),
+            QuadVec::new(60, 3, 6),
+            QuadVec::new(0, 0, 1),
+        ] {
+            while reference.prepare_update(&[tgt], 1, &mut |_| TestChunk {}) {
+                reference.do_update();
+                reference.complete_update();
+            }
+
+            // one update is enough
+            if tree.prepare_update(&[tgt], 1, &mut |_| TestChunk {}) {
+                // parents are always added before their children
+                let added = tree.iter_chunks_to_add_positions().collect::<Vec<_>>();
+                for (i, position) in added.iter().enumerate() {
+                    for earlier in added[..i].iter() {
+                        assert!(
+                            earlier.depth <= position.depth
+                                || !position.contains_child_node(*earlier)
+                        );
+                    }
+                }
+
+                tree.do_update();
+                tree.complete_update();
+            }
+            assert!(!tree.prepare_update(&[tgt], 1, &mut |_| TestChunk {}));
+
+            // and both trees have the same chunks
+            let expected = reference.iter_chunk_positions().collect::<HashSet<_>>();
+            let found = tree.iter_chunk_positions().collect::<HashSet<_>>();
+            assert_eq!(expected, found);
+
+            // which can all be found in the tree
+            for position in found {
+                assert!(tree.get_chunk_from_position(position).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors() {
+        // neighbours stay inside the root
+        assert_eq!(QuadVec::new(0, 0, 1).neighbor(0), None);
+        assert_eq!(
+            QuadVec::new(0, 0, 1).neighbor(1),
+            Some(QuadVec::new(1, 0, 1))
+        );
+        assert_eq!(QuadVec::new(1, 1, 1).neighbor(7), None);
+        assert_eq!(
+            OctVec::new(1, 1, 1, 2).neighbor(18),
+            Some(OctVec::new(0, 0, 0, 2))
+        );
+        assert_eq!(OctVec::new(3, 1, 1, 2).neighbor(1), None);
+
+        // the direction back is the opposite one
+        assert_eq!(QuadVec::new(1, 1, 2).opposite_direction(0), Some(1));
+        assert_eq!(QuadVec::new(1, 1, 2).opposite_direction(4), Some(7));
+        assert_eq!(OctVec::new(1, 1, 1, 2).opposite_direction(18), Some(25));
+        assert_eq!(QuadVec::new(0, 0, 1).opposite_direction(0), None);
+
+        // and directions past the number of neighbours don't exist
+        assert_eq!(QuadVec::new(1, 1, 2).neighbor(QuadVec::NUM_NEIGHBORS), None);
+        assert_eq!(
+            OctVec::new(1, 1, 1, 2).neighbor(OctVec::NUM_NEIGHBORS),
+            None
+        );
+        assert_eq!(LodVecN::new([1, 1, 1, 1], 2).neighbor(80), None);
+        assert_eq!(
+            CubeFaceVec::new(0, QuadVec::new(0, 0, 2)).neighbor(u32::MAX),
+            None
+        );
+
+        let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+        assert!(tree.get_neighbors(QuadVec::root()).is_none());
+
+        while tree.prepare_update(&[QuadVec::new(5, 9, 5)], 1, &mut |_| TestChunk {}) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        let positions = tree.iter_chunk_positions().collect::<Vec<_>>();
+        let is_leaf = |position: QuadVec| {
+            !positions
+                .iter()
+                .any(|x| x.depth > position.depth && position.contains_child_node(*x))
+        };
+
+        for position in positions.iter().copied().filter(|x| is_leaf(*x)) {
+            let neighbors = tree.get_neighbors(position).unwrap();
+
+            for direction in 0..QuadVec::NUM_FACE_NEIGHBORS {
+                let across = neighbors.iter().filter(|x| x.direction == direction);
+
+                // the face at the edge of the tree has nothing across it
+                let Some(same_depth) = position.neighbor(direction) else {
+                    assert_eq!(across.count(), 0);
+                    continue;
+                };
+
+                // otherwise the neighbours cover the face exactly
+                let mut covered = 0.0;
+                for neighbor in across {
+                    assert!(is_leaf(neighbor.position));
+                    assert_eq!(neighbor.depth, neighbor.position.depth);
+                    assert_eq!(tree.get_chunk_position(neighbor.index), neighbor.position);
+
+                    if neighbor.depth <= position.depth {
+                        // one bigger or equal chunk
+                        assert!(neighbor.position.contains_child_node(same_depth));
+                        covered += position.get_size();
+                    } else {
+                        // or smaller chunks touching the face
+                        assert!(same_depth.contains_child_node(neighbor.position));
+                        assert!(position.contains_child_node(
+                            neighbor
+                                .position
+                                .neighbor(position.opposite_direction(direction).unwrap())
+                                .unwrap()
+                        ));
+                        covered += neighbor.position.get_size();
+                    }
+                }
+                assert_eq!(covered, position.get_size());
+            }
+        }
+    }
+
+    #[test]
+    fn balanced_update() {
+        // every leaf is within one lod level of the leaves next to it
+        fn check_balanced(tree: &Tree<TestChunk, QuadVec>) {
+            let positions = (0..tree.get_num_chunks())
+                .map(|i| tree.get_chunk_position(i))
+                .collect::<Vec<_>>();
+
+            for position in positions.iter().copied() {
+                let neighbors = tree.get_neighbors(position).unwrap();
+                let is_leaf = !positions
+                    .iter()
+                    .any(|x| x.depth > position.depth && position.contains_child_node(*x));
+
+                if is_leaf {
+                    for neighbor in neighbors {
+                        assert!(neighbor.depth.abs_diff(position.depth) <= 1);
+                    }
+                }
+            }
+        }
+
+        for mode in [UpdateMode::OneLevel, UpdateMode::SinglePass] {
+            let mut unbalanced = Tree::<TestChunk, QuadVec>::new(64);
+            unbalanced.set_update_mode(mode);
+
+            let mut tree = Tree::<TestChunk, QuadVec>::new(64);
+            tree.set_update_mode(mode);
+            tree.set_balanced(true);
+
+            for tgt in [
+                QuadVec::new(0, 0, 6),
+                QuadVec::new(63, 63, 6),
+                QuadVec::new(31, 32, 6),
+                QuadVec::new(0, 0, 1),
+            ] {
+                while unbalanced.prepare_update(&[tgt], 0, &mut |_| TestChunk {}) {
+                    unbalanced.do_update();
+                    unbalanced.complete_update();
+                }
+
+                while tree.prepare_update(&[tgt], 0, &mut |_| TestChunk {}) {
+                    tree.do_update();
+
+                    // stays balanced during the update as well
+                    check_balanced(&tree);
+
+                    // and only leaves need to be restitched
+                    for i in 0..tree.get_num_chunks_to_restitch() {
+                        let position = tree.get_position_of_chunk_to_restitch(i);
+                        assert!(!(0..tree.get_num_chunks())
+                            .map(|i| tree.get_chunk_position(i))
+                            .any(|x| x.depth > position.depth && position.contains_child_node(x)));
+                    }
+
+                    tree.complete_update();
+                    assert_eq!(tree.get_num_chunks_to_restitch(), 0);
+                }
+
+                // and has at least all chunks of the unbalanced tree
+                for position in unbalanced.iter_chunk_positions() {
+                    assert!(tree.get_chunk_from_position(position).is_some());
+                }
+            }
+        }
+
+        // chunks next to a chunk that subdivides need to be restitched
+        let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+        let tgt = QuadVec::new(0, 0, 2);
+        while tree.prepare_update(&[tgt], 0, &mut |_| TestChunk {}) {
+            let subdivided = tree
+                .iter_chunks_to_deactivate_positions()
+                .collect::<Vec<_>>();
+            tree.do_update();
+
+            let restitch = (0..tree.get_num_chunks_to_restitch())
+                .map(|i| tree.get_position_of_chunk_to_restitch(i))
+                .collect::<Vec<_>>();
+
+            if subdivided.contains(&QuadVec::new(0, 0, 1)) {
+                assert!(restitch.contains(&QuadVec::new(1, 0, 1)));
+                assert!(restitch.contains(&QuadVec::new(0, 1, 1)));
+                assert!(!restitch.contains(&QuadVec::new(1, 1, 1)));
+            }
+
+            tree.complete_update();
+        }
+    }
+
+    #[test]
+    fn balance_set_reuse() {
+        // the balance set kept between updates is the same as one made from scratch
+        let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+        tree.set_balanced(true);
+
+        let a = QuadVec::new(0, 0, 5);
+        let b = QuadVec::new(20, 9, 5);
+        for targets in [
+            vec![a],
+            vec![a],
+            vec![a, b],
+            vec![b],
+            vec![QuadVec::new(31, 31, 5), b],
+            vec![],
+        ] {
+            while tree.prepare_update(&targets, 1, &mut |_| TestChunk {}) {
+                tree.do_update();
+                tree.complete_update();
+            }
+
+            let mut subdivided = HashSet::new();
+            Tree::<TestChunk, QuadVec>::find_balanced_subdivisions(
+                &targets,
+                &1,
+                &mut subdivided,
+                &mut HashSet::new(),
+                &mut HashSet::new(),
+                &mut Vec::new(),
+            );
+            assert_eq!(tree.balance_set, subdivided);
+        }
+    }
+
+    #[test]
+    fn restitch_masks() {
+        let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+        tree.set_balanced(true);
+
+        for tgt in [QuadVec::new(0, 0, 4), QuadVec::new(15, 3, 4)] {
+            while tree.prepare_update(&[tgt], 0, &mut |_| TestChunk {}) {
+                tree.do_update();
+
+                // the masks match what's next to the chunks
+                let restitch = tree.iter_chunks_to_restitch_positions().collect::<Vec<_>>();
+                assert_eq!(restitch.len(), tree.get_num_chunks_to_restitch());
+
+                for (i, position) in restitch.into_iter().enumerate() {
+                    let mask = tree.get_face_mask_of_chunk_to_restitch(i);
+                    assert_eq!(Some(mask), tree.get_face_mask(position));
+
+                    for neighbor in tree.get_neighbors(position).unwrap() {
+                        assert_eq!(
+                            mask.is_coarser(neighbor.direction),
+                            neighbor.depth < position.depth
+                        );
+                        assert_eq!(
+                            mask.is_finer(neighbor.direction),
+                            neighbor.depth > position.depth
+                        );
+                    }
+                }
+
+                tree.complete_update();
+            }
+        }
+
+        // a chunk bordering smaller chunks on the -x face, and a bigger one on the +x face
+        let mut tree = Tree::<TestChunk, QuadVec>::new(0);
+        while tree.prepare_update(&[QuadVec::new(0, 0, 2)], 0, &mut |_| TestChunk {}) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        let mask = tree.get_face_mask(QuadVec::new(1, 0, 1)).unwrap();
+        assert!(mask.is_finer(0) && !mask.is_coarser(0));
+        assert_eq!(mask.finer, 1);
+
+        let mask = tree.get_face_mask(QuadVec::new(1, 0, 2)).unwrap();
+        assert!(mask.is_coarser(1));
+        assert_eq!(mask.coarser, 2);
+        assert_eq!(tree.get_face_mask(QuadVec::new(3, 3, 2)), None);
+    }
+
+    #[test]
+    fn shift_origin() {
+        // chunks remember where they were made, and wether they were made during the shift
+        let mut tree = Tree::<(QuadVec, bool), QuadVec>::new(64);
+        while tree.prepare_update(&[QuadVec::new(6, 6, 3)], 1, &mut |pos| (pos, false)) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        // the chunks that will be moved are the ones in the right half, below the root
+        let num_chunks = tree.get_num_chunks();
+        let moved = (0..num_chunks)
+            .map(|i| tree.get_chunk_position(i))
+            .filter(|pos| pos.depth > 0 && pos.x >= 1 << (pos.depth - 1))
+            .count();
+
+        // move the right half of the tree to the left half
+        let mut created = 0;
+        tree.shift_origin([1, 0], 1, &mut |pos| {
+            created += 1;
+            (pos, true)
+        });
+
+        // the rest is deleted
+        assert_eq!(tree.get_num_chunks_to_delete(), num_chunks - moved);
+        tree.complete_update();
+
+        // the moved chunks are still there, at their new positions
+        assert_eq!(
+            tree.get_chunk_from_position(QuadVec::new(2, 6, 3)),
+            Some(&(QuadVec::new(6, 6, 3), false))
+        );
+
+        let mut made = 0;
+        for i in 0..tree.get_num_chunks() {
+            let (original, new) = *tree.get_chunk(i);
+            let position = tree.get_chunk_position(i);
+            if new {
+                assert_eq!(original, position);
+                made += 1;
+            } else {
+                assert_eq!(original.shift_origin([1, 0], 1), Some(position));
+            }
+        }
+
+        // a new root, and the right half of it as new leaves, which are all reported
+        assert_eq!(made, 3);
+        assert_eq!(created, made);
+        assert_eq!(tree.get_num_chunks_to_activate(), 2);
+        assert_eq!(tree.get_num_chunks_to_deactivate(), 1);
+        assert_eq!(tree.get_position_of_chunk_to_deactivate(0), QuadVec::root());
+        assert_eq!(tree.get_num_chunks(), moved + made);
+
+        // and the tree is the same as one made around the moved target
+        let mut expected = Tree::<(), QuadVec>::new(0);
+        while expected.prepare_update(&[QuadVec::new(2, 6, 3)], 1, &mut |_| ()) {
+            expected.do_update();
+            expected.complete_update();
+        }
+        while tree.prepare_update(&[QuadVec::new(2, 6, 3)], 1, &mut |pos| (pos, true)) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        let mut positions = (0..tree.get_num_chunks())
+            .map(|i| tree.get_chunk_position(i))
+            .collect::<Vec<_>>();
+        let mut expected_positions = (0..expected.get_num_chunks())
+            .map(|i| expected.get_chunk_position(i))
+            .collect::<Vec<_>>();
+        positions.sort_by_key(|pos| (pos.depth, pos.x, pos.y));
+        expected_positions.sort_by_key(|pos| (pos.depth, pos.x, pos.y));
+        assert_eq!(positions, expected_positions);
+
+        // moving by a whole root moves everything out
+        let num_chunks = tree.get_num_chunks();
+        tree.shift_origin([0, -1], 0, &mut |pos| (pos, true));
+        assert!(tree.get_num_chunks_to_delete() >= num_chunks);
+        assert_eq!(tree.get_num_chunks(), 1);
+        tree.complete_update();
+
+        // new nodes are taken from the moved cache first
+        let mut tree = Tree::<(QuadVec, bool), QuadVec>::new(4);
+        while tree.prepare_update(&[QuadVec::new(1, 1, 2)], 0, &mut |pos| (pos, false)) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        tree.insert_into_cache(QuadVec::new(2, 0, 2), (QuadVec::new(2, 0, 2), false));
+
+        let mut created = 0;
+        tree.shift_origin([1, 0], 2, &mut |pos| {
+            created += 1;
+            (pos, true)
+        });
+        assert_eq!(
+            tree.get_chunk_from_position(QuadVec::new(1, 0, 2)),
+            Some(&(QuadVec::new(2, 0, 2), false))
+        );
+        assert_eq!(tree.get_num_cached_chunks(), 0);
+
+        // every chunk that wasn't moved is reported, wether it's new or from the cache
+        assert_eq!(
+            tree.get_num_chunks_to_activate() + tree.get_num_chunks_to_deactivate(),
+            created + 1
+        );
+        assert_eq!(tree.get_num_chunks(), created + 1 + 2);
+    }
+
+    #[test]
+    fn cache_access() {
+        let mut tree = Tree::<QuadVec, QuadVec>::new(4);
+
+        // pre-warm the cache with a chunk, so it's not made again
+        tree.insert_into_cache(QuadVec::new(0, 0, 1), QuadVec::new(9, 9, 9));
+        assert_eq!(tree.get_num_cached_chunks(), 1);
+        while tree.prepare_update(&[QuadVec::new(0, 0, 1)], 0, &mut |pos| pos) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        assert_eq!(
+            tree.get_chunk_from_position(QuadVec::new(0, 0, 1)),
+            Some(&QuadVec::new(9, 9, 9))
+        );
+        assert_eq!(tree.get_num_cached_chunks(), 0);
+
+        // merging puts the children in the cache
+        while tree.prepare_update(&[QuadVec::new(0, 0, 0)], 0, &mut |pos| pos) {
+            tree.do_update();
+            tree.complete_update();
+        }
+        let cached = tree.iter_cached_chunks().collect::<Vec<_>>();
+        assert_eq!(cached.len(), 4);
+        assert_eq!(
+            tree.get_cached_chunk(QuadVec::new(1, 1, 1)),
+            Some(&QuadVec::new(1, 1, 1))
+        );
+
+        // an outdated chunk can be taken out or evicted
+        assert_eq!(
+            tree.take_cached_chunk(QuadVec::new(1, 1, 1)),
+            Some(QuadVec::new(1, 1, 1))
+        );
+        assert!(tree.evict_cached(QuadVec::new(1, 0, 1)));
+        assert!(!tree.evict_cached(QuadVec::new(1, 0, 1)));
+        assert_eq!(tree.get_num_chunks_to_delete(), 1);
+        assert_eq!(tree.get_num_cached_chunks(), 2);
+        tree.complete_update();
+
+        // and a full cache evicts into chunks_to_delete
+        for x in 0..4 {
+            tree.insert_into_cache(QuadVec::new(x, 3, 2), QuadVec::new(x, 3, 2));
+        }
+        assert_eq!(tree.get_num_cached_chunks(), 4);
+        assert_eq!(tree.get_num_chunks_to_delete(), 2);
+    }
+
+    #[test]
+    fn deferred() {
+        // chunks are none until they are done
+        let mut tree = Tree::<Option<QuadVec>, QuadVec>::new(4);
+        tree.set_update_mode(UpdateMode::Deferred);
+
+        // nothing is added until the root is ready
+        assert!(!tree.prepare_update(&[QuadVec::new(0, 0, 2)], 0, &mut |_| None));
+        assert_eq!(tree.get_num_pending_chunks(), 1);
+        assert!(tree.poll_pending(&mut |pos, chunk| {
+            *chunk = Some(pos);
+            true
+        }));
+        assert!(tree.prepare_update(&[QuadVec::new(0, 0, 2)], 0, &mut |_| None));
+        tree.do_update();
+        tree.complete_update();
+
+        // the root stays a leaf while only some children are ready
+        let mut finished = 0;
+        assert!(!tree.prepare_update(&[QuadVec::new(0, 0, 2)], 0, &mut |_| None));
+        assert!(!tree.poll_pending(&mut |pos, chunk| {
+            finished += 1;
+            *chunk = Some(pos);
+            finished < 3
+        }));
+        assert!(!tree.prepare_update(&[QuadVec::new(0, 0, 2)], 0, &mut |_| None));
+        assert_eq!(tree.get_num_chunks_to_deactivate(), 0);
+
+        // not needing the children anymore cancels them, the ones that are done go in the cache
+        assert!(!tree.prepare_update(&[QuadVec::new(0, 0, 0)], 0, &mut |_| None));
+        assert_eq!(tree.get_num_chunks_to_delete(), 2);
+        assert_eq!(tree.get_num_cached_chunks(), 2);
+        assert_eq!(tree.get_num_pending_chunks(), 0);
+        tree.complete_update();
+
+        // and finishing one chunk at a time gets to the same tree as a regular update, without any unfinished chunks
+        let target = QuadVec::new(3, 3, 2);
+        for _ in 0..64 {
+            tree.poll_pending(&mut |pos, chunk| {
+                *chunk = Some(pos);
+                true
+            });
+            if tree.prepare_update(&[target], 0, &mut |_| None) {
+                tree.do_update();
+            }
+            tree.complete_update();
+
+            for i in 0..tree.get_num_chunks() {
+                assert_eq!(*tree.get_chunk(i), Some(tree.get_chunk_position(i)));
+            }
+        }
+
+        let mut expected = Tree::<(), QuadVec>::new(0);
+        while expected.prepare_update(&[target], 0, &mut |_| ()) {
+            expected.do_update();
+            expected.complete_update();
+        }
+        assert_eq!(tree.get_num_chunks(), expected.get_num_chunks());
+        assert_eq!(tree.get_num_pending_chunks(), 0);
+    }
+
+    #[test]
+    fn revalidate() {
+        let mut tree = Tree::<QuadVec, QuadVec>::new(8);
+        while tree.prepare_update(&[QuadVec::new(0, 0, 0)], 0, &mut |pos| pos) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        // the root still needs to be subdivided, so everything is kept
+        assert!(tree.prepare_update(&[QuadVec::new(0, 0, 2)], 0, &mut |pos| pos));
+        let revalidation = tree.revalidate_update(&[QuadVec::new(3, 3, 2)], 0, &mut |_| {
+            panic!("no chunks should be made")
+        });
+        assert_eq!(
+            revalidation,
+            Revalidation {
+                needs_update: true,
+                kept: 4,
+                dropped: Vec::new()
+            }
+        );
+        tree.do_update();
+        tree.complete_update();
+
+        // but a different corner isn't needed anymore
+        let mut created = 0;
+        assert!(tree.prepare_update(&[QuadVec::new(0, 0, 3)], 0, &mut |pos| pos));
+        let revalidation = tree.revalidate_update(&[QuadVec::new(7, 7, 3)], 0, &mut |pos| {
+            created += 1;
+            pos
+        });
+        assert_eq!(revalidation.kept, 0);
+        assert_eq!(revalidation.dropped.len(), 4);
+        assert_eq!(created, 4);
+        assert_eq!(tree.get_num_cached_chunks(), 0);
+        assert!(revalidation
+            .dropped
+            .iter()
+            .all(|(pos, chunk)| chunk == pos && pos.x < 2 && pos.y < 2));
+        assert!(tree
+            .iter_chunks_to_add_and_positions()
+            .all(|(chunk, pos)| *chunk == pos && pos.x >= 2 && pos.y >= 2));
+
+        // cancelling gives back all chunks to add
+        let cancelled = tree.cancel_update();
+        assert_eq!(cancelled.len(), 4);
+        assert_eq!(tree.get_num_chunks_to_add(), 0);
+        assert_eq!(tree.get_num_chunks_to_deactivate(), 0);
+
+        // which can go in the cache
+        for (position, chunk) in revalidation.dropped.into_iter().chain(cancelled) {
+            tree.insert_into_cache(position, chunk);
+        }
+        assert_eq!(tree.get_num_cached_chunks(), 8);
+
+        // so they don't need to be made again
+        assert!(tree.prepare_update(&[QuadVec::new(0, 0, 3)], 0, &mut |_| {
+            panic!("no chunks should be made")
+        }));
+    }
+
+    #[test]
+    fn priority_and_budget() {
+        let target = QuadVec::new(1, 6, 3);
+
+        // without a budget, the nodes closest to the target are subdivided first
+        let mut tree = Tree::<(), QuadVec>::new(0);
+        tree.set_update_mode(UpdateMode::OneLevel);
+        for _ in 0..2 {
+            tree.prepare_update(&[target], 2, &mut |_| ());
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        assert!(tree.prepare_update(&[target], 2, &mut |_| ()));
+        assert!(tree.get_num_chunks_to_deactivate() > 1);
+        let distances = tree
+            .iter_chunks_to_add_positions()
+            .step_by(4)
+            .map(|pos| target.distance(QuadVec::new(pos.x >> 1, pos.y >> 1, pos.depth - 1)))
+            .collect::<Vec<_>>();
+        assert!(distances.windows(2).all(|x| x[0] <= x[1]));
+        assert_eq!(distances[0], 0);
+
+        // with a budget, only that many nodes are subdivided at once
+        let mut expected = Tree::<(), QuadVec>::new(0);
+        while expected.prepare_update(&[target], 2, &mut |_| ()) {
+            expected.do_update();
+            expected.complete_update();
+        }
+
+        for mode in [UpdateMode::OneLevel, UpdateMode::SinglePass] {
+            let mut budgeted = Tree::<(), QuadVec>::new(0);
+            budgeted.set_update_mode(mode);
+            budgeted.set_max_subdivisions(Some(1));
+            let mut updates = 0;
+            while budgeted.prepare_update(&[target], 2, &mut |_| ()) {
+                // single pass mode adds the root in the same update as it's children
+                let root =
+                    (budgeted.get_num_chunks() == 0 && mode == UpdateMode::SinglePass) as usize;
+                assert!(budgeted.get_num_chunks_to_add() <= 4 + root);
+                budgeted.do_update();
+                budgeted.complete_update();
+                updates += 1;
+            }
+
+            // but it still ends up at the same tree
+            assert_eq!(budgeted.get_num_chunks(), expected.get_num_chunks());
+            let root = (mode == UpdateMode::OneLevel) as usize;
+            assert_eq!(updates, root + (expected.get_num_chunks() - 1) / 4);
+        }
+
+        // in single pass mode, new chunks closest to the target are subdivided first as well
+        let mut single = Tree::<(), QuadVec>::new(0);
+        single.set_update_mode(UpdateMode::SinglePass);
+        single.set_max_subdivisions(Some(2));
+        assert!(single.prepare_update(&[target], 2, &mut |_| ()));
+        assert_eq!(single.get_num_chunks_to_add(), 9);
+        let subdivided = single
+            .get_chunks_to_add_slice_mut()
+            .iter()
+            .filter(|x| !x.active)
+            .map(|x| x.position)
+            .collect::<Vec<_>>();
+        assert_eq!(subdivided, [QuadVec::root(), QuadVec::new(0, 1, 1)]);
+    }
+
+    #[test]
+    fn edit() {
+        // chunks count how often they were made
+        let mut tree = Tree::<u32, QuadVec>::new(64);
+        let target = QuadVec::new(0, 0, 3);
+        while tree.prepare_update(&[target], 0, &mut |_| 0) {
+            tree.do_update();
+            tree.complete_update();
+        }
+
+        // put some chunks in the cache, one of which overlaps the edit
+        for x in 0..4 {
+            tree.insert_into_cache(QuadVec::new(x, 7, 3), 0);
+        }
+
+        // edit a single node in the corner, which overlaps one chunk at every depth
+        let edit = QuadVec::new(0, 0, 3);
+        assert!(tree.prepare_edit(edit, edit));
+        let mut edited = tree.iter_chunks_to_edit_positions().collect::<Vec<_>>();
+        edited.sort_by_key(|pos| pos.depth);
+        assert_eq!(
+            edited,
+            [
+                QuadVec::new(0, 0, 0),
+                QuadVec::new(0, 0, 1),
+                QuadVec::new(0, 0, 2),
+                QuadVec::new(0, 0, 3)
+            ]
+        );
+        for chunk in tree.iter_chunks_to_edit_mut() {
+            *chunk += 1;
+        }
+
+        // a bigger edit on the other side gets everything that overlaps it, and the chunks in the cache
+        assert!(tree.prepare_edit(QuadVec::new(2, 6, 3), QuadVec::new(7, 7, 3)));
+        assert_eq!(tree.get_num_chunks_to_edit(), 3);
+        assert_eq!(tree.get_num_chunks_to_delete(), 2);
+        assert_eq!(tree.get_num_cached_chunks(), 2);
+        tree.complete_update();
+        assert_eq!(tree.get_num_chunks_to_edit(), 0);
+
+        assert_eq!(
+            tree.get_chunk_from_position(QuadVec::new(0, 0, 3)),
+            Some(&1)
+        );
+        assert_eq!(
+            tree.get_chunk_from_position(QuadVec::new(1, 0, 3)),
+            Some(&0)
+        );
+    }
+
+    #[test]
+    fn try_update() {
+        let mut tree = Tree::<(), QuadVec>::new(0);
+        let target = QuadVec::new(1, 1, 2);
+
+        // nothing to do yet
+        assert_eq!(tree.try_do_update(), Err(Error::NoUpdatePrepared));
+        assert_eq!(
+            tree.try_get_chunk(0),
+            Err(Error::IndexOutOfBounds { index: 0, len: 0 })
+        );
+
+        // targets that are too deep are rejected
+        let deep = QuadVec {
+            x: 0,
+            y: 0,
+            depth: 61,
+        };
+        assert_eq!(
+            tree.try_prepare_update(&[deep], 1, &mut |_| ()),
+            Err(Error::TooDeep(61))
+        );
+        assert_eq!(
+            tree.try_prepare_update(&[Target::new(deep, 1)], TargetDetail, &mut |_| ()),
+            Err(Error::TooDeep(61))
+        );
+
+        // the update can't be prepared twice, or be skipped
+        assert_eq!(tree.try_prepare_update(&[target], 1, &mut |_| ()), Ok(true));
+        assert_eq!(
+            tree.try_prepare_update(&[target], 1, &mut |_| ()),
+            Err(Error::UpdatePending)
+        );
+        assert_eq!(
+            tree.try_get_position_of_chunk_to_add(0),
+            Ok(QuadVec::root())
+        );
+        assert!(tree.try_get_chunk_to_add_mut(1).is_err());
+
+        // or done twice
+        assert_eq!(tree.try_do_update(), Ok(()));
+        assert_eq!(tree.try_do_update(), Err(Error::NoUpdatePrepared));
+
+        // and needs to be completed before the next one
+        assert_eq!(
+            tree.try_prepare_update(&[target], 1, &mut |_| ()),
+            Err(Error::UpdateNotCompleted)
+        );
+        tree.complete_update();
+
+        // a cancelled update can be prepared again
+        assert_eq!(tree.try_prepare_update(&[target], 1, &mut |_| ()), Ok(true));
+        tree.cancel_update();
+        assert_eq!(tree.try_do_update(), Err(Error::NoUpdatePrepared));
+
+        while tree.try_prepare_update(&[target], 1, &mut |_| ()).unwrap() {
+            tree.try_do_update().unwrap();
+            tree.complete_update();
+        }
+        assert_eq!(tree.try_get_chunk_position(0), Ok(QuadVec::root()));
+        assert_eq!(
+            tree.try_get_chunk_to_deactivate(0),
+            Err(Error::IndexOutOfBounds { index: 0, len: 0 })
+        );
+        assert!(tree.try_get_chunk(tree.get_num_chunks()).is_err());
+    }
 }
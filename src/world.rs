@@ -0,0 +1,447 @@
+//! Mapping between world space and positions in the tree
+//!
+//! QuadVec and OctVec only cover the unit square or cube, so WorldMapping places the root somewhere in the world,
+//! converts signed world positions to positions in the tree, and converts positions in the tree back to world space boxes.
+
+use crate::coords::{LodVecN, OctVec, QuadVec};
+use crate::tree::TREE_MAX_DEPTH;
+
+/// deepest depth a world position can be converted to, which is the same as TREE_MAX_DEPTH
+pub const WORLD_MAX_DEPTH: u8 = TREE_MAX_DEPTH;
+
+/// Error returned when a world position can't be converted to a position in the tree
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum WorldMappingError {
+    /// the position is outside of the root
+    OutOfRange,
+
+    /// the position is NaN or infinite
+    NotFinite,
+
+    /// the requested depth is deeper than WORLD_MAX_DEPTH
+    TooDeep(u8),
+}
+
+impl std::fmt::Display for WorldMappingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange => write!(f, "position is outside of the root"),
+            Self::NotFinite => write!(f, "position is not finite"),
+            Self::TooDeep(d) => write!(
+                f,
+                "depth {} is deeper than the max of {}",
+                d, WORLD_MAX_DEPTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WorldMappingError {}
+
+/// Places the root of a tree in the world.
+///
+/// The root is a square or cube centered on the origin, with the extent as the size of each side.
+/// The axes decide which world axis each axis of the tree follows, and flip makes a tree axis go in the opposite direction.
+///
+/// For a quadtree over the ground of a world where y is up, use a WorldMapping<2> and pass the x and z coordinates.
+/// ```rust
+/// # use lodtree::*;
+/// # use lodtree::coords::{OctVec, QuadVec};
+/// // a 1024 unit wide root, with the origin of the world in the middle
+/// let mapping = WorldMapping::new([0.0, 0.0], 1024.0);
+/// let position = mapping.world_to_quad([-512.0, 511.0], 10).unwrap();
+/// assert_eq!(position, QuadVec::new(0, 1023, 10));
+///
+/// // and outside of it
+/// assert!(mapping.world_to_quad([512.0, 0.0], 10).is_err());
+///
+/// // an octree for a world where z is up, with y going into the screen
+/// let mapping = WorldMapping::new([0.0, 0.0, 0.0], 1024.0).with_axes([0, 2, 1], [false, false, true]);
+/// let position = mapping.world_to_oct([0.0, 100.0, 0.0], 1).unwrap();
+/// assert_eq!(position, OctVec::new(1, 1, 0, 1));
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct WorldMapping<const D: usize> {
+    /// world position of the center of the root
+    pub origin: [f64; D],
+
+    /// size of the root along every axis, in world units
+    pub extent: f64,
+
+    /// which world axis each axis of the tree follows
+    pub axes: [usize; D],
+
+    /// wether each axis of the tree goes in the opposite direction of the world axis
+    pub flip: [bool; D],
+}
+
+impl<const D: usize> WorldMapping<D> {
+    /// creates a new mapping, with the axes of the tree following the world axes
+    /// # Args
+    /// * `origin` The world position of the center of the root
+    /// * `extent` The size of the root along every axis, in world units
+    ///
+    /// Panics if the extent is not positive and finite.
+    #[inline]
+    pub fn new(origin: [f64; D], extent: f64) -> Self {
+        assert!(
+            extent > 0.0 && extent.is_finite(),
+            "extent of a WorldMapping needs to be positive and finite, got {}",
+            extent
+        );
+
+        let mut axes = [0; D];
+        for (i, axis) in axes.iter_mut().enumerate() {
+            *axis = i;
+        }
+
+        Self {
+            origin,
+            extent,
+            axes,
+            flip: [false; D],
+        }
+    }
+
+    /// sets the axis convention of this mapping
+    /// # Args
+    /// * `axes` Which world axis each axis of the tree follows, every world axis needs to be used once
+    /// * `flip` Wether each axis of the tree goes in the opposite direction of the world axis
+    ///
+    /// Panics if the axes don't use every world axis once.
+    #[inline]
+    pub fn with_axes(self, axes: [usize; D], flip: [bool; D]) -> Self {
+        assert!(
+            (0..D).all(|i| axes.contains(&i)),
+            "axes of a WorldMapping need to use every world axis once, got {:?}",
+            axes
+        );
+        Self { axes, flip, ..self }
+    }
+
+    /// converts a world position to coordinates in the tree, from 0 to 1 along each axis.
+    /// Returns an error if the position is outside of the root.
+    pub fn world_to_tree(&self, world: [f64; D]) -> Result<[f64; D], WorldMappingError> {
+        let mut coords = [0.0; D];
+
+        for (i, coord) in coords.iter_mut().enumerate() {
+            let axis = self.axes[i];
+            let offset = (world[axis] - self.origin[axis]) / self.extent;
+
+            if !offset.is_finite() {
+                return Err(WorldMappingError::NotFinite);
+            }
+
+            *coord = if self.flip[i] {
+                0.5 - offset
+            } else {
+                offset + 0.5
+            };
+
+            // the highest edge belongs to the next root over
+            if !(0.0..1.0).contains(coord) {
+                return Err(WorldMappingError::OutOfRange);
+            }
+        }
+
+        Ok(coords)
+    }
+
+    /// converts coordinates in the tree, from 0 to 1 along each axis, back to a world position
+    pub fn tree_to_world(&self, coords: [f64; D]) -> [f64; D] {
+        let mut world = self.origin;
+
+        for (i, coord) in coords.iter().enumerate() {
+            let axis = self.axes[i];
+            let offset = if self.flip[i] {
+                0.5 - coord
+            } else {
+                coord - 0.5
+            };
+
+            world[axis] += offset * self.extent;
+        }
+
+        world
+    }
+
+    /// converts a world position to a position in the tree at the given depth.
+    /// Returns an error if the position is outside of the root, or the depth is too deep.
+    pub fn world_to_lod_vec(
+        &self,
+        world: [f64; D],
+        depth: u8,
+    ) -> Result<LodVecN<D>, WorldMappingError> {
+        if depth > WORLD_MAX_DEPTH {
+            return Err(WorldMappingError::TooDeep(depth));
+        }
+
+        let coords = self.world_to_tree(world)?;
+
+        // scaling factor due to the lod depth
+        let scale_factor = (1u64 << depth) as f64;
+
+        // coords just below 1 can round up, but are still inside the root
+        let max = (1u64 << depth) - 1;
+
+        Ok(LodVecN {
+            pos: coords.map(|x| ((x * scale_factor) as u64).min(max)),
+            depth,
+        })
+    }
+
+    /// converts an integer world position to a position in the tree at the given depth.
+    /// Same as world_to_lod_vec, but for worlds with integer coordinates.
+    ///
+    /// When the origin is on a whole or half unit, and the extent is a whole number of units up to 2^66, this uses integer math,
+    /// so positions far away from the origin don't lose precision. Other mappings convert the position to f64 first.
+    pub fn world_int_to_lod_vec(
+        &self,
+        world: [i64; D],
+        depth: u8,
+    ) -> Result<LodVecN<D>, WorldMappingError> {
+        if depth > WORLD_MAX_DEPTH {
+            return Err(WorldMappingError::TooDeep(depth));
+        }
+
+        // everything is doubled, so the edges of the root are whole numbers if the origin is on a half unit
+        let Some(extent) = exact_int(self.extent).filter(|x| *x <= 1 << 66) else {
+            return self.world_to_lod_vec(world.map(|x| x as f64), depth);
+        };
+
+        let mut pos = [0; D];
+        for (i, pos) in pos.iter_mut().enumerate() {
+            let axis = self.axes[i];
+            let Some(origin) = exact_int(self.origin[axis] * 2.0) else {
+                return self.world_to_lod_vec(world.map(|x| x as f64), depth);
+            };
+
+            // distance to the lowest edge of the root along the tree axis, doubled
+            let world = world[axis] as i128 * 2;
+            let offset = if self.flip[i] {
+                origin + extent - world
+            } else {
+                world - origin + extent
+            };
+
+            // the highest edge belongs to the next root over
+            if !(0..extent * 2).contains(&offset) {
+                return Err(WorldMappingError::OutOfRange);
+            }
+
+            // this fits, as the offset is below 2^67, and the depth at most 60
+            *pos = ((offset << depth) / (extent * 2)) as u64;
+        }
+
+        Ok(LodVecN { pos, depth })
+    }
+
+    /// gets the box in world space a position in the tree covers.
+    /// Returns the lowest and highest corner of the box.
+    /// On flipped axes the highest edge of the box is inside of it, and the lowest edge belongs to the next node over.
+    pub fn lod_vec_to_world_aabb(&self, position: LodVecN<D>) -> ([f64; D], [f64; D]) {
+        let size = 1.0 / (1u64 << position.depth) as f64;
+        let low = self.tree_to_world(position.get_float_coords());
+        let high = self.tree_to_world(position.get_float_coords().map(|x| x + size));
+
+        // flipped axes have their corners swapped
+        let mut min = low;
+        let mut max = high;
+        for i in 0..D {
+            min[i] = low[i].min(high[i]);
+            max[i] = low[i].max(high[i]);
+        }
+
+        (min, max)
+    }
+}
+
+// the float as an integer, if it's a whole number small enough to do math with in an i128
+#[inline]
+fn exact_int(x: f64) -> Option<i128> {
+    (x.fract() == 0.0 && x.abs() <= (1u128 << 100) as f64).then_some(x as i128)
+}
+
+impl WorldMapping<2> {
+    /// converts a world position to a QuadVec at the given depth.
+    /// Returns an error if the position is outside of the root, or the depth is too deep.
+    #[inline]
+    pub fn world_to_quad(&self, world: [f64; 2], depth: u8) -> Result<QuadVec, WorldMappingError> {
+        self.world_to_lod_vec(world, depth).map(Into::into)
+    }
+
+    /// converts an integer world position to a QuadVec at the given depth.
+    /// Returns an error if the position is outside of the root, or the depth is too deep.
+    #[inline]
+    pub fn world_int_to_quad(
+        &self,
+        world: [i64; 2],
+        depth: u8,
+    ) -> Result<QuadVec, WorldMappingError> {
+        self.world_int_to_lod_vec(world, depth).map(Into::into)
+    }
+
+    /// gets the box in world space a QuadVec covers, as the lowest and highest corner.
+    #[inline]
+    pub fn quad_to_world_aabb(&self, position: QuadVec) -> ([f64; 2], [f64; 2]) {
+        self.lod_vec_to_world_aabb(position.into())
+    }
+}
+
+impl WorldMapping<3> {
+    /// converts a world position to an OctVec at the given depth.
+    /// Returns an error if the position is outside of the root, or the depth is too deep.
+    #[inline]
+    pub fn world_to_oct(&self, world: [f64; 3], depth: u8) -> Result<OctVec, WorldMappingError> {
+        self.world_to_lod_vec(world, depth).map(Into::into)
+    }
+
+    /// converts an integer world position to an OctVec at the given depth.
+    /// Returns an error if the position is outside of the root, or the depth is too deep.
+    #[inline]
+    pub fn world_int_to_oct(
+        &self,
+        world: [i64; 3],
+        depth: u8,
+    ) -> Result<OctVec, WorldMappingError> {
+        self.world_int_to_lod_vec(world, depth).map(Into::into)
+    }
+
+    /// gets the box in world space an OctVec covers, as the lowest and highest corner.
+    #[inline]
+    pub fn oct_to_world_aabb(&self, position: OctVec) -> ([f64; 3], [f64; 3]) {
+        self.lod_vec_to_world_aabb(position.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn quad_mapping() {
+        let mapping = WorldMapping::new([100.0, -50.0], 64.0);
+
+        // corners of the root
+        assert_eq!(
+            mapping.world_to_quad([68.0, -82.0], 3),
+            Ok(QuadVec::new(0, 0, 3))
+        );
+        assert_eq!(
+            mapping.world_int_to_quad([131, -19], 3),
+            Ok(QuadVec::new(7, 7, 3))
+        );
+
+        // the highest edge is outside, as is anything past it
+        assert_eq!(
+            mapping.world_to_quad([132.0, -50.0], 3),
+            Err(WorldMappingError::OutOfRange)
+        );
+        assert_eq!(
+            mapping.world_int_to_quad([i64::MIN, 0], 3),
+            Err(WorldMappingError::OutOfRange)
+        );
+        assert_eq!(
+            mapping.world_to_quad([f64::NAN, 0.0], 3),
+            Err(WorldMappingError::NotFinite)
+        );
+        assert_eq!(
+            mapping.world_to_quad([100.0, -50.0], 61),
+            Err(WorldMappingError::TooDeep(61))
+        );
+
+        // and back
+        let (min, max) = mapping.quad_to_world_aabb(QuadVec::new(4, 1, 3));
+        assert_eq!(min, [100.0, -74.0]);
+        assert_eq!(max, [108.0, -66.0]);
+
+        // the deepest level still works
+        let position = mapping.world_to_quad([131.999, -18.001], 60).unwrap();
+        assert_eq!(position.depth, 60);
+        assert!(position.x < 1 << 60 && position.y < 1 << 60);
+    }
+
+    #[test]
+    fn oct_mapping_axes() {
+        // z is up in the world, and tree y goes the opposite way of world y
+        let mapping =
+            WorldMapping::new([0.0, 0.0, 0.0], 16.0).with_axes([0, 2, 1], [false, false, true]);
+
+        let position = mapping.world_to_oct([-8.0, 7.5, 7.5], 4).unwrap();
+        assert_eq!(position, OctVec::new(0, 15, 0, 4));
+
+        // every position ends up in the box of the node it maps to
+        for world in [[1.0, 2.1, 3.0], [-7.9, 0.1, -3.3], [7.99, -7.99, 0.0]] {
+            let position = mapping.world_to_oct(world, 5).unwrap();
+            let (min, max) = mapping.oct_to_world_aabb(position);
+
+            for axis in 0..3 {
+                assert!(min[axis] <= world[axis] && world[axis] < max[axis]);
+                assert_eq!(max[axis] - min[axis], 0.5);
+            }
+        }
+
+        // and tree coords map back to the same world position
+        let coords = mapping.world_to_tree([1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(mapping.tree_to_world(coords), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn int_mapping() {
+        // nodes of 2 units at the deepest level, which f64 can't tell apart this far from the origin
+        let mapping = WorldMapping::new([0.0, 0.0], (1u64 << 61) as f64);
+        let edge = -(1 << 60);
+        assert_eq!(
+            mapping.world_int_to_quad([edge + 2, edge + 200], 60),
+            Ok(QuadVec::new(1, 100, 60))
+        );
+        assert_eq!(
+            mapping.world_int_to_quad([edge + 1, (1 << 60) - 1], 60),
+            Ok(QuadVec::new(0, (1 << 60) - 1, 60))
+        );
+
+        // just outside is outside, instead of being rounded in
+        assert_eq!(
+            mapping.world_int_to_quad([edge - 1, 0], 60),
+            Err(WorldMappingError::OutOfRange)
+        );
+        assert_eq!(
+            mapping.world_int_to_quad([0, 1 << 60], 60),
+            Err(WorldMappingError::OutOfRange)
+        );
+
+        // with an odd extent and flipped axes, it's the same as converting to f64 close to the origin
+        let mapping =
+            WorldMapping::new([3.5, -2.0, 0.0], 9.0).with_axes([2, 0, 1], [true, false, true]);
+        for x in -6..=10 {
+            for y in -7..=3 {
+                let world = [x, y, x - y];
+                let float = world.map(|x| x as f64);
+                assert_eq!(
+                    mapping.world_int_to_lod_vec(world, 3),
+                    mapping.world_to_lod_vec(float, 3)
+                );
+            }
+        }
+
+        // and mappings that aren't on whole units still work
+        let mapping = WorldMapping::new([0.25], 10.0);
+        assert_eq!(
+            mapping.world_int_to_lod_vec([-4], 1),
+            Ok(LodVecN::new([0], 1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "extent")]
+    fn bad_extent() {
+        WorldMapping::new([0.0, 0.0], -1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "axes")]
+    fn bad_axes() {
+        WorldMapping::new([0.0, 0.0, 0.0], 1.0).with_axes([0, 1, 1], [false; 3]);
+    }
+}